@@ -0,0 +1,112 @@
+use super::{Ally, Confusion, Map, Monster, Position, RunState, Viewshed, WantsToMelee};
+use rltk::Point;
+use specs::prelude::*;
+
+/// How far an ally will let the player get ahead before it stops to catch up.
+const FOLLOW_DISTANCE: f32 = 2.5;
+
+/// Allies attack any hostile monster they can see, and otherwise follow the player.
+pub struct AllyAISystem;
+
+impl<'a> System<'a> for AllyAISystem {
+    #[allow(clippy::type_complexity)]
+    type SystemData = (
+        WriteExpect<'a, Map>,
+        ReadExpect<'a, Point>,
+        ReadExpect<'a, RunState>,
+        Entities<'a>,
+        WriteStorage<'a, Viewshed>,
+        ReadStorage<'a, Ally>,
+        ReadStorage<'a, Monster>,
+        WriteStorage<'a, Position>,
+        WriteStorage<'a, WantsToMelee>,
+        WriteStorage<'a, Confusion>,
+    );
+
+    fn run(&mut self, data: Self::SystemData) {
+        let (
+            mut map,
+            player_pos,
+            run_state,
+            entities,
+            mut viewshed,
+            allies,
+            monsters,
+            mut position,
+            mut wants_to_melee,
+            mut confused,
+        ) = data;
+
+        if *run_state != RunState::MonsterTurn {
+            return;
+        }
+
+        let enemies: Vec<(Entity, Point)> = (&entities, &monsters, &position)
+            .join()
+            .map(|(entity, _monster, pos)| (entity, Point::new(pos.x, pos.y)))
+            .collect();
+
+        for (entity, viewshed, _ally, pos) in
+            (&entities, &mut viewshed, &allies, &mut position).join()
+        {
+            if let Some(i_am_confused) = confused.get_mut(entity) {
+                i_am_confused.turns -= 1;
+                if i_am_confused.turns < 1 {
+                    confused.remove(entity);
+                }
+                continue;
+            }
+
+            let my_pos = Point::new(pos.x, pos.y);
+            let nearest_enemy = enemies
+                .iter()
+                .filter(|(_, enemy_pos)| viewshed.visible_tiles.contains(enemy_pos))
+                .min_by(|a, b| {
+                    let da = rltk::DistanceAlg::Pythagoras.distance2d(my_pos, a.1);
+                    let db = rltk::DistanceAlg::Pythagoras.distance2d(my_pos, b.1);
+                    da.partial_cmp(&db).unwrap()
+                });
+
+            let goal = match nearest_enemy {
+                Some((enemy, enemy_pos)) => {
+                    if rltk::DistanceAlg::Pythagoras.distance2d(my_pos, *enemy_pos) < 1.5 {
+                        wants_to_melee
+                            .insert(entity, WantsToMelee { target: *enemy })
+                            .expect("Unable to insert attack");
+                        continue;
+                    }
+                    *enemy_pos
+                }
+                None => {
+                    if rltk::DistanceAlg::Pythagoras.distance2d(my_pos, *player_pos)
+                        < FOLLOW_DISTANCE
+                    {
+                        continue;
+                    }
+                    *player_pos
+                }
+            };
+
+            // The goal may be an enemy's tile, so let the path finder step onto it
+            let goal_idx = map.xy_idx(goal.x, goal.y);
+            let goal_blocked = map.blocked[goal_idx];
+            map.blocked[goal_idx] = false;
+            let path =
+                rltk::a_star_search(map.xy_idx(pos.x, pos.y) as i32, goal_idx as i32, &mut *map);
+            map.blocked[goal_idx] = goal_blocked;
+            if path.success && path.steps.len() > 2 {
+                let next = path.steps[1];
+                if map.blocked[next as usize] {
+                    continue;
+                }
+                let mut idx = map.xy_idx(pos.x, pos.y);
+                map.blocked[idx] = false;
+                pos.x = next % map.width;
+                pos.y = next / map.width;
+                idx = map.xy_idx(pos.x, pos.y);
+                map.blocked[idx] = true;
+                viewshed.dirty = true;
+            }
+        }
+    }
+}
@@ -0,0 +1,310 @@
+use super::{
+    game_log::GameLog, magic_items, map::MAP_COUNT, rect::Rect, save_storage::SaveStore, spawner,
+    DefenseBonus, EquipmentSlot, Equippable, Equipped, Item, ItemCategory, ItemClass, ItemKind,
+    MagicItem, Map, MeleeFireDamage, MeleePowerBonus, Name, Position, Renderable, SerializeMe,
+    Value, Weight,
+};
+use rltk::{RandomNumberGenerator, RGB};
+use serde::{Deserialize, Serialize};
+use specs::prelude::*;
+use specs::saveload::{MarkedBuilder, SimpleMarker};
+use std::io;
+
+/// Chance in 100 that a death leaves bones behind.
+const BONES_CHANCE: i32 = 33;
+
+/// A piece of the dead character's equipment, as their ghost carries it.
+#[derive(Serialize, Deserialize)]
+pub struct BonesItem {
+    pub name: String,
+    /// Bones left by older versions don't say.
+    #[serde(default)]
+    pub kind: Option<String>,
+    pub glyph: u8,
+    pub fg: RGB,
+    pub category: ItemCategory,
+    pub lbs: f32,
+    pub slot: EquipmentSlot,
+    pub power: Option<i32>,
+    pub defense: Option<i32>,
+    pub fire_damage: Option<i32>,
+    pub magic: Option<MagicItem>,
+    pub value: Option<i32>,
+}
+
+/// What's left of a dead character: the level they died on, where they fell and the
+/// gear they had equipped, cursed by their death.
+#[derive(Serialize, Deserialize)]
+pub struct Bones {
+    pub map: Map,
+    pub x: i32,
+    pub y: i32,
+    pub equipment: Vec<BonesItem>,
+}
+
+/// Bones are kept beside the saves, one file per haunted level.
+fn bones_file(depth: i32) -> String {
+    format!("bones/level{}.json", depth)
+}
+
+/// Sometimes leaves the dead player's bones for a later run to find. The first level
+/// is never haunted, and a level is only haunted by one ghost at a time.
+pub fn leave_bones(ecs: &mut World) -> io::Result<()> {
+    let depth = ecs.fetch::<Map>().depth;
+    let storage = ecs.fetch::<SaveStore>().storage.clone();
+    if depth <= 1 || storage.exists(&bones_file(depth)) {
+        return Ok(());
+    }
+    if ecs
+        .write_resource::<RandomNumberGenerator>()
+        .roll_dice(1, 100)
+        > BONES_CHANCE
+    {
+        return Ok(());
+    }
+    write_bones(ecs)
+}
+
+fn write_bones(ecs: &mut World) -> io::Result<()> {
+    let player_entity = *ecs.fetch::<Entity>();
+    let gear: Vec<Entity> = {
+        let entities = ecs.entities();
+        let equipped = ecs.read_storage::<Equipped>();
+        (&entities, &equipped)
+            .join()
+            .filter(|(_, equipped)| equipped.owner == player_entity)
+            .map(|(item, _)| item)
+            .collect()
+    };
+    for item in gear.iter() {
+        magic_items::curse_item(ecs, *item);
+    }
+
+    let pos = ecs
+        .read_storage::<Position>()
+        .get(player_entity)
+        .cloned()
+        .unwrap_or(Position { x: 0, y: 0 });
+    let bones = Bones {
+        map: (*ecs.fetch::<Map>()).clone(),
+        x: pos.x,
+        y: pos.y,
+        equipment: gear
+            .iter()
+            .filter_map(|item| bones_item(ecs, *item))
+            .collect(),
+    };
+
+    let depth = bones.map.depth;
+    let data = serde_json::to_vec(&bones)?;
+    ecs.fetch::<SaveStore>()
+        .storage
+        .write(&bones_file(depth), &data)
+}
+
+fn bones_item(ecs: &World, item: Entity) -> Option<BonesItem> {
+    let render = ecs.read_storage::<Renderable>().get(item).cloned()?;
+    Some(BonesItem {
+        name: ecs.read_storage::<Name>().get(item)?.name.clone(),
+        kind: ecs
+            .read_storage::<ItemKind>()
+            .get(item)
+            .map(|kind| kind.name.clone()),
+        glyph: render.glyph,
+        fg: render.fg,
+        category: ecs.read_storage::<ItemClass>().get(item)?.category,
+        lbs: ecs
+            .read_storage::<Weight>()
+            .get(item)
+            .map_or(0.0, |w| w.lbs),
+        slot: ecs.read_storage::<Equippable>().get(item)?.slot,
+        power: ecs
+            .read_storage::<MeleePowerBonus>()
+            .get(item)
+            .map(|b| b.power),
+        defense: ecs
+            .read_storage::<DefenseBonus>()
+            .get(item)
+            .map(|b| b.defense),
+        fire_damage: ecs
+            .read_storage::<MeleeFireDamage>()
+            .get(item)
+            .map(|f| f.damage),
+        magic: ecs.read_storage::<MagicItem>().get(item).cloned(),
+        value: ecs.read_storage::<Value>().get(item).map(|v| v.gold),
+    })
+}
+
+/// Takes the bones left on `depth`, if any. Bones are used up once found.
+pub fn take_bones(ecs: &World, depth: i32) -> Option<Bones> {
+    let storage = ecs.fetch::<SaveStore>().storage.clone();
+    let data = storage.read(&bones_file(depth)).ok()?;
+    // Bones that can't be read are left for a version of the game that can
+    let mut bones: Bones = serde_json::from_slice(&data).ok()?;
+    storage.remove(&bones_file(depth)).ok()?;
+
+    // Nobody alive has seen this level yet
+    bones.map.revealed_tiles = vec![false; MAP_COUNT];
+    bones.map.visible_tiles = vec![false; MAP_COUNT];
+    bones.map.tile_content = vec![Vec::new(); MAP_COUNT];
+    Some(bones)
+}
+
+/// Raises the ghost of the character the bones belonged to, carrying their gear.
+/// It waits where they fell, unless that's where the player arrives.
+pub fn raise_ghost(ecs: &mut World, bones: &Bones) -> Entity {
+    let (x, y) = if bones.map.rooms[0].intersect(&Rect::new(bones.x, bones.y, 0, 0)) {
+        bones.map.rooms[bones.map.rooms.len() - 1].center()
+    } else {
+        (bones.x, bones.y)
+    };
+    let ghost = spawner::ghost(ecs, x, y);
+
+    for gear in bones.equipment.iter() {
+        let item = ecs
+            .create_entity()
+            .with(Renderable {
+                glyph: gear.glyph,
+                fg: gear.fg,
+                bg: RGB::named(rltk::BLACK),
+                render_order: 2,
+            })
+            .with(Name {
+                name: gear.name.clone(),
+            })
+            .with(Item {})
+            .with(ItemClass {
+                category: gear.category,
+            })
+            .with(Weight { lbs: gear.lbs })
+            .with(Equippable { slot: gear.slot })
+            .with(Equipped {
+                owner: ghost,
+                slot: gear.slot,
+            })
+            .marked::<SimpleMarker<SerializeMe>>()
+            .build();
+        if let Some(power) = gear.power {
+            ecs.write_storage::<MeleePowerBonus>()
+                .insert(item, MeleePowerBonus { power })
+                .expect("Unable to insert power bonus");
+        }
+        if let Some(defense) = gear.defense {
+            ecs.write_storage::<DefenseBonus>()
+                .insert(item, DefenseBonus { defense })
+                .expect("Unable to insert defense bonus");
+        }
+        if let Some(damage) = gear.fire_damage {
+            ecs.write_storage::<MeleeFireDamage>()
+                .insert(item, MeleeFireDamage { damage })
+                .expect("Unable to insert fire damage");
+        }
+        if let Some(magic) = &gear.magic {
+            ecs.write_storage::<MagicItem>()
+                .insert(item, magic.clone())
+                .expect("Unable to insert magic item");
+        }
+        if let Some(gold) = gear.value {
+            ecs.write_storage::<Value>()
+                .insert(item, Value { gold })
+                .expect("Unable to insert value");
+        }
+        if let Some(name) = &gear.kind {
+            ecs.write_storage::<ItemKind>()
+                .insert(item, ItemKind { name: name.clone() })
+                .expect("Unable to insert item kind");
+        }
+    }
+
+    ecs.fetch_mut::<GameLog>().entries.insert(
+        0,
+        "A chill runs down your spine. A ghost haunts this level.".to_string(),
+    );
+    ghost
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::component_registry::register_components;
+    use crate::save_storage::MemoryStorage;
+    use crate::spawner::Spawn;
+    use specs::saveload::SimpleMarkerAllocator;
+
+    /// A player on level 3, wielding a dagger.
+    fn test_world() -> (World, Entity) {
+        let mut ecs = World::new();
+        register_components(&mut ecs);
+        ecs.insert(SimpleMarkerAllocator::<SerializeMe>::new());
+        ecs.insert(SaveStore::new(MemoryStorage::default()));
+        ecs.insert(RandomNumberGenerator::seeded(3));
+        ecs.insert(GameLog {
+            entries: Vec::new(),
+        });
+        ecs.insert(Map::new_map_rooms_and_corridors(3));
+        let (x, y) = ecs.fetch::<Map>().rooms[1].center();
+        let player = spawner::player(&mut ecs, x, y);
+        ecs.insert(player);
+
+        let dagger = spawner::spawn_item(&mut ecs, Spawn::Dagger, x, y, 3).unwrap();
+        ecs.write_storage::<Position>().remove(dagger);
+        ecs.write_storage::<Equipped>()
+            .insert(
+                dagger,
+                Equipped {
+                    owner: player,
+                    slot: EquipmentSlot::Melee,
+                },
+            )
+            .unwrap();
+        (ecs, dagger)
+    }
+
+    #[test]
+    fn bones_carry_cursed_gear() {
+        let (mut ecs, _) = test_world();
+        write_bones(&mut ecs).unwrap();
+
+        let bones = take_bones(&ecs, 3).unwrap();
+        assert_eq!(bones.equipment.len(), 1);
+        let dagger = &bones.equipment[0];
+        assert!(dagger.magic.as_ref().unwrap().enchantment < 0);
+        assert!(dagger.name.starts_with('-') && dagger.name.contains("Dagger"));
+    }
+
+    #[test]
+    fn bones_are_used_up() {
+        let (mut ecs, _) = test_world();
+        write_bones(&mut ecs).unwrap();
+        assert!(take_bones(&ecs, 3).is_some());
+        assert!(take_bones(&ecs, 3).is_none());
+    }
+
+    #[test]
+    fn unreadable_bones_are_left_alone() {
+        let (ecs, _) = test_world();
+        let storage = ecs.fetch::<SaveStore>().storage.clone();
+        storage.write(&bones_file(3), b"not bones").unwrap();
+        assert!(take_bones(&ecs, 3).is_none());
+        assert!(storage.exists(&bones_file(3)));
+    }
+
+    #[test]
+    fn ghosts_carry_the_gear() {
+        let (mut ecs, _) = test_world();
+        write_bones(&mut ecs).unwrap();
+        let bones = take_bones(&ecs, 3).unwrap();
+
+        let ghost = raise_ghost(&mut ecs, &bones);
+        let names = ecs.read_storage::<Name>();
+        assert_eq!(names.get(ghost).unwrap().name, "Ghost");
+        let equipped = ecs.read_storage::<Equipped>();
+        let gear: Vec<&Name> = (&names, &equipped)
+            .join()
+            .filter(|(_, e)| e.owner == ghost)
+            .map(|(n, _)| n)
+            .collect();
+        assert_eq!(gear.len(), 1);
+        assert_eq!(gear[0].name, bones.equipment[0].name);
+    }
+}
@@ -0,0 +1,231 @@
+//! Every component is declared here exactly once, as either saved or transient.
+//! Registering components with the world and writing them to save files are both
+//! driven by these lists, so a new component only needs adding in one place.
+
+use super::components::*;
+use specs::prelude::*;
+use specs::saveload::SimpleMarker;
+use specs::shred::MetaTable;
+use specs::storage::{AnyStorage, MaskedStorage};
+
+/// Passes every component that is written to save files on to `$callback`, after
+/// any leading arguments.
+macro_rules! saved_components {
+    ($callback:ident!($($args:tt)*)) => {
+        $callback!(
+            $($args)*
+            Position,
+            Renderable,
+            Player,
+            Viewshed,
+            Monster,
+            Name,
+            BlocksTile,
+            CombatStats,
+            Item,
+            Consumable,
+            Ranged,
+            InflictsDamage,
+            AreaOfEffect,
+            Confusion,
+            ProvidesHealing,
+            InBackpack,
+            SerializationHelper,
+            Equippable,
+            Equipped,
+            MeleePowerBonus,
+            DefenseBonus,
+            HungerClock,
+            ProvidesFood,
+            MagicMapper,
+            MagicItem,
+            MeleeFireDamage,
+            Charges,
+            Recharger,
+            Weight,
+            Encumbrance,
+            ItemClass,
+            Container,
+            InContainer,
+            Mana,
+            Spell,
+            KnownSpell,
+            TeachesSpell,
+            ProvidesStatus,
+            StatusEffects,
+            Invisible,
+            Teleports,
+            Trap,
+            CastsBlink,
+            CraftingStation,
+            KnownRecipes,
+            TeachesRecipe,
+            Perishable,
+            Corpse,
+            Undead,
+            Necromancer,
+            Ally,
+            Charms,
+            Purse,
+            Gold,
+            Value,
+            Shopkeeper,
+            ForSale,
+            Npc,
+            ItemKind
+        )
+    };
+}
+
+/// Passes every component that is never saved on to `$callback`. Intents are acted
+/// on within the turn they're made and particles fade within moments, so neither is
+/// around when the game is saved.
+macro_rules! transient_components {
+    ($callback:ident!($($args:tt)*)) => {
+        $callback!(
+            $($args)*
+            // marks which entities get saved, rather than being saved itself
+            SimpleMarker<SerializeMe>,
+            SufferDamage,
+            WantsToMelee,
+            WantsToPickupItem,
+            WantsToUseItem,
+            WantsToDropItem,
+            WantsToRemoveItem,
+            WantsToStashItem,
+            WantsToTeleport,
+            ParticleLifetime
+        )
+    };
+}
+
+macro_rules! register_each {
+    ($ecs:expr, $( $type:ty ),*) => {
+        $(
+            $ecs.register::<$type>();
+        )*
+    };
+}
+
+macro_rules! count_each {
+    ($( $type:ty ),*) => {
+        [$( stringify!($type) ),*].len()
+    };
+}
+
+/// Lists each component's name alongside whether its storage is in `$ecs`.
+macro_rules! declared_each {
+    ($ecs:expr, $( $type:ty ),*) => {
+        vec![$( (stringify!($type), $ecs.has_value::<MaskedStorage<$type>>()) ),*]
+    };
+}
+
+pub fn register_components(ecs: &mut World) {
+    saved_components!(register_each!(ecs,));
+    transient_components!(register_each!(ecs,));
+}
+
+/// Panics if anything has registered a component that isn't declared above, such as
+/// a system using a new component. Left alone it would quietly be dropped from saves.
+pub fn check_components_declared(ecs: &World) {
+    let mut declared = saved_components!(declared_each!(ecs,));
+    declared.extend(transient_components!(declared_each!(ecs,)));
+    let registered = ecs.fetch::<MetaTable<dyn AnyStorage>>().iter(ecs).count();
+    let problems = declaration_problems(&declared, registered);
+    if !problems.is_empty() {
+        panic!(
+            "declare every component in component_registry.rs once, as saved or transient: {}",
+            problems.join("; ")
+        );
+    }
+}
+
+/// Compares the declared components, and whether each is registered, against the
+/// number of storages in the world. The world can't name its storages, so any that
+/// aren't declared can only be counted.
+fn declaration_problems(declared: &[(&str, bool)], registered: usize) -> Vec<String> {
+    let mut problems = Vec::new();
+
+    let mut names: Vec<&str> = declared.iter().map(|(name, _)| *name).collect();
+    names.sort_unstable();
+    let mut twice: Vec<&str> = names
+        .windows(2)
+        .filter(|pair| pair[0] == pair[1])
+        .map(|pair| pair[0])
+        .collect();
+    twice.dedup();
+    if !twice.is_empty() {
+        problems.push(format!("declared more than once: {}", twice.join(", ")));
+    }
+
+    let mut missing: Vec<&str> = declared
+        .iter()
+        .filter(|(_, is_registered)| !is_registered)
+        .map(|(name, _)| *name)
+        .collect();
+    missing.sort_unstable();
+    missing.dedup();
+    if !missing.is_empty() {
+        problems.push(format!(
+            "declared but not registered: {}",
+            missing.join(", ")
+        ));
+    }
+
+    let mut found: Vec<&str> = declared
+        .iter()
+        .filter(|(_, is_registered)| *is_registered)
+        .map(|(name, _)| *name)
+        .collect();
+    found.sort_unstable();
+    found.dedup();
+    if registered > found.len() {
+        problems.push(format!(
+            "{} registered but not declared",
+            registered - found.len()
+        ));
+    }
+
+    problems
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn every_component_is_declared() {
+        let mut ecs = World::new();
+        let mut systems = crate::build_systems();
+        register_components(&mut ecs);
+        systems.setup(&mut ecs);
+        check_components_declared(&ecs);
+    }
+
+    #[derive(Component)]
+    struct Undeclared {}
+
+    #[test]
+    #[should_panic(expected = "declare every component")]
+    fn undeclared_components_are_caught() {
+        let mut ecs = World::new();
+        register_components(&mut ecs);
+        ecs.register::<Undeclared>();
+        check_components_declared(&ecs);
+    }
+
+    #[test]
+    fn duplicates_are_not_mistaken_for_the_missing() {
+        // Three declarations for three storages, but one is declared twice
+        let declared = [("Position", true), ("Position", true), ("Name", true)];
+        let problems = declaration_problems(&declared, 3);
+        assert_eq!(
+            problems,
+            vec![
+                "declared more than once: Position".to_string(),
+                "1 registered but not declared".to_string(),
+            ]
+        );
+        assert!(declaration_problems(&declared[1..], 2).is_empty());
+    }
+}
@@ -11,6 +11,8 @@ pub struct SerializeMe;
 pub struct SerializationHelper {
     pub map: super::map::Map,
     pub game_log: super::game_log::GameLog,
+    pub turn_counter: super::game_log::TurnCounter,
+    pub run_stats: super::game_log::RunStats,
 }
 
 #[derive(Component, ConvertSaveload, Clone)]
@@ -61,9 +63,21 @@ pub struct WantsToMelee {
     pub target: Entity,
 }
 
-#[derive(Component, ConvertSaveload, Clone)]
+/// Where damage came from, so a death can be put down to something.
+#[derive(PartialEq, Copy, Clone)]
+pub enum DamageSource {
+    /// A blow, spell or item used by this entity.
+    Attacker(Entity),
+    Hunger,
+    Sickness,
+    Choking,
+}
+
+/// Never saved, so it needn't be serializable.
+#[derive(Component, Clone)]
 pub struct SufferDamage {
     pub amount: i32,
+    pub source: DamageSource,
 }
 
 #[derive(Component, Serialize, Deserialize, Clone)]
@@ -130,43 +144,12 @@ pub struct Equippable {
     pub slot: EquipmentSlot,
 }
 
-#[derive(Component, Clone)]
+#[derive(Component, ConvertSaveload, Clone)]
 pub struct Equipped {
     pub owner: Entity,
     pub slot: EquipmentSlot,
 }
 
-// Equipped wrapper
-#[derive(Serialize, Deserialize, Clone)]
-pub struct EquippedData<M>(M, EquipmentSlot);
-
-impl<M: Marker + Serialize> ConvertSaveload<M> for Equipped
-where
-    for<'de> M: Deserialize<'de>,
-{
-    type Data = EquippedData<M>;
-    type Error = NoError;
-
-    fn convert_into<F>(&self, mut ids: F) -> Result<Self::Data, Self::Error>
-    where
-        F: FnMut(Entity) -> Option<M>,
-    {
-        let marker = ids(self.owner).unwrap();
-        Ok(EquippedData(marker, self.slot))
-    }
-
-    fn convert_from<F>(data: Self::Data, mut ids: F) -> Result<Self, Self::Error>
-    where
-        F: FnMut(M) -> Option<Entity>,
-    {
-        let entity = ids(data.0).unwrap();
-        Ok(Equipped {
-            owner: entity,
-            slot: data.1,
-        })
-    }
-}
-
 #[derive(Component, ConvertSaveload, Clone)]
 pub struct MeleePowerBonus {
     pub power: i32,
@@ -198,11 +181,311 @@ pub enum HungerState {
 #[derive(Component, Serialize, Deserialize, Clone)]
 pub struct HungerClock {
     pub state: HungerState,
-    pub duration: i32,
+    /// Counts down every turn; the state is whichever band this falls in.
+    pub nutrition: i32,
 }
 
 #[derive(Component, Debug, Serialize, Deserialize, Clone)]
-pub struct ProvidesFood {}
+pub struct ProvidesFood {
+    pub nutrition: i32,
+}
 
 #[derive(Component, Debug, Serialize, Deserialize, Clone)]
 pub struct MagicMapper {}
+
+#[derive(PartialEq, Copy, Clone, Serialize, Deserialize)]
+pub enum ItemAffix {
+    Flaming,
+    Keen,
+    Warding,
+    Might,
+}
+
+#[derive(Component, Serialize, Deserialize, Clone)]
+pub struct MagicItem {
+    pub enchantment: i32,
+    pub prefix: Option<ItemAffix>,
+    pub suffix: Option<ItemAffix>,
+}
+
+#[derive(Component, ConvertSaveload, Clone)]
+pub struct MeleeFireDamage {
+    pub damage: i32,
+}
+
+#[derive(Component, Serialize, Deserialize, Clone)]
+pub struct Charges {
+    pub current: i32,
+    pub max: i32,
+}
+
+#[derive(Component, Debug, Serialize, Deserialize, Clone)]
+pub struct Recharger {}
+
+#[derive(Component, Serialize, Deserialize, Clone)]
+pub struct Weight {
+    pub lbs: f32,
+}
+
+#[derive(Component, Serialize, Deserialize, Clone)]
+pub struct Encumbrance {
+    pub carried: f32,
+    pub capacity: f32,
+}
+
+impl Encumbrance {
+    /// Combat penalty for carrying more than capacity: one point, plus another for every
+    /// further quarter of capacity.
+    pub fn penalty(&self) -> i32 {
+        if self.carried <= self.capacity {
+            0
+        } else {
+            1 + ((self.carried - self.capacity) / (self.capacity * 0.25)) as i32
+        }
+    }
+
+    /// Nobody can carry more than twice their comfortable load.
+    pub fn hard_limit(&self) -> f32 {
+        self.capacity * 2.0
+    }
+}
+
+#[derive(PartialEq, Eq, Hash, Copy, Clone, Serialize, Deserialize)]
+pub enum ItemCategory {
+    Food,
+    Potion,
+    Scroll,
+    Wand,
+    Weapon,
+    Armour,
+    Container,
+    Book,
+    Gold,
+}
+
+#[derive(Component, Serialize, Deserialize, Clone)]
+pub struct ItemClass {
+    pub category: ItemCategory,
+}
+
+/// What an item is, whatever it's called now: "Rations" even once they've gone
+/// rotten, "Dagger" however it's enchanted. Recipes go by this.
+#[derive(Component, Serialize, Deserialize, Clone)]
+pub struct ItemKind {
+    pub name: String,
+}
+
+#[derive(Component, Serialize, Deserialize, Clone)]
+pub struct Container {}
+
+#[derive(Component, ConvertSaveload, Clone)]
+pub struct InContainer {
+    pub container: Entity,
+}
+
+#[derive(Component, ConvertSaveload, Clone)]
+pub struct WantsToStashItem {
+    pub item: Entity,
+    pub container: Entity,
+}
+
+#[derive(Component, Serialize, Deserialize, Clone)]
+pub struct Mana {
+    pub current: i32,
+    pub max: i32,
+    pub regen_timer: i32,
+}
+
+#[derive(PartialEq, Copy, Clone, Serialize, Deserialize)]
+pub enum SpellKind {
+    MagicMissile,
+    Fireball,
+    Confusion,
+    Healing,
+}
+
+#[derive(Component, Serialize, Deserialize, Clone)]
+pub struct Spell {
+    pub kind: SpellKind,
+    pub mana_cost: i32,
+}
+
+#[derive(Component, ConvertSaveload, Clone)]
+pub struct KnownSpell {
+    pub owner: Entity,
+}
+
+#[derive(Component, Serialize, Deserialize, Clone)]
+pub struct TeachesSpell {
+    pub spell: SpellKind,
+}
+
+#[derive(PartialEq, Eq, Copy, Clone, Serialize, Deserialize)]
+pub enum StatusEffect {
+    Strength,
+    Telepathy,
+    DetectItems,
+    Speed,
+    Levitation,
+    SeeInvisible,
+    Sick,
+}
+
+#[derive(Component, Serialize, Deserialize, Clone)]
+pub struct ProvidesStatus {
+    pub effect: StatusEffect,
+    pub turns: i32,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct ActiveStatus {
+    pub effect: StatusEffect,
+    pub turns: i32,
+}
+
+/// Timed effects currently affecting an entity, counted down each of its turns.
+#[derive(Component, Serialize, Deserialize, Clone, Default)]
+pub struct StatusEffects {
+    pub active: Vec<ActiveStatus>,
+}
+
+impl StatusEffects {
+    pub fn has(&self, effect: StatusEffect) -> bool {
+        self.active.iter().any(|s| s.effect == effect)
+    }
+
+    /// Starts an effect, or extends it if it is already running.
+    pub fn add(&mut self, effect: StatusEffect, turns: i32) {
+        match self.active.iter_mut().find(|s| s.effect == effect) {
+            Some(status) => status.turns += turns,
+            None => self.active.push(ActiveStatus { effect, turns }),
+        }
+    }
+
+    pub fn turns_left(&self, effect: StatusEffect) -> i32 {
+        self.active
+            .iter()
+            .find(|s| s.effect == effect)
+            .map_or(0, |s| s.turns)
+    }
+}
+
+#[derive(Component, Serialize, Deserialize, Clone)]
+pub struct Invisible {}
+
+#[derive(PartialEq, Copy, Clone, Serialize, Deserialize)]
+pub enum TeleportKind {
+    Random,
+    Blink,
+    Level,
+}
+
+/// Teleports whoever uses the item, or whoever steps onto the trap.
+#[derive(Component, Serialize, Deserialize, Clone)]
+pub struct Teleports {
+    pub kind: TeleportKind,
+}
+
+#[derive(PartialEq, Copy, Clone, Serialize, Deserialize)]
+pub enum TeleportDestination {
+    Random,
+    Tile { x: i32, y: i32 },
+    Level,
+}
+
+#[derive(Component, Serialize, Deserialize, Clone)]
+pub struct WantsToTeleport {
+    pub destination: TeleportDestination,
+}
+
+#[derive(Component, Serialize, Deserialize, Clone)]
+pub struct Trap {}
+
+#[derive(Component, Serialize, Deserialize, Clone)]
+pub struct CastsBlink {}
+
+/// A fixed workbench, such as an alchemy station, that some recipes need nearby.
+#[derive(Component, Serialize, Deserialize, Clone)]
+pub struct CraftingStation {}
+
+/// Recipes the entity has learned during play.
+#[derive(Component, Serialize, Deserialize, Clone, Default)]
+pub struct KnownRecipes {
+    pub recipes: Vec<String>,
+}
+
+#[derive(Component, Serialize, Deserialize, Clone)]
+pub struct TeachesRecipe {
+    pub recipe: String,
+}
+
+/// Food that goes off; once `freshness` runs out it is rotten.
+#[derive(Component, Serialize, Deserialize, Clone)]
+pub struct Perishable {
+    pub freshness: i32,
+}
+
+impl Perishable {
+    pub fn is_rotten(&self) -> bool {
+        self.freshness < 1
+    }
+}
+
+/// The remains of a dead creature, remembering what it was.
+#[derive(Component, Serialize, Deserialize, Clone)]
+pub struct Corpse {
+    pub species: String,
+}
+
+/// Undead creatures leave no corpse behind.
+#[derive(Component, Serialize, Deserialize, Clone)]
+pub struct Undead {}
+
+/// Raises corpses it can see as zombies.
+#[derive(Component, Serialize, Deserialize, Clone)]
+pub struct Necromancer {}
+
+/// Fights alongside the player instead of against them.
+#[derive(Component, Serialize, Deserialize, Clone)]
+pub struct Ally {}
+
+/// Turns a hostile target into an ally.
+#[derive(Component, Serialize, Deserialize, Clone)]
+pub struct Charms {}
+
+/// The coins someone is carrying.
+#[derive(Component, Serialize, Deserialize, Clone)]
+pub struct Purse {
+    pub gold: i32,
+}
+
+/// A pile of coins; picking it up empties it into the collector's purse.
+#[derive(Component, Serialize, Deserialize, Clone)]
+pub struct Gold {
+    pub amount: i32,
+}
+
+/// What a shopkeeper charges for an item. They buy it back for half.
+#[derive(Component, Serialize, Deserialize, Clone)]
+pub struct Value {
+    pub gold: i32,
+}
+
+/// Trades with the player when bumped, until they are robbed.
+#[derive(Component, Serialize, Deserialize, Clone)]
+pub struct Shopkeeper {}
+
+/// Shop stock, which must be paid for before it is picked up.
+#[derive(Component, ConvertSaveload, Clone)]
+pub struct ForSale {
+    pub shopkeeper: Entity,
+}
+
+/// A peaceful character who talks when bumped into, until provoked.
+#[derive(Component, Serialize, Deserialize, Clone)]
+pub struct Npc {
+    /// Name of the conversation in the dialogue file.
+    pub conversation: String,
+    /// Node the next conversation starts at, if not the first.
+    pub start: Option<String>,
+}
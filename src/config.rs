@@ -0,0 +1,85 @@
+use super::components::ItemCategory;
+use super::save_storage::SaveStorage;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::io;
+
+/// Kept beside the saves.
+const CONFIG_FILE: &str = "config.json";
+
+/// Where the config was kept before it moved in with the saves.
+#[cfg(not(target_arch = "wasm32"))]
+const OLD_CONFIG_PATH: &str = "./config.json";
+
+/// Player preferences that persist between runs, independent of any save game.
+#[derive(Serialize, Deserialize, Clone, Default)]
+#[serde(default)]
+pub struct GameConfig {
+    pub auto_pickup: HashSet<ItemCategory>,
+    pub autosave: AutosaveConfig,
+    pub save_encoding: SaveEncoding,
+}
+
+/// How saves are written. They load whichever way they were written.
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Default)]
+pub enum SaveEncoding {
+    #[default]
+    CompressedBinary,
+    Binary,
+    /// Readable JSON, for debugging.
+    Json,
+}
+
+/// When the game saves itself, so a crash doesn't lose the whole run.
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct AutosaveConfig {
+    pub on_level_change: bool,
+    /// Autosave after every this many turns; 0 turns it off.
+    pub every_turns: i32,
+}
+
+impl Default for AutosaveConfig {
+    fn default() -> AutosaveConfig {
+        AutosaveConfig {
+            on_level_change: true,
+            every_turns: 100,
+        }
+    }
+}
+
+impl GameConfig {
+    /// Reads the config, falling back to defaults if it is missing or unreadable.
+    pub fn load(storage: &dyn SaveStorage) -> GameConfig {
+        let data = storage.read(CONFIG_FILE);
+        #[cfg(not(target_arch = "wasm32"))]
+        let data = data.or_else(|_| std::fs::read(OLD_CONFIG_PATH));
+        data.ok()
+            .and_then(|data| serde_json::from_slice(&data).ok())
+            .unwrap_or_default()
+    }
+
+    pub fn save(&self, storage: &dyn SaveStorage) -> io::Result<()> {
+        let data = serde_json::to_vec_pretty(self)?;
+        storage.write(CONFIG_FILE, &data)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::save_storage::MemoryStorage;
+
+    #[test]
+    fn config_is_kept_with_the_saves() {
+        let storage = MemoryStorage::default();
+        let mut config = GameConfig::default();
+        config.auto_pickup.insert(ItemCategory::Potion);
+        config.save_encoding = SaveEncoding::Json;
+        config.save(&storage).unwrap();
+
+        let loaded = GameConfig::load(&storage);
+        assert!(loaded.auto_pickup.contains(&ItemCategory::Potion));
+        assert!(loaded.save_encoding == SaveEncoding::Json);
+    }
+}
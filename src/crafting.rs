@@ -0,0 +1,335 @@
+use super::{
+    game_log::{with_article, GameLog},
+    spawner,
+    spawner::Spawn,
+    CraftingStation, InBackpack, ItemKind, KnownRecipes, Map, Name, Position,
+};
+use rltk::Point;
+use serde::{Deserialize, Serialize};
+use specs::prelude::*;
+
+/// A way of combining inventory items into a new one. Recipes live in
+/// `resources/recipes.json`, which is read as the game starts, so adding one needs
+/// no code and no rebuild.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Recipe {
+    pub name: String,
+    /// Kinds of item consumed by the recipe, named as they are when first found, so
+    /// rotten or enchanted ones still count. List a kind twice to need two of it.
+    pub ingredients: Vec<String>,
+    /// Name of the station the player must stand on or next to, if any.
+    #[serde(default)]
+    pub station: Option<String>,
+    pub result: Spawn,
+    /// Known from the start, rather than learned from alchemist's notes.
+    #[serde(default)]
+    pub known: bool,
+}
+
+pub struct RecipeBook {
+    pub recipes: Vec<Recipe>,
+}
+
+/// Where recipes are read from. The game keeps a copy of the ones it shipped with, in
+/// case the file is missing or can't be read.
+#[cfg(not(target_arch = "wasm32"))]
+const RECIPE_FILE: &str = "./resources/recipes.json";
+
+#[cfg(not(target_arch = "wasm32"))]
+fn read_recipe_file() -> Option<Vec<Recipe>> {
+    let data = std::fs::read(RECIPE_FILE).ok()?;
+    serde_json::from_slice(&data).ok()
+}
+
+/// The web build has no files to read.
+#[cfg(target_arch = "wasm32")]
+fn read_recipe_file() -> Option<Vec<Recipe>> {
+    None
+}
+
+impl RecipeBook {
+    pub fn load() -> RecipeBook {
+        match read_recipe_file() {
+            Some(recipes) => RecipeBook { recipes },
+            None => RecipeBook::built_in(),
+        }
+    }
+
+    fn built_in() -> RecipeBook {
+        let data = include_str!("../resources/recipes.json");
+        RecipeBook {
+            recipes: serde_json::from_str(data).expect("Unable to parse recipes.json"),
+        }
+    }
+
+    pub fn find(&self, name: &str) -> Option<&Recipe> {
+        self.recipes.iter().find(|recipe| recipe.name == name)
+    }
+}
+
+pub fn is_known(recipe: &Recipe, known: Option<&KnownRecipes>) -> bool {
+    recipe.known || known.is_some_and(|known| known.recipes.contains(&recipe.name))
+}
+
+/// Picks one backpack item per ingredient, or `None` if something is missing.
+fn find_ingredients(ecs: &World, recipe: &Recipe) -> Option<Vec<Entity>> {
+    let player_entity = ecs.fetch::<Entity>();
+    let entities = ecs.entities();
+    let backpack = ecs.read_storage::<InBackpack>();
+    let kinds = ecs.read_storage::<ItemKind>();
+
+    let mut chosen: Vec<Entity> = Vec::new();
+    for ingredient in recipe.ingredients.iter() {
+        let item = (&entities, &backpack, &kinds)
+            .join()
+            .find(|(item, pack, kind)| {
+                pack.owner == *player_entity && kind.name == *ingredient && !chosen.contains(item)
+            })
+            .map(|(item, _, _)| item)?;
+        chosen.push(item);
+    }
+    Some(chosen)
+}
+
+fn near_station(ecs: &World, station: &str) -> bool {
+    let player_pos = ecs.fetch::<Point>();
+    let stations = ecs.read_storage::<CraftingStation>();
+    let names = ecs.read_storage::<Name>();
+    let positions = ecs.read_storage::<Position>();
+
+    (&stations, &names, &positions)
+        .join()
+        .any(|(_, name, pos)| {
+            name.name == station
+                && i32::abs(pos.x - player_pos.x) <= 1
+                && i32::abs(pos.y - player_pos.y) <= 1
+        })
+}
+
+pub fn can_craft(ecs: &World, recipe: &Recipe) -> bool {
+    let at_station = match &recipe.station {
+        Some(station) => near_station(ecs, station),
+        None => true,
+    };
+    at_station && find_ingredients(ecs, recipe).is_some()
+}
+
+/// Consumes the ingredients of the named recipe and puts the result in the player's
+/// backpack. Returns false (and explains why) if it can't be made right now.
+pub fn craft(ecs: &mut World, recipe_name: &str) -> bool {
+    let recipe = match ecs.fetch::<RecipeBook>().find(recipe_name) {
+        Some(recipe) => recipe.clone(),
+        None => return false,
+    };
+
+    if let Some(station) = &recipe.station {
+        if !near_station(ecs, station) {
+            ecs.fetch_mut::<GameLog>().entries.insert(
+                0,
+                format!("You need to be at {} to make that.", with_article(station)),
+            );
+            return false;
+        }
+    }
+
+    let ingredients = match find_ingredients(ecs, &recipe) {
+        Some(ingredients) => ingredients,
+        None => {
+            ecs.fetch_mut::<GameLog>()
+                .entries
+                .insert(0, "You don't have the ingredients for that.".to_string());
+            return false;
+        }
+    };
+
+    let player_entity = *ecs.fetch::<Entity>();
+    let player_pos = *ecs.fetch::<Point>();
+    let depth = ecs.fetch::<Map>().depth;
+    let item = match spawner::spawn_item(ecs, recipe.result, player_pos.x, player_pos.y, depth) {
+        Some(item) => item,
+        None => {
+            ecs.fetch_mut::<GameLog>()
+                .entries
+                .insert(0, format!("The {} doesn't turn out.", recipe.name));
+            return false;
+        }
+    };
+    ecs.delete_entities(&ingredients)
+        .expect("Unable to consume ingredients");
+    ecs.write_storage::<Position>().remove(item);
+    ecs.write_storage::<InBackpack>()
+        .insert(
+            item,
+            InBackpack {
+                owner: player_entity,
+            },
+        )
+        .expect("Unable to insert backpack entry");
+
+    ecs.fetch_mut::<GameLog>()
+        .entries
+        .insert(0, format!("You make {}.", with_article(&recipe.name)));
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::component_registry::register_components;
+    use crate::{Monster, SerializeMe};
+    use rltk::RandomNumberGenerator;
+    use specs::saveload::SimpleMarkerAllocator;
+
+    fn test_world() -> World {
+        let mut ecs = World::new();
+        register_components(&mut ecs);
+        ecs.insert(SimpleMarkerAllocator::<SerializeMe>::new());
+        ecs.insert(RandomNumberGenerator::seeded(5));
+        ecs.insert(RecipeBook::load());
+        ecs
+    }
+
+    #[test]
+    fn recipes_use_things_that_exist() {
+        let mut ecs = test_world();
+        for spawn in Spawn::ALL.iter() {
+            spawner::spawn_entity(&mut ecs, *spawn, 1, 1, 5);
+        }
+        // Monsters only become ingredients once they're dead
+        let species: Vec<String> = (&ecs.read_storage::<Monster>(), &ecs.read_storage::<Name>())
+            .join()
+            .map(|(_, name)| name.name.clone())
+            .collect();
+        for species in species.iter() {
+            spawner::corpse(&mut ecs, 1, 1, species);
+        }
+        let items: Vec<String> = ecs
+            .read_storage::<ItemKind>()
+            .join()
+            .map(|kind| kind.name.clone())
+            .collect();
+        let stations: Vec<String> = (
+            &ecs.read_storage::<CraftingStation>(),
+            &ecs.read_storage::<Name>(),
+        )
+            .join()
+            .map(|(_, name)| name.name.clone())
+            .collect();
+
+        let book = RecipeBook::load();
+        for recipe in book.recipes.iter() {
+            for ingredient in recipe.ingredients.iter() {
+                assert!(
+                    items.contains(ingredient),
+                    "{} needs {}, which doesn't exist",
+                    recipe.name,
+                    ingredient
+                );
+            }
+            if let Some(station) = &recipe.station {
+                assert!(
+                    stations.contains(station),
+                    "{} is made at {}, which doesn't exist",
+                    recipe.name,
+                    station
+                );
+            }
+            assert!(
+                spawner::spawn_item(&mut ecs, recipe.result, 1, 1, 5).is_some(),
+                "{} makes something that isn't an item",
+                recipe.name
+            );
+        }
+    }
+
+    #[test]
+    fn failed_crafting_keeps_the_ingredients() {
+        let mut ecs = test_world();
+        ecs.insert(GameLog {
+            entries: Vec::new(),
+        });
+        ecs.insert(Map::new_map_rooms_and_corridors(1));
+        ecs.insert(Point::new(1, 1));
+        let player = spawner::player(&mut ecs, 1, 1);
+        ecs.insert(player);
+        let potion = spawner::spawn_item(&mut ecs, Spawn::HealthPotion, 1, 1, 1).unwrap();
+        ecs.write_storage::<Position>().remove(potion);
+        ecs.write_storage::<InBackpack>()
+            .insert(potion, InBackpack { owner: player })
+            .unwrap();
+        ecs.insert(RecipeBook {
+            recipes: vec![Recipe {
+                name: "Goblin".to_string(),
+                ingredients: vec!["Health Potion".to_string()],
+                station: None,
+                result: Spawn::Goblin,
+                known: true,
+            }],
+        });
+
+        assert!(!craft(&mut ecs, "Goblin"));
+        ecs.maintain();
+        assert!(ecs.is_alive(potion));
+        assert_eq!(
+            ecs.fetch::<GameLog>().entries[0],
+            "The Goblin doesn't turn out."
+        );
+    }
+
+    #[test]
+    fn spoiled_ingredients_still_count() {
+        let mut ecs = test_world();
+        ecs.insert(GameLog {
+            entries: Vec::new(),
+        });
+        ecs.insert(Map::new_map_rooms_and_corridors(1));
+        ecs.insert(Point::new(1, 1));
+        let player = spawner::player(&mut ecs, 1, 1);
+        ecs.insert(player);
+        let rations = spawner::spawn_item(&mut ecs, Spawn::Rations, 1, 1, 1).unwrap();
+        let corpse = spawner::corpse(&mut ecs, 1, 1, "Orc");
+        for item in [rations, corpse].iter() {
+            ecs.write_storage::<Position>().remove(*item);
+            ecs.write_storage::<InBackpack>()
+                .insert(*item, InBackpack { owner: player })
+                .unwrap();
+            let mut names = ecs.write_storage::<Name>();
+            let name = names.get_mut(*item).unwrap();
+            name.name = format!("Rotten {}", name.name);
+        }
+
+        assert!(craft(&mut ecs, "Hearty Stew"));
+    }
+
+    #[test]
+    fn recipe_file_parses() {
+        let book = RecipeBook::load();
+        assert!(!book.recipes.is_empty());
+        assert!(book.recipes.iter().all(|r| !r.ingredients.is_empty()));
+    }
+
+    #[test]
+    fn recipe_names_are_unique() {
+        let book = RecipeBook::load();
+        for recipe in book.recipes.iter() {
+            assert_eq!(
+                book.recipes
+                    .iter()
+                    .filter(|r| r.name == recipe.name)
+                    .count(),
+                1
+            );
+        }
+    }
+
+    #[test]
+    fn learned_recipes_are_known() {
+        let book = RecipeBook::load();
+        let secret = book.recipes.iter().find(|r| !r.known).unwrap();
+        assert!(!is_known(secret, None));
+        let learned = KnownRecipes {
+            recipes: vec![secret.name.clone()],
+        };
+        assert!(is_known(secret, Some(&learned)));
+    }
+}
@@ -1,4 +1,8 @@
-use super::{game_log::GameLog, CombatStats, Map, Name, Player, Position, RunState, SufferDamage};
+use super::{
+    game_log::{with_article, GameLog, RunStats},
+    spawner, CombatStats, DamageSource, Equipped, ForSale, Map, Name, Player, Position, Purse,
+    RunState, SufferDamage, Undead,
+};
 use specs::prelude::*;
 
 pub struct DamageSystem;
@@ -10,30 +14,76 @@ impl<'a> System<'a> for DamageSystem {
         ReadStorage<'a, Position>,
         WriteExpect<'a, Map>,
         Entities<'a>,
+        ReadStorage<'a, Name>,
+        ReadExpect<'a, Entity>,
+        WriteExpect<'a, RunStats>,
     );
     fn run(&mut self, data: Self::SystemData) {
-        let (mut stats, mut damage, positions, mut map, entities) = data;
+        let (
+            mut stats,
+            mut damage,
+            positions,
+            mut map,
+            entities,
+            names,
+            player_entity,
+            mut run_stats,
+        ) = data;
 
         for (entity, mut stats, damage) in (&entities, &mut stats, &damage).join() {
+            let was_alive = stats.hp > 0;
             stats.hp -= damage.amount;
             let pos = positions.get(entity);
             if let Some(pos) = pos {
                 let idx = map.xy_idx(pos.x, pos.y);
                 map.bloodstains.insert(idx);
             }
+
+            // Note who killed whom, for the death recap
+            if was_alive && stats.hp < 1 {
+                if entity == *player_entity {
+                    run_stats.cause_of_death = Some(cause_of_death(damage.source, &names, entity));
+                } else if damage.source == DamageSource::Attacker(*player_entity) {
+                    if let Some(name) = names.get(entity) {
+                        *run_stats.kills.entry(name.name.clone()).or_insert(0) += 1;
+                    }
+                }
+            }
         }
 
         damage.clear();
     }
 }
 
+fn cause_of_death(source: DamageSource, names: &ReadStorage<Name>, player: Entity) -> String {
+    match source {
+        DamageSource::Attacker(attacker) if attacker == player => {
+            "Killed by their own hand".to_string()
+        }
+        DamageSource::Attacker(attacker) => match names.get(attacker) {
+            Some(name) => format!("Killed by {}", with_article(&name.name)),
+            None => "Killed by something unseen".to_string(),
+        },
+        DamageSource::Hunger => "Starved to death".to_string(),
+        DamageSource::Sickness => "Died of sickness".to_string(),
+        DamageSource::Choking => "Choked on their food".to_string(),
+    }
+}
+
 pub fn delete_the_dead(ecs: &mut World) {
     let mut dead: Vec<Entity> = Vec::new();
+    let mut corpses: Vec<(i32, i32, String)> = Vec::new();
+    let mut dropped_gold: Vec<(i32, i32, i32)> = Vec::new();
+    let mut dropped_gear: Vec<(Entity, Position)> = Vec::new();
     {
         let combat_stats = ecs.read_storage::<CombatStats>();
         let players = ecs.read_storage::<Player>();
         let entities = ecs.entities();
         let names = ecs.read_storage::<Name>();
+        let positions = ecs.read_storage::<Position>();
+        let undead = ecs.read_storage::<Undead>();
+        let purses = ecs.read_storage::<Purse>();
+        let equipped = ecs.read_storage::<Equipped>();
         let mut log = ecs.write_resource::<GameLog>();
 
         for (entity, stats) in (&entities, &combat_stats).join() {
@@ -45,6 +95,28 @@ pub fn delete_the_dead(ecs: &mut World) {
                         if let Some(victim_name) = victim_name {
                             log.entries
                                 .insert(0, format!("{} is dead", &victim_name.name));
+
+                            // The living leave a corpse where they fell
+                            if let Some(pos) = positions.get(entity) {
+                                if undead.get(entity).is_none() {
+                                    corpses.push((pos.x, pos.y, victim_name.name.clone()));
+                                }
+                            }
+                        }
+                        if let (Some(purse), Some(pos)) =
+                            (purses.get(entity), positions.get(entity))
+                        {
+                            if purse.gold > 0 {
+                                dropped_gold.push((pos.x, pos.y, purse.gold));
+                            }
+                        }
+                        // Whatever they had equipped falls to the floor
+                        if let Some(pos) = positions.get(entity) {
+                            for (item, equipped_by) in (&entities, &equipped).join() {
+                                if equipped_by.owner == entity {
+                                    dropped_gear.push((item, pos.clone()));
+                                }
+                            }
                         }
                         dead.push(entity)
                     }
@@ -60,4 +132,33 @@ pub fn delete_the_dead(ecs: &mut World) {
     for victim in dead {
         ecs.delete_entity(victim).expect("Unable to delete");
     }
+
+    // A dead shopkeeper's stock is free for the taking
+    {
+        let entities = ecs.entities();
+        let mut for_sale = ecs.write_storage::<ForSale>();
+        let unowned: Vec<Entity> = (&entities, &for_sale)
+            .join()
+            .filter(|(_, sale)| !entities.is_alive(sale.shopkeeper))
+            .map(|(item, _)| item)
+            .collect();
+        for item in unowned {
+            for_sale.remove(item);
+        }
+    }
+
+    for (x, y, species) in corpses {
+        spawner::corpse(ecs, x, y, &species);
+    }
+
+    for (x, y, amount) in dropped_gold {
+        spawner::gold(ecs, x, y, amount);
+    }
+
+    for (item, pos) in dropped_gear {
+        ecs.write_storage::<Equipped>().remove(item);
+        ecs.write_storage::<Position>()
+            .insert(item, pos)
+            .expect("Unable to insert position");
+    }
 }
@@ -0,0 +1,203 @@
+use super::{
+    game_log::GameLog, spawner, spawner::Spawn, InBackpack, Map, Monster, Name, Npc, Position,
+};
+use rltk::Point;
+use serde::{Deserialize, Serialize};
+use specs::prelude::*;
+
+/// Something that happens when the player picks a dialogue choice.
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug)]
+pub enum DialogueAction {
+    GiveItem(Spawn),
+    RevealMap,
+    StartFight,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Choice {
+    pub text: String,
+    /// The node to continue with; without one, the conversation ends.
+    #[serde(default)]
+    pub next: Option<String>,
+    #[serde(default)]
+    pub action: Option<DialogueAction>,
+    /// Where the next conversation with this NPC starts, e.g. once they've given
+    /// something away.
+    #[serde(default)]
+    pub next_time: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct DialogueNode {
+    pub id: String,
+    pub text: String,
+    pub choices: Vec<Choice>,
+}
+
+/// A dialogue tree. Conversations start at the first node unless the NPC remembers
+/// otherwise.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Conversation {
+    pub name: String,
+    pub nodes: Vec<DialogueNode>,
+}
+
+impl Conversation {
+    pub fn node_index(&self, id: &str) -> Option<usize> {
+        self.nodes.iter().position(|node| node.id == id)
+    }
+}
+
+/// Every conversation NPCs can have. They live in `resources/dialogue.json`, so
+/// writing new dialogue needs no code.
+pub struct DialogueBook {
+    pub conversations: Vec<Conversation>,
+}
+
+impl DialogueBook {
+    pub fn load() -> DialogueBook {
+        let data = include_str!("../resources/dialogue.json");
+        DialogueBook {
+            conversations: serde_json::from_str(data).expect("Unable to parse dialogue.json"),
+        }
+    }
+
+    pub fn find(&self, name: &str) -> Option<&Conversation> {
+        self.conversations.iter().find(|c| c.name == name)
+    }
+}
+
+/// Where the player's next conversation with `npc` begins.
+pub fn start_node(ecs: &World, npc: Entity) -> usize {
+    let npcs = ecs.read_storage::<Npc>();
+    let book = ecs.fetch::<DialogueBook>();
+    npcs.get(npc)
+        .and_then(|npc| {
+            let start = npc.start.as_ref()?;
+            book.find(&npc.conversation)?.node_index(start)
+        })
+        .unwrap_or(0)
+}
+
+/// Looks up the conversation `npc` is having, if its dialogue exists.
+pub fn conversation_with(ecs: &World, npc: Entity) -> Option<Conversation> {
+    let npcs = ecs.read_storage::<Npc>();
+    let book = ecs.fetch::<DialogueBook>();
+    npcs.get(npc)
+        .and_then(|npc| book.find(&npc.conversation))
+        .cloned()
+}
+
+pub enum DialogueOutcome {
+    Next(usize),
+    Leave,
+    RevealMap,
+}
+
+/// Carries out the player's choice and says where the conversation goes next.
+/// Revealing the map and starting a fight both end it.
+pub fn choose(ecs: &mut World, npc: Entity, node: usize, choice: usize) -> DialogueOutcome {
+    let conversation = match conversation_with(ecs, npc) {
+        Some(conversation) => conversation,
+        None => return DialogueOutcome::Leave,
+    };
+    let choice = match conversation
+        .nodes
+        .get(node)
+        .and_then(|node| node.choices.get(choice))
+    {
+        Some(choice) => choice.clone(),
+        None => return DialogueOutcome::Leave,
+    };
+
+    if let Some(next_time) = &choice.next_time {
+        if let Some(npc) = ecs.write_storage::<Npc>().get_mut(npc) {
+            npc.start = Some(next_time.clone());
+        }
+    }
+
+    match choice.action {
+        Some(DialogueAction::GiveItem(spawn)) => give_item(ecs, spawn),
+        Some(DialogueAction::RevealMap) => return DialogueOutcome::RevealMap,
+        Some(DialogueAction::StartFight) => {
+            let name = ecs
+                .read_storage::<Name>()
+                .get(npc)
+                .map_or("They".to_string(), |n| format!("The {}", n.name));
+            ecs.write_storage::<Monster>()
+                .insert(npc, Monster {})
+                .expect("Unable to insert monster");
+            ecs.fetch_mut::<GameLog>()
+                .entries
+                .insert(0, format!("{} attacks!", name));
+            return DialogueOutcome::Leave;
+        }
+        None => {}
+    }
+
+    match choice.next.and_then(|next| conversation.node_index(&next)) {
+        Some(next) => DialogueOutcome::Next(next),
+        None => DialogueOutcome::Leave,
+    }
+}
+
+fn give_item(ecs: &mut World, spawn: Spawn) {
+    let player_entity = *ecs.fetch::<Entity>();
+    let player_pos = *ecs.fetch::<Point>();
+    let depth = ecs.fetch::<Map>().depth;
+    if let Some(item) = spawner::spawn_item(ecs, spawn, player_pos.x, player_pos.y, depth) {
+        ecs.write_storage::<Position>().remove(item);
+        ecs.write_storage::<InBackpack>()
+            .insert(
+                item,
+                InBackpack {
+                    owner: player_entity,
+                },
+            )
+            .expect("Unable to insert backpack entry");
+        let name = ecs
+            .read_storage::<Name>()
+            .get(item)
+            .map_or("something".to_string(), |n| n.name.to_string());
+        ecs.fetch_mut::<GameLog>()
+            .entries
+            .insert(0, format!("You receive the {}.", name));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn dialogue_file_parses() {
+        let book = DialogueBook::load();
+        assert!(!book.conversations.is_empty());
+        assert!(book.conversations.iter().all(|c| !c.nodes.is_empty()));
+    }
+
+    #[test]
+    fn every_node_offers_a_choice() {
+        let book = DialogueBook::load();
+        for conversation in book.conversations.iter() {
+            assert!(conversation.nodes.iter().all(|n| !n.choices.is_empty()));
+        }
+    }
+
+    #[test]
+    fn choices_lead_to_existing_nodes() {
+        let book = DialogueBook::load();
+        for conversation in book.conversations.iter() {
+            for choice in conversation.nodes.iter().flat_map(|n| n.choices.iter()) {
+                for target in choice.next.iter().chain(choice.next_time.iter()) {
+                    assert!(
+                        conversation.node_index(target).is_some(),
+                        "{} has no node {}",
+                        conversation.name,
+                        target
+                    );
+                }
+            }
+        }
+    }
+}
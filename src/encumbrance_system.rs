@@ -0,0 +1,81 @@
+use super::{
+    status_effect_system::strength_bonus, CombatStats, Encumbrance, Equipped, InBackpack,
+    InContainer, StatusEffects, Weight,
+};
+use specs::prelude::*;
+use specs::storage::MaskedStorage;
+use std::ops::Deref;
+
+/// How much a creature can carry before it is slowed down, based on its strength.
+pub fn carry_capacity(stats: &CombatStats, status: Option<&StatusEffects>) -> f32 {
+    10.0 * (stats.power + strength_bonus(status)) as f32
+}
+
+/// Total weight of everything `owner` has in their backpack or equipped, including the
+/// contents of any bags they are carrying.
+pub fn carried_weight<B, E, C>(
+    owner: Entity,
+    weights: &ReadStorage<Weight>,
+    backpack: &Storage<InBackpack, B>,
+    equipped: &Storage<Equipped, E>,
+    in_container: &Storage<InContainer, C>,
+) -> f32
+where
+    B: Deref<Target = MaskedStorage<InBackpack>>,
+    E: Deref<Target = MaskedStorage<Equipped>>,
+    C: Deref<Target = MaskedStorage<InContainer>>,
+{
+    let mut total = 0.0;
+    for (weight, pack) in (weights, backpack).join() {
+        if pack.owner == owner {
+            total += weight.lbs;
+        }
+    }
+    for (weight, eq) in (weights, equipped).join() {
+        if eq.owner == owner {
+            total += weight.lbs;
+        }
+    }
+    for (weight, inside) in (weights, in_container).join() {
+        if let Some(pack) = backpack.get(inside.container) {
+            if pack.owner == owner {
+                total += weight.lbs;
+            }
+        }
+    }
+    total
+}
+
+pub struct EncumbranceSystem;
+
+impl<'a> System<'a> for EncumbranceSystem {
+    #[allow(clippy::type_complexity)]
+    type SystemData = (
+        Entities<'a>,
+        WriteStorage<'a, Encumbrance>,
+        ReadStorage<'a, CombatStats>,
+        ReadStorage<'a, Weight>,
+        ReadStorage<'a, InBackpack>,
+        ReadStorage<'a, Equipped>,
+        ReadStorage<'a, InContainer>,
+        ReadStorage<'a, StatusEffects>,
+    );
+
+    fn run(&mut self, data: Self::SystemData) {
+        let (
+            entities,
+            mut encumbrance,
+            combat_stats,
+            weights,
+            backpack,
+            equipped,
+            in_container,
+            statuses,
+        ) = data;
+
+        for (entity, load, stats) in (&entities, &mut encumbrance, &combat_stats).join() {
+            load.carried = carried_weight(entity, &weights, &backpack, &equipped, &in_container);
+            load.capacity = carry_capacity(stats, statuses.get(entity));
+        }
+    }
+}
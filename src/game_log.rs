@@ -1,6 +1,33 @@
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 
 #[derive(Serialize, Deserialize, Clone)]
 pub struct GameLog {
     pub entries: Vec<String>,
 }
+
+/// How many turns the player has taken this run.
+#[derive(Serialize, Deserialize, Clone, Copy, Default)]
+pub struct TurnCounter {
+    pub turns: i32,
+}
+
+/// What the player has done this run, for the death recap and hall of fame.
+#[derive(Serialize, Deserialize, Clone, Default)]
+pub struct RunStats {
+    /// How many of each kind of monster the player has killed.
+    pub kills: BTreeMap<String, i32>,
+    /// Filled in when the player dies, e.g. "Killed by an Orc".
+    pub cause_of_death: Option<String>,
+    /// The deepest level reached, which a level teleport may have since left.
+    pub deepest: i32,
+}
+
+/// `name` with "a" or "an" in front, e.g. "an Orc" or "a Goblin".
+pub fn with_article(name: &str) -> String {
+    let article = match name.chars().next() {
+        Some('A') | Some('E') | Some('I') | Some('O') | Some('U') => "an",
+        _ => "a",
+    };
+    format!("{} {}", article, name)
+}
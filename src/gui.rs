@@ -1,6 +1,16 @@
 use super::{
-    game_log::GameLog, rex_assets::RexAssets, CombatStats, Equipped, HungerClock, HungerState,
-    InBackpack, Map, Name, Player, Position, RunState, State, Viewshed,
+    config::GameConfig,
+    crafting::{self, Recipe, RecipeBook},
+    dialogue,
+    game_log::GameLog,
+    hall_of_fame, morgue,
+    rex_assets::RexAssets,
+    save_storage::format_time,
+    saveload_system::{self, SaveHeader, SlotHeaders, SAVE_SLOTS},
+    shop, status_effect_system, Charges, CombatStats, Container, Encumbrance, Equipped, ForSale,
+    HungerClock, HungerState, InBackpack, InContainer, Invisible, Item, ItemCategory, KnownRecipes,
+    KnownSpell, Mana, Map, Monster, Name, Player, Position, Purse, RunState, Spell, State,
+    StatusEffect, StatusEffects, Value, Viewshed,
 };
 use rltk::{Console, Point, Rltk, VirtualKeyCode, RGB};
 use specs::prelude::*;
@@ -33,7 +43,7 @@ pub fn draw_ui(ecs: &World, ctx: &mut Rltk) {
         ctx.draw_bar_horizontal(
             28,
             43,
-            51,
+            21,
             stats.hp,
             stats.max_hp,
             RGB::named(rltk::RED),
@@ -72,6 +82,83 @@ pub fn draw_ui(ecs: &World, ctx: &mut Rltk) {
         }
     }
 
+    let mana = ecs.read_storage::<Mana>();
+    for (_player, pool) in (&players, &mana).join() {
+        let mana_text = format!(" MP: {} / {} ", pool.current, pool.max);
+        ctx.print_color(
+            50,
+            43,
+            RGB::named(rltk::CYAN),
+            RGB::named(rltk::BLACK),
+            &mana_text,
+        );
+        ctx.draw_bar_horizontal(
+            63,
+            43,
+            15,
+            pool.current,
+            pool.max,
+            RGB::named(rltk::BLUE),
+            RGB::named(rltk::BLACK),
+        );
+    }
+
+    let encumbrance = ecs.read_storage::<Encumbrance>();
+    for (_player, load) in (&players, &encumbrance).join() {
+        if load.penalty() > 0 {
+            ctx.print_color(
+                48,
+                42,
+                RGB::named(rltk::ORANGE),
+                RGB::named(rltk::BLACK),
+                &format!("Burdened {:.0}/{:.0} lbs", load.carried, load.capacity),
+            );
+        } else {
+            ctx.print_color(
+                57,
+                42,
+                RGB::named(rltk::GREY),
+                RGB::named(rltk::BLACK),
+                &format!("{:.0}/{:.0} lbs", load.carried, load.capacity),
+            );
+        }
+    }
+
+    // Active potion effects, e.g. "Fast(12) Telepathic(40)"
+    let statuses = ecs.read_storage::<StatusEffects>();
+    for (_player, status) in (&players, &statuses).join() {
+        let mut x = 2;
+        for effect in status.active.iter() {
+            let text = format!(
+                "{}({})",
+                status_effect_system::status_name(effect.effect),
+                effect.turns
+            );
+            if x + text.len() as i32 > 47 {
+                break;
+            }
+            ctx.print_color(
+                x,
+                42,
+                RGB::named(rltk::LIGHT_BLUE),
+                RGB::named(rltk::BLACK),
+                &text,
+            );
+            x += text.len() as i32 + 1;
+        }
+    }
+
+    let purses = ecs.read_storage::<Purse>();
+    for (_player, purse) in (&players, &purses).join() {
+        ctx.print_color(
+            2,
+            49,
+            RGB::named(rltk::GOLD),
+            RGB::named(rltk::BLACK),
+            &format!(" Gold: {} ", purse.gold),
+        );
+    }
+
     let mut y = 44;
     for s in log.entries.iter() {
         if y < 49 {
@@ -102,7 +189,7 @@ pub fn draw_ui(ecs: &World, ctx: &mut Rltk) {
         ctx.draw_bar_horizontal(
             28,
             43,
-            51,
+            21,
             stats.hp,
             stats.max_hp,
             RGB::named(rltk::RED),
@@ -123,10 +210,38 @@ pub fn draw_ui(ecs: &World, ctx: &mut Rltk) {
     draw_tooltips(ecs, ctx);
 }
 
+/// Whether the player can perceive `entity`: it must be in view, unless the player's
+/// telepathy or item sense reveals it, and invisible things need see invisible.
+pub fn player_can_see(ecs: &World, entity: Entity, position: &Position) -> bool {
+    let map = ecs.fetch::<Map>();
+    let player_entity = ecs.fetch::<Entity>();
+    let statuses = ecs.read_storage::<StatusEffects>();
+    let status = statuses.get(*player_entity);
+    let has = |effect| status.is_some_and(|s: &StatusEffects| s.has(effect));
+
+    if entity == *player_entity {
+        return true;
+    }
+    if ecs.read_storage::<Invisible>().get(entity).is_some() && !has(StatusEffect::SeeInvisible) {
+        return false;
+    }
+    if map.visible_tiles[map.xy_idx(position.x, position.y)] {
+        return true;
+    }
+    if has(StatusEffect::Telepathy) && ecs.read_storage::<Monster>().get(entity).is_some() {
+        return true;
+    }
+    has(StatusEffect::DetectItems) && ecs.read_storage::<Item>().get(entity).is_some()
+}
+
 fn draw_tooltips(ecs: &World, ctx: &mut Rltk) {
     let map = ecs.fetch::<Map>();
     let names = ecs.read_storage::<Name>();
     let positions = ecs.read_storage::<Position>();
+    let charges = ecs.read_storage::<Charges>();
+    let for_sale = ecs.read_storage::<ForSale>();
+    let values = ecs.read_storage::<Value>();
+    let entities = ecs.entities();
 
     let mouse_pos = ctx.mouse_pos();
     if mouse_pos.0 >= map.width || mouse_pos.1 >= map.height {
@@ -134,12 +249,16 @@ fn draw_tooltips(ecs: &World, ctx: &mut Rltk) {
     }
 
     let mut tooltip: Vec<String> = Vec::new();
-    for (name, position) in (&names, &positions).join() {
+    for (entity, name, position) in (&entities, &names, &positions).join() {
         if position.x == mouse_pos.0 && position.y == mouse_pos.1 {
-            if !map.visible_tiles[map.xy_idx(position.x, position.y)] {
+            if !player_can_see(ecs, entity, position) {
                 continue;
             }
-            tooltip.push(name.name.to_string());
+            let mut text = item_display_name(name, charges.get(entity));
+            if let (Some(_sale), Some(value)) = (for_sale.get(entity), values.get(entity)) {
+                text = format!("{} ({} gold)", text, value.gold);
+            }
+            tooltip.push(text);
         }
     }
 
@@ -290,6 +409,14 @@ pub fn ranged_target(
     (ItemMenuResult::NoResponse, None)
 }
 
+/// Item names as shown in menus and tooltips, with remaining charges for wands and staves.
+fn item_display_name(name: &Name, charges: Option<&Charges>) -> String {
+    match charges {
+        None => name.name.to_string(),
+        Some(charges) => format!("{} ({}/{})", name.name, charges.current, charges.max),
+    }
+}
+
 #[derive(PartialEq, Copy, Clone)]
 pub enum ItemMenuResult {
     Cancel,
@@ -300,6 +427,7 @@ pub enum ItemMenuResult {
 pub fn show_inventory(gs: &mut State, ctx: &mut Rltk) -> (ItemMenuResult, Option<Entity>) {
     let player_entity = gs.ecs.fetch::<Entity>();
     let names = gs.ecs.read_storage::<Name>();
+    let charges = gs.ecs.read_storage::<Charges>();
     let backpack = gs.ecs.read_storage::<InBackpack>();
     let entities = gs.ecs.entities();
 
@@ -360,7 +488,7 @@ pub fn show_inventory(gs: &mut State, ctx: &mut Rltk) -> (ItemMenuResult, Option
             rltk::to_cp437(')'),
         );
 
-        ctx.print(21, y, &name.name.to_string());
+        ctx.print(21, y, &item_display_name(name, charges.get(entity)));
         equippable.push(entity);
         y += 1;
         j += 1;
@@ -384,21 +512,22 @@ pub fn show_inventory(gs: &mut State, ctx: &mut Rltk) -> (ItemMenuResult, Option
     }
 }
 
-pub fn drop_item_menu(gs: &mut State, ctx: &mut Rltk) -> (ItemMenuResult, Option<Entity>) {
+pub fn show_spells(gs: &mut State, ctx: &mut Rltk) -> (ItemMenuResult, Option<Entity>) {
     let player_entity = gs.ecs.fetch::<Entity>();
     let names = gs.ecs.read_storage::<Name>();
-    let backpack = gs.ecs.read_storage::<InBackpack>();
+    let known_spells = gs.ecs.read_storage::<KnownSpell>();
+    let spells = gs.ecs.read_storage::<Spell>();
     let entities = gs.ecs.entities();
 
-    let inventory = (&backpack, &names)
+    let spellbook = (&known_spells, &spells)
         .join()
-        .filter(|item| item.0.owner == *player_entity);
-    let count = inventory.count();
+        .filter(|spell| spell.0.owner == *player_entity);
+    let count = spellbook.count();
 
-    let mut y = (25 - (count / 2)) as i32;
+    let top = (25 - (count / 2)) as i32;
     ctx.draw_box(
         15,
-        y - 2,
+        top - 2,
         31,
         (count + 3) as i32,
         RGB::named(rltk::WHITE),
@@ -406,25 +535,26 @@ pub fn drop_item_menu(gs: &mut State, ctx: &mut Rltk) -> (ItemMenuResult, Option
     );
     ctx.print_color(
         18,
-        y - 2,
+        top - 2,
         RGB::named(rltk::YELLOW),
         RGB::named(rltk::BLACK),
-        "Drop Which Item?",
+        "Cast Which Spell?",
     );
     ctx.print_color(
         18,
-        y + count as i32 + 1,
+        top + count as i32 + 1,
         RGB::named(rltk::YELLOW),
         RGB::named(rltk::BLACK),
         "ESCAPE to cancel",
     );
 
-    let mut equippable: Vec<Entity> = Vec::new();
-    let mut j = 0;
-    for (entity, _pack, name) in (&entities, &backpack, &names)
+    let mut castable: Vec<Entity> = Vec::new();
+    for (j, (entity, _known, spell, name)) in (&entities, &known_spells, &spells, &names)
         .join()
-        .filter(|item| item.1.owner == *player_entity)
+        .filter(|spell| spell.1.owner == *player_entity)
+        .enumerate()
     {
+        let y = top + j as i32;
         ctx.set(
             17,
             y,
@@ -447,23 +577,18 @@ pub fn drop_item_menu(gs: &mut State, ctx: &mut Rltk) -> (ItemMenuResult, Option
             rltk::to_cp437(')'),
         );
 
-        ctx.print(21, y, &name.name.to_string());
-        equippable.push(entity);
-        y += 1;
-        j += 1;
+        ctx.print(21, y, &format!("{} ({} mp)", name.name, spell.mana_cost));
+        castable.push(entity);
     }
 
     match ctx.key {
         None => (ItemMenuResult::NoResponse, None),
         Some(key) => match key {
-            VirtualKeyCode::Escape | VirtualKeyCode::D => (ItemMenuResult::Cancel, None),
+            VirtualKeyCode::Escape | VirtualKeyCode::Z => (ItemMenuResult::Cancel, None),
             _ => {
                 let selection = rltk::letter_to_option(key);
                 if selection > -1 && selection < count as i32 {
-                    return (
-                        ItemMenuResult::Selected,
-                        Some(equippable[selection as usize]),
-                    );
+                    return (ItemMenuResult::Selected, Some(castable[selection as usize]));
                 }
                 (ItemMenuResult::NoResponse, None)
             }
@@ -471,178 +596,115 @@ pub fn drop_item_menu(gs: &mut State, ctx: &mut Rltk) -> (ItemMenuResult, Option
     }
 }
 
-#[derive(PartialEq, Copy, Clone)]
-pub enum MainMenuSelection {
-    NewGame,
-    LoadGame,
-    Quit,
-}
-
-#[derive(PartialEq, Copy, Clone)]
-pub enum MainMenuResult {
-    NoSelection { selected: MainMenuSelection },
-    Selected { selected: MainMenuSelection },
-}
-pub fn main_menu(gs: &mut State, ctx: &mut Rltk) -> MainMenuResult {
-    let save_exists = super::saveload_system::does_save_exist();
-    let runstate = gs.ecs.fetch::<RunState>();
+pub fn crafting_menu(gs: &mut State, ctx: &mut Rltk) -> (ItemMenuResult, Option<String>) {
+    let player_entity = gs.ecs.fetch::<Entity>();
+    let book = gs.ecs.fetch::<RecipeBook>();
+    let known_recipes = gs.ecs.read_storage::<KnownRecipes>();
+    let known = known_recipes.get(*player_entity);
 
-    let assets = gs.ecs.fetch::<RexAssets>();
-    ctx.render_xp_sprite(&assets.menu, 0, 0);
+    let recipes: Vec<&Recipe> = book
+        .recipes
+        .iter()
+        .filter(|recipe| crafting::is_known(recipe, known))
+        .collect();
+    let count = recipes.len();
 
-    ctx.draw_box_double(
-        24,
-        18,
-        31,
-        10,
-        RGB::named(rltk::WHEAT),
+    let top = (25 - (count / 2)) as i32;
+    ctx.draw_box(
+        5,
+        top - 2,
+        70,
+        (count + 3) as i32,
+        RGB::named(rltk::WHITE),
         RGB::named(rltk::BLACK),
     );
-    ctx.print_color_centered(
-        20,
+    ctx.print_color(
+        8,
+        top - 2,
         RGB::named(rltk::YELLOW),
         RGB::named(rltk::BLACK),
-        "Rust Roguelike Tutorial",
-    );
-    ctx.print_color_centered(
-        21,
-        RGB::named(rltk::CYAN),
-        RGB::named(rltk::BLACK),
-        "copied by Jacobious52",
+        "Craft What?",
     );
-    ctx.print_color_centered(
-        22,
-        RGB::named(rltk::GRAY),
+    ctx.print_color(
+        8,
+        top + count as i32 + 1,
+        RGB::named(rltk::YELLOW),
         RGB::named(rltk::BLACK),
-        "Use Up/Down Arrows and Enter",
+        "ESCAPE to cancel",
     );
 
-    let mut y = 24;
-    if let RunState::MainMenu {
-        menu_selection: selection,
-    } = *runstate
-    {
-        if selection == MainMenuSelection::NewGame {
-            ctx.print_color_centered(
-                y,
-                RGB::named(rltk::MAGENTA),
-                RGB::named(rltk::BLACK),
-                "Begin New Game",
-            );
-        } else {
-            ctx.print_color_centered(
-                y,
-                RGB::named(rltk::WHITE),
-                RGB::named(rltk::BLACK),
-                "Begin New Game",
-            );
-        }
-        y += 1;
-
-        if save_exists {
-            if selection == MainMenuSelection::LoadGame {
-                ctx.print_color_centered(
-                    y,
-                    RGB::named(rltk::MAGENTA),
-                    RGB::named(rltk::BLACK),
-                    "Load Game",
-                );
-            } else {
-                ctx.print_color_centered(
-                    y,
-                    RGB::named(rltk::WHITE),
-                    RGB::named(rltk::BLACK),
-                    "Load Game",
-                );
-            }
-            y += 1;
-        }
+    for (j, recipe) in recipes.iter().enumerate() {
+        let y = top + j as i32;
+        ctx.set(
+            7,
+            y,
+            RGB::named(rltk::WHITE),
+            RGB::named(rltk::BLACK),
+            rltk::to_cp437('('),
+        );
+        ctx.set(
+            8,
+            y,
+            RGB::named(rltk::YELLOW),
+            RGB::named(rltk::BLACK),
+            97 + j as u8,
+        );
+        ctx.set(
+            9,
+            y,
+            RGB::named(rltk::WHITE),
+            RGB::named(rltk::BLACK),
+            rltk::to_cp437(')'),
+        );
 
-        if selection == MainMenuSelection::Quit {
-            ctx.print_color_centered(
-                y,
-                RGB::named(rltk::MAGENTA),
-                RGB::named(rltk::BLACK),
-                "Quit",
-            );
+        // Recipes that can be made right now stand out
+        let colour = if crafting::can_craft(&gs.ecs, recipe) {
+            RGB::named(rltk::WHITE)
         } else {
-            ctx.print_color_centered(y, RGB::named(rltk::WHITE), RGB::named(rltk::BLACK), "Quit");
+            RGB::named(rltk::GREY)
+        };
+        let mut text = format!("{}: {}", recipe.name, recipe.ingredients.join(" + "));
+        if let Some(station) = &recipe.station {
+            text.push_str(&format!(" (at {})", station));
         }
+        ctx.print_color(11, y, colour, RGB::named(rltk::BLACK), &text);
+    }
 
-        match ctx.key {
-            None => {
-                return MainMenuResult::NoSelection {
-                    selected: selection,
+    match ctx.key {
+        None => (ItemMenuResult::NoResponse, None),
+        Some(key) => match key {
+            VirtualKeyCode::Escape | VirtualKeyCode::C => (ItemMenuResult::Cancel, None),
+            _ => {
+                let selection = rltk::letter_to_option(key);
+                if selection > -1 && selection < count as i32 {
+                    return (
+                        ItemMenuResult::Selected,
+                        Some(recipes[selection as usize].name.clone()),
+                    );
                 }
+                (ItemMenuResult::NoResponse, None)
             }
-            Some(key) => match key {
-                VirtualKeyCode::Escape => {
-                    return MainMenuResult::NoSelection {
-                        selected: MainMenuSelection::Quit,
-                    }
-                }
-                VirtualKeyCode::Up => {
-                    let mut newselection;
-                    match selection {
-                        MainMenuSelection::NewGame => newselection = MainMenuSelection::Quit,
-                        MainMenuSelection::LoadGame => newselection = MainMenuSelection::NewGame,
-                        MainMenuSelection::Quit => newselection = MainMenuSelection::LoadGame,
-                    }
-                    if newselection == MainMenuSelection::LoadGame && !save_exists {
-                        newselection = MainMenuSelection::NewGame;
-                    }
-                    return MainMenuResult::NoSelection {
-                        selected: newselection,
-                    };
-                }
-                VirtualKeyCode::Down => {
-                    let mut newselection;
-                    match selection {
-                        MainMenuSelection::NewGame => newselection = MainMenuSelection::LoadGame,
-                        MainMenuSelection::LoadGame => newselection = MainMenuSelection::Quit,
-                        MainMenuSelection::Quit => newselection = MainMenuSelection::NewGame,
-                    }
-                    if newselection == MainMenuSelection::LoadGame && !save_exists {
-                        newselection = MainMenuSelection::Quit;
-                    }
-                    return MainMenuResult::NoSelection {
-                        selected: newselection,
-                    };
-                }
-                VirtualKeyCode::Return => {
-                    return MainMenuResult::Selected {
-                        selected: selection,
-                    }
-                }
-                _ => {
-                    return MainMenuResult::NoSelection {
-                        selected: selection,
-                    }
-                }
-            },
-        }
-    }
-
-    MainMenuResult::NoSelection {
-        selected: MainMenuSelection::NewGame,
+        },
     }
 }
 
-pub fn remove_item_menu(gs: &mut State, ctx: &mut Rltk) -> (ItemMenuResult, Option<Entity>) {
-    let player_entity = gs.ecs.fetch::<Entity>();
+pub fn pickup_item_menu(gs: &mut State, ctx: &mut Rltk) -> (ItemMenuResult, Option<Entity>) {
+    let player_pos = gs.ecs.fetch::<Point>();
     let names = gs.ecs.read_storage::<Name>();
-    let backpack = gs.ecs.read_storage::<Equipped>();
+    let items = gs.ecs.read_storage::<Item>();
+    let positions = gs.ecs.read_storage::<Position>();
+    let charges = gs.ecs.read_storage::<Charges>();
     let entities = gs.ecs.entities();
 
-    let inventory = (&backpack, &names)
+    let inventory = (&items, &positions, &names)
         .join()
-        .filter(|item| item.0.owner == *player_entity);
+        .filter(|item| item.1.x == player_pos.x && item.1.y == player_pos.y);
     let count = inventory.count();
 
-    let mut y = (25 - (count / 2)) as i32;
+    let top = (25 - (count / 2)) as i32;
     ctx.draw_box(
         15,
-        y - 2,
+        top - 2,
         31,
         (count + 3) as i32,
         RGB::named(rltk::WHITE),
@@ -650,25 +712,26 @@ pub fn remove_item_menu(gs: &mut State, ctx: &mut Rltk) -> (ItemMenuResult, Opti
     );
     ctx.print_color(
         18,
-        y - 2,
+        top - 2,
         RGB::named(rltk::YELLOW),
         RGB::named(rltk::BLACK),
-        "Remove Which Item?",
+        "Pick Up Which Item?",
     );
     ctx.print_color(
         18,
-        y + count as i32 + 1,
+        top + count as i32 + 1,
         RGB::named(rltk::YELLOW),
         RGB::named(rltk::BLACK),
         "ESCAPE to cancel",
     );
 
-    let mut equippable: Vec<Entity> = Vec::new();
-    let mut j = 0;
-    for (entity, _pack, name) in (&entities, &backpack, &names)
+    let mut pickable: Vec<Entity> = Vec::new();
+    for (j, (entity, _item, _pos, name)) in (&entities, &items, &positions, &names)
         .join()
-        .filter(|item| item.1.owner == *player_entity)
+        .filter(|item| item.2.x == player_pos.x && item.2.y == player_pos.y)
+        .enumerate()
     {
+        let y = top + j as i32;
         ctx.set(
             17,
             y,
@@ -691,23 +754,18 @@ pub fn remove_item_menu(gs: &mut State, ctx: &mut Rltk) -> (ItemMenuResult, Opti
             rltk::to_cp437(')'),
         );
 
-        ctx.print(21, y, &name.name.to_string());
-        equippable.push(entity);
-        y += 1;
-        j += 1;
+        ctx.print(21, y, &item_display_name(name, charges.get(entity)));
+        pickable.push(entity);
     }
 
     match ctx.key {
         None => (ItemMenuResult::NoResponse, None),
         Some(key) => match key {
-            VirtualKeyCode::Escape => (ItemMenuResult::Cancel, None),
+            VirtualKeyCode::Escape | VirtualKeyCode::G => (ItemMenuResult::Cancel, None),
             _ => {
                 let selection = rltk::letter_to_option(key);
                 if selection > -1 && selection < count as i32 {
-                    return (
-                        ItemMenuResult::Selected,
-                        Some(equippable[selection as usize]),
-                    );
+                    return (ItemMenuResult::Selected, Some(pickable[selection as usize]));
                 }
                 (ItemMenuResult::NoResponse, None)
             }
@@ -716,33 +774,1190 @@ pub fn remove_item_menu(gs: &mut State, ctx: &mut Rltk) -> (ItemMenuResult, Opti
 }
 
 #[derive(PartialEq, Copy, Clone)]
-pub enum GameOverResult {
-    NoSelection,
-    QuitToMenu,
+pub enum ContainerMenuResult {
+    Cancel,
+    NoResponse,
+    Stash,
+    Take { item: Entity },
 }
 
-pub fn game_over(ctx: &mut Rltk) -> GameOverResult {
-    ctx.print_color_centered(
+pub fn container_menu(gs: &mut State, ctx: &mut Rltk, container: Entity) -> ContainerMenuResult {
+    let names = gs.ecs.read_storage::<Name>();
+    let in_container = gs.ecs.read_storage::<InContainer>();
+    let charges = gs.ecs.read_storage::<Charges>();
+    let entities = gs.ecs.entities();
+
+    let contents = (&in_container, &names)
+        .join()
+        .filter(|item| item.0.container == container);
+    let count = contents.count();
+
+    let top = (25 - (count / 2)) as i32;
+    ctx.draw_box(
         15,
-        RGB::named(rltk::YELLOW),
+        top - 2,
+        31,
+        (count + 3) as i32,
+        RGB::named(rltk::WHITE),
         RGB::named(rltk::BLACK),
-        "Your journey has ended!",
     );
-    ctx.print_color_centered(
-        17,
-        RGB::named(rltk::WHITE),
+    let title = names
+        .get(container)
+        .map_or("Container".to_string(), |n| n.name.to_string());
+    ctx.print_color(
+        18,
+        top - 2,
+        RGB::named(rltk::YELLOW),
         RGB::named(rltk::BLACK),
-        "One day, we'll tell you all about how you did.",
+        &title,
     );
-    ctx.print_color_centered(
+    ctx.print_color(
         18,
-        RGB::named(rltk::WHITE),
+        top + count as i32 + 1,
+        RGB::named(rltk::YELLOW),
         RGB::named(rltk::BLACK),
-        "That day, sadly, is not in this chapter..",
+        "TAB to put in, ESC to close",
     );
+    if count == 0 {
+        ctx.print(21, top, "(empty)");
+    }
 
-    ctx.print_color_centered(
-        20,
+    let mut takeable: Vec<Entity> = Vec::new();
+    for (j, (entity, _inside, name)) in (&entities, &in_container, &names)
+        .join()
+        .filter(|item| item.1.container == container)
+        .enumerate()
+    {
+        let y = top + j as i32;
+        ctx.set(
+            17,
+            y,
+            RGB::named(rltk::WHITE),
+            RGB::named(rltk::BLACK),
+            rltk::to_cp437('('),
+        );
+        ctx.set(
+            18,
+            y,
+            RGB::named(rltk::YELLOW),
+            RGB::named(rltk::BLACK),
+            97 + j as u8,
+        );
+        ctx.set(
+            19,
+            y,
+            RGB::named(rltk::WHITE),
+            RGB::named(rltk::BLACK),
+            rltk::to_cp437(')'),
+        );
+
+        ctx.print(21, y, &item_display_name(name, charges.get(entity)));
+        takeable.push(entity);
+    }
+
+    match ctx.key {
+        None => ContainerMenuResult::NoResponse,
+        Some(key) => match key {
+            VirtualKeyCode::Escape => ContainerMenuResult::Cancel,
+            VirtualKeyCode::Tab => ContainerMenuResult::Stash,
+            _ => {
+                let selection = rltk::letter_to_option(key);
+                if selection > -1 && selection < count as i32 {
+                    return ContainerMenuResult::Take {
+                        item: takeable[selection as usize],
+                    };
+                }
+                ContainerMenuResult::NoResponse
+            }
+        },
+    }
+}
+
+/// Lists backpack items that can be put into `container`. Containers cannot be nested.
+pub fn stash_item_menu(
+    gs: &mut State,
+    ctx: &mut Rltk,
+    container: Entity,
+) -> (ItemMenuResult, Option<Entity>) {
+    let player_entity = gs.ecs.fetch::<Entity>();
+    let names = gs.ecs.read_storage::<Name>();
+    let backpack = gs.ecs.read_storage::<InBackpack>();
+    let containers = gs.ecs.read_storage::<Container>();
+    let charges = gs.ecs.read_storage::<Charges>();
+    let entities = gs.ecs.entities();
+
+    let inventory = (&backpack, &names, !&containers)
+        .join()
+        .filter(|item| item.0.owner == *player_entity);
+    let count = inventory.count();
+
+    let top = (25 - (count / 2)) as i32;
+    ctx.draw_box(
+        15,
+        top - 2,
+        31,
+        (count + 3) as i32,
+        RGB::named(rltk::WHITE),
+        RGB::named(rltk::BLACK),
+    );
+    let title = names
+        .get(container)
+        .map_or("container".to_string(), |n| n.name.to_string());
+    ctx.print_color(
+        18,
+        top - 2,
+        RGB::named(rltk::YELLOW),
+        RGB::named(rltk::BLACK),
+        &format!("Put What In The {}?", title),
+    );
+    ctx.print_color(
+        18,
+        top + count as i32 + 1,
+        RGB::named(rltk::YELLOW),
+        RGB::named(rltk::BLACK),
+        "ESCAPE to cancel",
+    );
+
+    let mut stashable: Vec<Entity> = Vec::new();
+    for (j, (entity, _pack, name, _not_container)) in (&entities, &backpack, &names, !&containers)
+        .join()
+        .filter(|item| item.1.owner == *player_entity)
+        .enumerate()
+    {
+        let y = top + j as i32;
+        ctx.set(
+            17,
+            y,
+            RGB::named(rltk::WHITE),
+            RGB::named(rltk::BLACK),
+            rltk::to_cp437('('),
+        );
+        ctx.set(
+            18,
+            y,
+            RGB::named(rltk::YELLOW),
+            RGB::named(rltk::BLACK),
+            97 + j as u8,
+        );
+        ctx.set(
+            19,
+            y,
+            RGB::named(rltk::WHITE),
+            RGB::named(rltk::BLACK),
+            rltk::to_cp437(')'),
+        );
+
+        ctx.print(21, y, &item_display_name(name, charges.get(entity)));
+        stashable.push(entity);
+    }
+
+    match ctx.key {
+        None => (ItemMenuResult::NoResponse, None),
+        Some(key) => match key {
+            VirtualKeyCode::Escape => (ItemMenuResult::Cancel, None),
+            _ => {
+                let selection = rltk::letter_to_option(key);
+                if selection > -1 && selection < count as i32 {
+                    return (
+                        ItemMenuResult::Selected,
+                        Some(stashable[selection as usize]),
+                    );
+                }
+                (ItemMenuResult::NoResponse, None)
+            }
+        },
+    }
+}
+
+/// Breaks `text` into lines no longer than `width`, between words.
+pub fn wrap_text(text: &str, width: usize) -> Vec<String> {
+    let mut lines: Vec<String> = Vec::new();
+    let mut line = String::new();
+    for word in text.split_whitespace() {
+        if !line.is_empty() && line.len() + 1 + word.len() > width {
+            lines.push(line);
+            line = String::new();
+        }
+        if !line.is_empty() {
+            line.push(' ');
+        }
+        line.push_str(word);
+    }
+    if !line.is_empty() {
+        lines.push(line);
+    }
+    lines
+}
+
+/// Shows what `npc` says at `node` of their conversation and the player's replies.
+/// Returns the index of the chosen reply.
+pub fn dialogue_menu(
+    gs: &mut State,
+    ctx: &mut Rltk,
+    npc: Entity,
+    node: usize,
+) -> (ItemMenuResult, Option<usize>) {
+    let conversation = match dialogue::conversation_with(&gs.ecs, npc) {
+        Some(conversation) => conversation,
+        None => return (ItemMenuResult::Cancel, None),
+    };
+    let node = match conversation.nodes.get(node) {
+        Some(node) => node,
+        None => return (ItemMenuResult::Cancel, None),
+    };
+    let names = gs.ecs.read_storage::<Name>();
+
+    let lines = wrap_text(&node.text, 54);
+    let count = node.choices.len();
+    let height = lines.len() + count + 1;
+    let top = (25 - (height / 2)) as i32;
+    ctx.draw_box(
+        10,
+        top - 2,
+        59,
+        (height + 3) as i32,
+        RGB::named(rltk::WHITE),
+        RGB::named(rltk::BLACK),
+    );
+    let title = names
+        .get(npc)
+        .map_or("Stranger".to_string(), |n| n.name.to_string());
+    ctx.print_color(
+        13,
+        top - 2,
+        RGB::named(rltk::YELLOW),
+        RGB::named(rltk::BLACK),
+        &title,
+    );
+    ctx.print_color(
+        13,
+        top + height as i32 + 1,
+        RGB::named(rltk::YELLOW),
+        RGB::named(rltk::BLACK),
+        "ESCAPE to leave",
+    );
+
+    let mut y = top;
+    for line in lines.iter() {
+        ctx.print(13, y, line);
+        y += 1;
+    }
+    y += 1;
+
+    for (j, choice) in node.choices.iter().enumerate() {
+        ctx.set(
+            12,
+            y,
+            RGB::named(rltk::WHITE),
+            RGB::named(rltk::BLACK),
+            rltk::to_cp437('('),
+        );
+        ctx.set(
+            13,
+            y,
+            RGB::named(rltk::YELLOW),
+            RGB::named(rltk::BLACK),
+            97 + j as u8,
+        );
+        ctx.set(
+            14,
+            y,
+            RGB::named(rltk::WHITE),
+            RGB::named(rltk::BLACK),
+            rltk::to_cp437(')'),
+        );
+        ctx.print_color(
+            16,
+            y,
+            RGB::named(rltk::CYAN),
+            RGB::named(rltk::BLACK),
+            &choice.text,
+        );
+        y += 1;
+    }
+
+    match ctx.key {
+        None => (ItemMenuResult::NoResponse, None),
+        Some(key) => match key {
+            VirtualKeyCode::Escape => (ItemMenuResult::Cancel, None),
+            _ => {
+                let selection = rltk::letter_to_option(key);
+                if selection > -1 && selection < count as i32 {
+                    return (ItemMenuResult::Selected, Some(selection as usize));
+                }
+                (ItemMenuResult::NoResponse, None)
+            }
+        },
+    }
+}
+
+#[derive(PartialEq, Copy, Clone)]
+pub enum ShopMenuResult {
+    Cancel,
+    NoResponse,
+    Sell,
+    Buy { item: Entity },
+}
+
+/// Lists what `shopkeeper` has for sale, with prices.
+pub fn shop_menu(gs: &mut State, ctx: &mut Rltk, shopkeeper: Entity) -> ShopMenuResult {
+    let player_entity = gs.ecs.fetch::<Entity>();
+    let names = gs.ecs.read_storage::<Name>();
+    let for_sale = gs.ecs.read_storage::<ForSale>();
+    let values = gs.ecs.read_storage::<Value>();
+    let purses = gs.ecs.read_storage::<Purse>();
+    let charges = gs.ecs.read_storage::<Charges>();
+    let entities = gs.ecs.entities();
+
+    let stock = (&for_sale, &names)
+        .join()
+        .filter(|item| item.0.shopkeeper == shopkeeper);
+    let count = stock.count();
+
+    let top = (25 - (count / 2)) as i32;
+    ctx.draw_box(
+        15,
+        top - 2,
+        45,
+        (count + 3) as i32,
+        RGB::named(rltk::WHITE),
+        RGB::named(rltk::BLACK),
+    );
+    let gold = purses.get(*player_entity).map_or(0, |p| p.gold);
+    ctx.print_color(
+        18,
+        top - 2,
+        RGB::named(rltk::YELLOW),
+        RGB::named(rltk::BLACK),
+        &format!("Buy What? (you have {} gold)", gold),
+    );
+    ctx.print_color(
+        18,
+        top + count as i32 + 1,
+        RGB::named(rltk::YELLOW),
+        RGB::named(rltk::BLACK),
+        "TAB to sell, ESC to leave",
+    );
+    if count == 0 {
+        ctx.print(21, top, "(sold out)");
+    }
+
+    let mut buyable: Vec<Entity> = Vec::new();
+    for (j, (entity, _sale, name)) in (&entities, &for_sale, &names)
+        .join()
+        .filter(|item| item.1.shopkeeper == shopkeeper)
+        .enumerate()
+    {
+        let y = top + j as i32;
+        ctx.set(
+            17,
+            y,
+            RGB::named(rltk::WHITE),
+            RGB::named(rltk::BLACK),
+            rltk::to_cp437('('),
+        );
+        ctx.set(
+            18,
+            y,
+            RGB::named(rltk::YELLOW),
+            RGB::named(rltk::BLACK),
+            97 + j as u8,
+        );
+        ctx.set(
+            19,
+            y,
+            RGB::named(rltk::WHITE),
+            RGB::named(rltk::BLACK),
+            rltk::to_cp437(')'),
+        );
+
+        ctx.print(21, y, &item_display_name(name, charges.get(entity)));
+        let price = values.get(entity).map_or(0, |v| v.gold);
+        ctx.print_color(
+            53,
+            y,
+            RGB::named(rltk::GOLD),
+            RGB::named(rltk::BLACK),
+            &format!("{:>5}", price),
+        );
+        buyable.push(entity);
+    }
+
+    match ctx.key {
+        None => ShopMenuResult::NoResponse,
+        Some(key) => match key {
+            VirtualKeyCode::Escape => ShopMenuResult::Cancel,
+            VirtualKeyCode::Tab => ShopMenuResult::Sell,
+            _ => {
+                let selection = rltk::letter_to_option(key);
+                if selection > -1 && selection < count as i32 {
+                    return ShopMenuResult::Buy {
+                        item: buyable[selection as usize],
+                    };
+                }
+                ShopMenuResult::NoResponse
+            }
+        },
+    }
+}
+
+/// Lists backpack items with what `shopkeeper` would pay for them. Full bags would
+/// take their contents with them, so containers aren't offered.
+pub fn sell_item_menu(
+    gs: &mut State,
+    ctx: &mut Rltk,
+    shopkeeper: Entity,
+) -> (ItemMenuResult, Option<Entity>) {
+    let player_entity = gs.ecs.fetch::<Entity>();
+    let names = gs.ecs.read_storage::<Name>();
+    let backpack = gs.ecs.read_storage::<InBackpack>();
+    let containers = gs.ecs.read_storage::<Container>();
+    let values = gs.ecs.read_storage::<Value>();
+    let purses = gs.ecs.read_storage::<Purse>();
+    let charges = gs.ecs.read_storage::<Charges>();
+    let entities = gs.ecs.entities();
+
+    let inventory = (&backpack, &names, !&containers)
+        .join()
+        .filter(|item| item.0.owner == *player_entity);
+    let count = inventory.count();
+
+    let top = (25 - (count / 2)) as i32;
+    ctx.draw_box(
+        15,
+        top - 2,
+        45,
+        (count + 3) as i32,
+        RGB::named(rltk::WHITE),
+        RGB::named(rltk::BLACK),
+    );
+    let keeper_gold = purses.get(shopkeeper).map_or(0, |p| p.gold);
+    ctx.print_color(
+        18,
+        top - 2,
+        RGB::named(rltk::YELLOW),
+        RGB::named(rltk::BLACK),
+        &format!("Sell What? (shop has {} gold)", keeper_gold),
+    );
+    ctx.print_color(
+        18,
+        top + count as i32 + 1,
+        RGB::named(rltk::YELLOW),
+        RGB::named(rltk::BLACK),
+        "ESCAPE to cancel",
+    );
+
+    let mut sellable: Vec<Entity> = Vec::new();
+    for (j, (entity, _pack, name, _not_container)) in (&entities, &backpack, &names, !&containers)
+        .join()
+        .filter(|item| item.1.owner == *player_entity)
+        .enumerate()
+    {
+        let y = top + j as i32;
+        ctx.set(
+            17,
+            y,
+            RGB::named(rltk::WHITE),
+            RGB::named(rltk::BLACK),
+            rltk::to_cp437('('),
+        );
+        ctx.set(
+            18,
+            y,
+            RGB::named(rltk::YELLOW),
+            RGB::named(rltk::BLACK),
+            97 + j as u8,
+        );
+        ctx.set(
+            19,
+            y,
+            RGB::named(rltk::WHITE),
+            RGB::named(rltk::BLACK),
+            rltk::to_cp437(')'),
+        );
+
+        ctx.print(21, y, &item_display_name(name, charges.get(entity)));
+        let price = match values.get(entity) {
+            Some(value) => format!("{:>5}", shop::sell_price(value)),
+            None => "    -".to_string(),
+        };
+        ctx.print_color(
+            53,
+            y,
+            RGB::named(rltk::GOLD),
+            RGB::named(rltk::BLACK),
+            &price,
+        );
+        sellable.push(entity);
+    }
+
+    match ctx.key {
+        None => (ItemMenuResult::NoResponse, None),
+        Some(key) => match key {
+            VirtualKeyCode::Escape => (ItemMenuResult::Cancel, None),
+            _ => {
+                let selection = rltk::letter_to_option(key);
+                if selection > -1 && selection < count as i32 {
+                    return (ItemMenuResult::Selected, Some(sellable[selection as usize]));
+                }
+                (ItemMenuResult::NoResponse, None)
+            }
+        },
+    }
+}
+
+const AUTO_PICKUP_CATEGORIES: [(ItemCategory, &str); 9] = [
+    (ItemCategory::Food, "Food"),
+    (ItemCategory::Potion, "Potions"),
+    (ItemCategory::Scroll, "Scrolls"),
+    (ItemCategory::Wand, "Wands and Staves"),
+    (ItemCategory::Weapon, "Weapons"),
+    (ItemCategory::Armour, "Armour"),
+    (ItemCategory::Container, "Bags"),
+    (ItemCategory::Book, "Books"),
+    (ItemCategory::Gold, "Gold"),
+];
+
+/// Lets the player toggle which categories of item are picked up automatically.
+/// Selecting a category flips it; the caller stays in the menu until it is cancelled.
+pub fn auto_pickup_menu(gs: &mut State, ctx: &mut Rltk) -> (ItemMenuResult, Option<ItemCategory>) {
+    let config = gs.ecs.fetch::<GameConfig>();
+    let count = AUTO_PICKUP_CATEGORIES.len();
+
+    let top = (25 - (count / 2)) as i32;
+    ctx.draw_box(
+        15,
+        top - 2,
+        31,
+        (count + 3) as i32,
+        RGB::named(rltk::WHITE),
+        RGB::named(rltk::BLACK),
+    );
+    ctx.print_color(
+        18,
+        top - 2,
+        RGB::named(rltk::YELLOW),
+        RGB::named(rltk::BLACK),
+        "Auto Pick Up",
+    );
+    ctx.print_color(
+        18,
+        top + count as i32 + 1,
+        RGB::named(rltk::YELLOW),
+        RGB::named(rltk::BLACK),
+        "ESCAPE when done",
+    );
+
+    for (j, (category, label)) in AUTO_PICKUP_CATEGORIES.iter().enumerate() {
+        let y = top + j as i32;
+        ctx.set(
+            17,
+            y,
+            RGB::named(rltk::WHITE),
+            RGB::named(rltk::BLACK),
+            rltk::to_cp437('('),
+        );
+        ctx.set(
+            18,
+            y,
+            RGB::named(rltk::YELLOW),
+            RGB::named(rltk::BLACK),
+            97 + j as u8,
+        );
+        ctx.set(
+            19,
+            y,
+            RGB::named(rltk::WHITE),
+            RGB::named(rltk::BLACK),
+            rltk::to_cp437(')'),
+        );
+
+        if config.auto_pickup.contains(category) {
+            ctx.print_color(
+                21,
+                y,
+                RGB::named(rltk::GREEN),
+                RGB::named(rltk::BLACK),
+                &format!("[x] {}", label),
+            );
+        } else {
+            ctx.print(21, y, &format!("[ ] {}", label));
+        }
+    }
+
+    match ctx.key {
+        None => (ItemMenuResult::NoResponse, None),
+        Some(key) => match key {
+            VirtualKeyCode::Escape | VirtualKeyCode::A => (ItemMenuResult::Cancel, None),
+            _ => {
+                let selection = rltk::letter_to_option(key);
+                if selection > -1 && selection < count as i32 {
+                    return (
+                        ItemMenuResult::Selected,
+                        Some(AUTO_PICKUP_CATEGORIES[selection as usize].0),
+                    );
+                }
+                (ItemMenuResult::NoResponse, None)
+            }
+        },
+    }
+}
+
+pub fn drop_item_menu(gs: &mut State, ctx: &mut Rltk) -> (ItemMenuResult, Option<Entity>) {
+    let player_entity = gs.ecs.fetch::<Entity>();
+    let names = gs.ecs.read_storage::<Name>();
+    let charges = gs.ecs.read_storage::<Charges>();
+    let backpack = gs.ecs.read_storage::<InBackpack>();
+    let entities = gs.ecs.entities();
+
+    let inventory = (&backpack, &names)
+        .join()
+        .filter(|item| item.0.owner == *player_entity);
+    let count = inventory.count();
+
+    let mut y = (25 - (count / 2)) as i32;
+    ctx.draw_box(
+        15,
+        y - 2,
+        31,
+        (count + 3) as i32,
+        RGB::named(rltk::WHITE),
+        RGB::named(rltk::BLACK),
+    );
+    ctx.print_color(
+        18,
+        y - 2,
+        RGB::named(rltk::YELLOW),
+        RGB::named(rltk::BLACK),
+        "Drop Which Item?",
+    );
+    ctx.print_color(
+        18,
+        y + count as i32 + 1,
+        RGB::named(rltk::YELLOW),
+        RGB::named(rltk::BLACK),
+        "ESCAPE to cancel",
+    );
+
+    let mut equippable: Vec<Entity> = Vec::new();
+    let mut j = 0;
+    for (entity, _pack, name) in (&entities, &backpack, &names)
+        .join()
+        .filter(|item| item.1.owner == *player_entity)
+    {
+        ctx.set(
+            17,
+            y,
+            RGB::named(rltk::WHITE),
+            RGB::named(rltk::BLACK),
+            rltk::to_cp437('('),
+        );
+        ctx.set(
+            18,
+            y,
+            RGB::named(rltk::YELLOW),
+            RGB::named(rltk::BLACK),
+            97 + j as u8,
+        );
+        ctx.set(
+            19,
+            y,
+            RGB::named(rltk::WHITE),
+            RGB::named(rltk::BLACK),
+            rltk::to_cp437(')'),
+        );
+
+        ctx.print(21, y, &item_display_name(name, charges.get(entity)));
+        equippable.push(entity);
+        y += 1;
+        j += 1;
+    }
+
+    match ctx.key {
+        None => (ItemMenuResult::NoResponse, None),
+        Some(key) => match key {
+            VirtualKeyCode::Escape | VirtualKeyCode::D => (ItemMenuResult::Cancel, None),
+            _ => {
+                let selection = rltk::letter_to_option(key);
+                if selection > -1 && selection < count as i32 {
+                    return (
+                        ItemMenuResult::Selected,
+                        Some(equippable[selection as usize]),
+                    );
+                }
+                (ItemMenuResult::NoResponse, None)
+            }
+        },
+    }
+}
+
+#[derive(PartialEq, Copy, Clone)]
+pub enum MainMenuSelection {
+    ResumeAutosave,
+    NewGame,
+    LoadGame,
+    HallOfFame,
+    Quit,
+}
+
+#[derive(PartialEq, Copy, Clone)]
+pub enum MainMenuResult {
+    NoSelection { selected: MainMenuSelection },
+    Selected { selected: MainMenuSelection },
+}
+
+/// Shown under the main menu, e.g. to say why a save couldn't be loaded.
+#[derive(Default)]
+pub struct MainMenuMessage {
+    pub text: Option<String>,
+}
+
+pub fn main_menu(gs: &mut State, ctx: &mut Rltk) -> MainMenuResult {
+    let mut options = Vec::new();
+    if saveload_system::does_autosave_exist(&gs.ecs) {
+        options.push((MainMenuSelection::ResumeAutosave, "Resume Autosave"));
+    }
+    options.push((MainMenuSelection::NewGame, "Begin New Game"));
+    if saveload_system::does_save_exist(&gs.ecs) {
+        options.push((MainMenuSelection::LoadGame, "Load Game"));
+    }
+    options.push((MainMenuSelection::HallOfFame, "Hall of Fame"));
+    options.push((MainMenuSelection::Quit, "Quit"));
+
+    let runstate = gs.ecs.fetch::<RunState>();
+
+    let assets = gs.ecs.fetch::<RexAssets>();
+    ctx.render_xp_sprite(&assets.menu, 0, 0);
+
+    ctx.draw_box_double(
+        24,
+        18,
+        31,
+        11,
+        RGB::named(rltk::WHEAT),
+        RGB::named(rltk::BLACK),
+    );
+    ctx.print_color_centered(
+        20,
+        RGB::named(rltk::YELLOW),
+        RGB::named(rltk::BLACK),
+        "Rust Roguelike Tutorial",
+    );
+    ctx.print_color_centered(
+        21,
+        RGB::named(rltk::CYAN),
+        RGB::named(rltk::BLACK),
+        "copied by Jacobious52",
+    );
+    ctx.print_color_centered(
+        22,
+        RGB::named(rltk::GRAY),
+        RGB::named(rltk::BLACK),
+        "Use Up/Down Arrows and Enter",
+    );
+
+    if let Some(text) = &gs.ecs.fetch::<MainMenuMessage>().text {
+        for (i, line) in wrap_text(text, 60).iter().enumerate() {
+            ctx.print_color_centered(
+                31 + i as i32,
+                RGB::named(rltk::RED),
+                RGB::named(rltk::BLACK),
+                line,
+            );
+        }
+    }
+
+    let selection = match *runstate {
+        RunState::MainMenu { menu_selection } => menu_selection,
+        _ => MainMenuSelection::NewGame,
+    };
+    // Whatever was selected may no longer be on offer, e.g. once the last save is gone
+    let selected = options
+        .iter()
+        .position(|(option, _)| *option == selection)
+        .unwrap_or_else(|| {
+            options
+                .iter()
+                .position(|(option, _)| *option == MainMenuSelection::NewGame)
+                .unwrap()
+        });
+
+    for (i, (_, text)) in options.iter().enumerate() {
+        let fg = if i == selected {
+            RGB::named(rltk::MAGENTA)
+        } else {
+            RGB::named(rltk::WHITE)
+        };
+        ctx.print_color_centered(24 + i as i32, fg, RGB::named(rltk::BLACK), text);
+    }
+
+    let selection = options[selected].0;
+    match ctx.key {
+        None => MainMenuResult::NoSelection {
+            selected: selection,
+        },
+        Some(key) => match key {
+            VirtualKeyCode::Escape => MainMenuResult::NoSelection {
+                selected: MainMenuSelection::Quit,
+            },
+            VirtualKeyCode::Up => MainMenuResult::NoSelection {
+                selected: options[(selected + options.len() - 1) % options.len()].0,
+            },
+            VirtualKeyCode::Down => MainMenuResult::NoSelection {
+                selected: options[(selected + 1) % options.len()].0,
+            },
+            VirtualKeyCode::Return => MainMenuResult::Selected {
+                selected: selection,
+            },
+            _ => MainMenuResult::NoSelection {
+                selected: selection,
+            },
+        },
+    }
+}
+
+#[derive(PartialEq, Copy, Clone)]
+pub enum SlotMenuResult {
+    Cancel,
+    NoResponse { selected: usize },
+    Selected { slot: usize },
+    Delete { slot: usize },
+}
+
+/// Two lines describing a save slot: how far the run got, and when it was saved.
+fn describe_slot(header: Option<&SaveHeader>) -> (String, String) {
+    let header = match header {
+        Some(header) => header,
+        None => return ("Empty".to_string(), String::new()),
+    };
+    let progress = match &header.summary {
+        Some(summary) => format!(
+            "Depth {}, turn {}, HP {}/{}",
+            summary.depth, summary.turns, summary.hp, summary.max_hp
+        ),
+        None => "Saved by an older version".to_string(),
+    };
+    let saved_at = format_time(header.saved_at, "%Y-%m-%d %H:%M")
+        .map_or(String::new(), |time| format!("Saved {}", time));
+    (progress, saved_at)
+}
+
+/// Draws the save slots with `selected` highlighted. When loading, this is part of
+/// the main menu.
+pub fn draw_slots(gs: &State, ctx: &mut Rltk, loading: bool, selected: usize) {
+    if loading {
+        let assets = gs.ecs.fetch::<RexAssets>();
+        ctx.render_xp_sprite(&assets.menu, 0, 0);
+    }
+
+    let top = 16;
+    ctx.draw_box(
+        10,
+        top,
+        59,
+        (SAVE_SLOTS * 3 + 2) as i32,
+        RGB::named(rltk::WHITE),
+        RGB::named(rltk::BLACK),
+    );
+    ctx.print_color(
+        13,
+        top,
+        RGB::named(rltk::YELLOW),
+        RGB::named(rltk::BLACK),
+        if loading { "Load Game" } else { "Save Game" },
+    );
+    ctx.print_color(
+        13,
+        top + (SAVE_SLOTS * 3 + 2) as i32,
+        RGB::named(rltk::YELLOW),
+        RGB::named(rltk::BLACK),
+        if loading {
+            "Enter to load, D to delete, ESCAPE to cancel"
+        } else {
+            "Enter to save, ESCAPE to cancel"
+        },
+    );
+
+    let headers = gs.ecs.fetch::<SlotHeaders>();
+    for slot in 0..SAVE_SLOTS {
+        let y = top + 2 + slot as i32 * 3;
+        let (progress, saved_at) = describe_slot(headers.get(slot));
+        let fg = if slot == selected {
+            RGB::named(rltk::MAGENTA)
+        } else {
+            RGB::named(rltk::WHITE)
+        };
+        ctx.print_color(
+            13,
+            y,
+            fg,
+            RGB::named(rltk::BLACK),
+            &format!("Slot {}: {}", slot + 1, progress),
+        );
+        ctx.print_color(
+            21,
+            y + 1,
+            RGB::named(rltk::GRAY),
+            RGB::named(rltk::BLACK),
+            &saved_at,
+        );
+    }
+}
+
+/// Lets the player pick a save slot. Only saves can be loaded or deleted, but any
+/// slot can be saved to.
+pub fn slot_menu(gs: &State, ctx: &mut Rltk, loading: bool, selected: usize) -> SlotMenuResult {
+    draw_slots(gs, ctx, loading, selected);
+
+    let occupied = gs.ecs.fetch::<SlotHeaders>().get(selected).is_some();
+    match ctx.key {
+        None => SlotMenuResult::NoResponse { selected },
+        Some(key) => match key {
+            VirtualKeyCode::Escape => SlotMenuResult::Cancel,
+            VirtualKeyCode::Up => SlotMenuResult::NoResponse {
+                selected: (selected + SAVE_SLOTS - 1) % SAVE_SLOTS,
+            },
+            VirtualKeyCode::Down => SlotMenuResult::NoResponse {
+                selected: (selected + 1) % SAVE_SLOTS,
+            },
+            VirtualKeyCode::Return if occupied || !loading => {
+                SlotMenuResult::Selected { slot: selected }
+            }
+            VirtualKeyCode::D if occupied && loading => SlotMenuResult::Delete { slot: selected },
+            _ => SlotMenuResult::NoResponse { selected },
+        },
+    }
+}
+
+/// Asks a yes or no question; Selected means yes.
+pub fn confirm(ctx: &mut Rltk, question: &str) -> ItemMenuResult {
+    let width = question.len() as i32 + 4;
+    ctx.draw_box(
+        40 - width / 2,
+        22,
+        width,
+        4,
+        RGB::named(rltk::WHITE),
+        RGB::named(rltk::BLACK),
+    );
+    ctx.print_color_centered(
+        23,
+        RGB::named(rltk::WHITE),
+        RGB::named(rltk::BLACK),
+        question,
+    );
+    ctx.print_color_centered(
+        25,
+        RGB::named(rltk::YELLOW),
+        RGB::named(rltk::BLACK),
+        "Y / N",
+    );
+
+    match ctx.key {
+        None => ItemMenuResult::NoResponse,
+        Some(key) => match key {
+            VirtualKeyCode::Y => ItemMenuResult::Selected,
+            VirtualKeyCode::N | VirtualKeyCode::Escape => ItemMenuResult::Cancel,
+            _ => ItemMenuResult::NoResponse,
+        },
+    }
+}
+
+pub fn remove_item_menu(gs: &mut State, ctx: &mut Rltk) -> (ItemMenuResult, Option<Entity>) {
+    let player_entity = gs.ecs.fetch::<Entity>();
+    let names = gs.ecs.read_storage::<Name>();
+    let backpack = gs.ecs.read_storage::<Equipped>();
+    let entities = gs.ecs.entities();
+
+    let inventory = (&backpack, &names)
+        .join()
+        .filter(|item| item.0.owner == *player_entity);
+    let count = inventory.count();
+
+    let mut y = (25 - (count / 2)) as i32;
+    ctx.draw_box(
+        15,
+        y - 2,
+        31,
+        (count + 3) as i32,
+        RGB::named(rltk::WHITE),
+        RGB::named(rltk::BLACK),
+    );
+    ctx.print_color(
+        18,
+        y - 2,
+        RGB::named(rltk::YELLOW),
+        RGB::named(rltk::BLACK),
+        "Remove Which Item?",
+    );
+    ctx.print_color(
+        18,
+        y + count as i32 + 1,
+        RGB::named(rltk::YELLOW),
+        RGB::named(rltk::BLACK),
+        "ESCAPE to cancel",
+    );
+
+    let mut equippable: Vec<Entity> = Vec::new();
+    let mut j = 0;
+    for (entity, _pack, name) in (&entities, &backpack, &names)
+        .join()
+        .filter(|item| item.1.owner == *player_entity)
+    {
+        ctx.set(
+            17,
+            y,
+            RGB::named(rltk::WHITE),
+            RGB::named(rltk::BLACK),
+            rltk::to_cp437('('),
+        );
+        ctx.set(
+            18,
+            y,
+            RGB::named(rltk::YELLOW),
+            RGB::named(rltk::BLACK),
+            97 + j as u8,
+        );
+        ctx.set(
+            19,
+            y,
+            RGB::named(rltk::WHITE),
+            RGB::named(rltk::BLACK),
+            rltk::to_cp437(')'),
+        );
+
+        ctx.print(21, y, &name.name.to_string());
+        equippable.push(entity);
+        y += 1;
+        j += 1;
+    }
+
+    match ctx.key {
+        None => (ItemMenuResult::NoResponse, None),
+        Some(key) => match key {
+            VirtualKeyCode::Escape => (ItemMenuResult::Cancel, None),
+            _ => {
+                let selection = rltk::letter_to_option(key);
+                if selection > -1 && selection < count as i32 {
+                    return (
+                        ItemMenuResult::Selected,
+                        Some(equippable[selection as usize]),
+                    );
+                }
+                (ItemMenuResult::NoResponse, None)
+            }
+        },
+    }
+}
+
+#[derive(PartialEq, Copy, Clone)]
+pub enum GameOverResult {
+    NoSelection,
+    QuitToMenu,
+}
+
+/// Sums up the run that just ended.
+pub fn game_over(ecs: &World, ctx: &mut Rltk) -> GameOverResult {
+    let recap = ecs.fetch::<morgue::DeathRecap>();
+    ctx.print_color_centered(
+        1,
+        RGB::named(rltk::YELLOW),
+        RGB::named(rltk::BLACK),
+        "Your journey has ended!",
+    );
+
+    // Whatever doesn't fit is still in the morgue file
+    for (y, line) in (3..45).zip(recap.lines().iter()) {
+        let line: String = line.chars().take(78).collect();
+        ctx.print_color(
+            1,
+            y,
+            RGB::named(rltk::WHITE),
+            RGB::named(rltk::BLACK),
+            &line,
+        );
+    }
+
+    let notes = format!("{} {}", recap.morgue, recap.hall_of_fame);
+    for (y, line) in (45..48).zip(wrap_text(&notes, 78).iter()) {
+        ctx.print_color(1, y, RGB::named(rltk::GRAY), RGB::named(rltk::BLACK), line);
+    }
+    ctx.print_color_centered(
+        48,
+        RGB::named(rltk::MAGENTA),
+        RGB::named(rltk::BLACK),
+        "Press any key to return to the menu.",
+    );
+
+    match ctx.key {
+        None => GameOverResult::NoSelection,
+        Some(_) => GameOverResult::QuitToMenu,
+    }
+}
+
+/// Lists the best runs so far, until a key is pressed.
+pub fn hall_of_fame(ecs: &World, ctx: &mut Rltk) -> GameOverResult {
+    ctx.print_color_centered(
+        3,
+        RGB::named(rltk::YELLOW),
+        RGB::named(rltk::BLACK),
+        "Hall of Fame",
+    );
+
+    match &ecs.fetch::<hall_of_fame::LoadedHallOfFame>().entries {
+        Ok(entries) if entries.is_empty() => ctx.print_color_centered(
+            6,
+            RGB::named(rltk::GRAY),
+            RGB::named(rltk::BLACK),
+            "No runs yet. Go and make your name!",
+        ),
+        Ok(entries) => {
+            ctx.print_color(
+                2,
+                6,
+                RGB::named(rltk::CYAN),
+                RGB::named(rltk::BLACK),
+                "  # Score Depth Kills Turns Date             How it ended",
+            );
+            for (rank, entry) in entries.iter().enumerate() {
+                let date = format_time(entry.date, "%Y-%m-%d %H:%M").unwrap_or_default();
+                let line = format!(
+                    "{:>3} {:>5} {:>5} {:>5} {:>5} {:<16} {}",
+                    rank + 1,
+                    entry.score,
+                    entry.depth,
+                    entry.kills,
+                    entry.turns,
+                    date,
+                    entry.cause
+                );
+                let line: String = line.chars().take(76).collect();
+                ctx.print_color(
+                    2,
+                    8 + rank as i32 * 2,
+                    RGB::named(rltk::WHITE),
+                    RGB::named(rltk::BLACK),
+                    &line,
+                );
+            }
+        }
+        Err(err) => {
+            for (i, line) in wrap_text(&format!("Unable to read the Hall of Fame: {}", err), 60)
+                .iter()
+                .enumerate()
+            {
+                ctx.print_color_centered(
+                    6 + i as i32,
+                    RGB::named(rltk::RED),
+                    RGB::named(rltk::BLACK),
+                    line,
+                );
+            }
+        }
+    }
+
+    ctx.print_color_centered(
+        48,
         RGB::named(rltk::MAGENTA),
         RGB::named(rltk::BLACK),
         "Press any key to return to the menu.",
@@ -0,0 +1,173 @@
+use super::{
+    game_log::{RunStats, TurnCounter},
+    save_storage::{self, SaveStore},
+    Map,
+};
+use serde::{Deserialize, Serialize};
+use specs::prelude::*;
+use std::io;
+
+/// How many runs the hall of fame remembers.
+pub const HALL_OF_FAME_SIZE: usize = 10;
+
+/// Kept beside the saves, so it outlives every run.
+const HALL_OF_FAME_FILE: &str = "hall_of_fame.json";
+
+/// A finished run, as remembered by the hall of fame.
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+pub struct HallOfFameEntry {
+    pub score: i32,
+    pub depth: i32,
+    pub kills: i32,
+    pub turns: i32,
+    /// How the run ended, e.g. "Killed by an Orc".
+    pub cause: String,
+    /// Seconds since the Unix epoch, or 0 if undated.
+    pub date: u64,
+}
+
+/// The hall of fame as read when its screen was opened, rather than on every frame
+/// the screen is drawn.
+pub struct LoadedHallOfFame {
+    pub entries: Result<Vec<HallOfFameEntry>, String>,
+}
+
+impl Default for LoadedHallOfFame {
+    fn default() -> LoadedHallOfFame {
+        LoadedHallOfFame {
+            entries: Ok(Vec::new()),
+        }
+    }
+}
+
+/// Going deeper counts for most, then kills, with a little for lasting longer.
+pub fn score(depth: i32, kills: i32, turns: i32) -> i32 {
+    depth * 1000 + kills * 25 + turns / 10
+}
+
+/// The best runs so far, best first. Having no hall of fame yet isn't an error.
+pub fn load(ecs: &World) -> io::Result<Vec<HallOfFameEntry>> {
+    let storage = ecs.fetch::<SaveStore>().storage.clone();
+    if !storage.exists(HALL_OF_FAME_FILE) {
+        return Ok(Vec::new());
+    }
+    let data = storage.read(HALL_OF_FAME_FILE)?;
+    Ok(serde_json::from_slice(&data)?)
+}
+
+/// Rereads the hall of fame, for its screen.
+pub fn refresh(ecs: &World) {
+    let entries = load(ecs).map_err(|err| err.to_string());
+    ecs.fetch_mut::<LoadedHallOfFame>().entries = entries;
+}
+
+/// Adds the entry, keeping the best runs in order. Ties go to the earlier run.
+fn insert(entries: &mut Vec<HallOfFameEntry>, entry: HallOfFameEntry) -> Option<usize> {
+    let rank = entries
+        .iter()
+        .position(|other| entry.score > other.score)
+        .unwrap_or(entries.len());
+    if rank >= HALL_OF_FAME_SIZE {
+        return None;
+    }
+    entries.insert(rank, entry);
+    entries.truncate(HALL_OF_FAME_SIZE);
+    Some(rank)
+}
+
+/// Scores a finished run, whether the player died or retired, and adds it to the
+/// hall of fame. Returns where it ranks, if it made it in at all.
+pub fn record_run(ecs: &World, cause: &str) -> io::Result<Option<usize>> {
+    // An unreadable hall of fame is left alone rather than replaced
+    let mut entries = load(ecs)?;
+
+    let run_stats = ecs.fetch::<RunStats>();
+    let depth = i32::max(run_stats.deepest, ecs.fetch::<Map>().depth);
+    let kills = run_stats.kills.values().sum();
+    let turns = ecs.fetch::<TurnCounter>().turns;
+    let entry = HallOfFameEntry {
+        score: score(depth, kills, turns),
+        depth,
+        kills,
+        turns,
+        cause: cause.to_string(),
+        date: save_storage::now(),
+    };
+
+    let rank = insert(&mut entries, entry);
+    if rank.is_some() {
+        let data = serde_json::to_vec_pretty(&entries)?;
+        ecs.fetch::<SaveStore>()
+            .storage
+            .write(HALL_OF_FAME_FILE, &data)?;
+    }
+    Ok(rank)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::save_storage::MemoryStorage;
+
+    fn entry(score: i32) -> HallOfFameEntry {
+        HallOfFameEntry {
+            score,
+            depth: 1,
+            kills: 0,
+            turns: 0,
+            cause: "Starved to death".to_string(),
+            date: 0,
+        }
+    }
+
+    fn test_world(depth: i32, kills: i32, turns: i32) -> World {
+        let mut ecs = World::new();
+        ecs.insert(SaveStore::new(MemoryStorage::default()));
+        ecs.insert(Map::new_map_rooms_and_corridors(depth));
+        ecs.insert(TurnCounter { turns });
+        let mut run_stats = RunStats::default();
+        run_stats.kills.insert("Orc".to_string(), kills);
+        ecs.insert(run_stats);
+        ecs
+    }
+
+    #[test]
+    fn only_the_best_runs_are_kept() {
+        let mut entries = Vec::new();
+        for score in 1..=HALL_OF_FAME_SIZE as i32 {
+            insert(&mut entries, entry(score * 10));
+        }
+        assert_eq!(entries[0].score, HALL_OF_FAME_SIZE as i32 * 10);
+
+        assert_eq!(insert(&mut entries, entry(5)), None);
+        assert_eq!(insert(&mut entries, entry(15)), Some(HALL_OF_FAME_SIZE - 1));
+        assert_eq!(entries.len(), HALL_OF_FAME_SIZE);
+        assert_eq!(entries.last().unwrap().score, 15);
+    }
+
+    #[test]
+    fn runs_are_remembered() {
+        let ecs = test_world(2, 4, 200);
+        ecs.fetch_mut::<RunStats>().deepest = 3;
+        assert_eq!(record_run(&ecs, "Killed by an Orc").unwrap(), Some(0));
+
+        let entries = load(&ecs).unwrap();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].score, score(3, 4, 200));
+        assert_eq!(entries[0].kills, 4);
+        assert_eq!(entries[0].cause, "Killed by an Orc");
+
+        *ecs.fetch_mut::<TurnCounter>() = TurnCounter { turns: 500 };
+        assert_eq!(record_run(&ecs, "Starved to death").unwrap(), Some(0));
+        assert_eq!(load(&ecs).unwrap()[1].cause, "Killed by an Orc");
+    }
+
+    #[test]
+    fn unreadable_hall_of_fame_is_not_replaced() {
+        let ecs = test_world(2, 0, 10);
+        let storage = ecs.fetch::<SaveStore>().storage.clone();
+        storage.write(HALL_OF_FAME_FILE, b"not json").unwrap();
+        assert!(record_run(&ecs, "Died").is_err());
+        assert_eq!(storage.read(HALL_OF_FAME_FILE).unwrap(), b"not json");
+    }
+}
@@ -1,6 +1,27 @@
-use super::{game_log::GameLog, HungerClock, HungerState, RunState, SufferDamage};
+use super::{
+    game_log::GameLog, Corpse, DamageSource, HungerClock, HungerState, Name, Perishable, RunState,
+    SufferDamage,
+};
 use specs::prelude::*;
 
+/// Eating past this point risks choking; nutrition never goes above it.
+pub const MAX_NUTRITION: i32 = 1000;
+const WELL_FED_ABOVE: i32 = 400;
+const HUNGRY_BELOW: i32 = 200;
+
+/// Which hunger band a nutrition value falls in.
+pub fn hunger_state(nutrition: i32) -> HungerState {
+    if nutrition > WELL_FED_ABOVE {
+        HungerState::WellFed
+    } else if nutrition > HUNGRY_BELOW {
+        HungerState::Normal
+    } else if nutrition > 0 {
+        HungerState::Hungry
+    } else {
+        HungerState::Starving
+    }
+}
+
 pub struct HungerSystem;
 
 impl<'a> System<'a> for HungerSystem {
@@ -36,42 +57,83 @@ impl<'a> System<'a> for HungerSystem {
             }
 
             if proceed {
-                clock.duration -= 1;
-                if clock.duration < 1 {
-                    match clock.state {
-                        HungerState::WellFed => {
-                            clock.state = HungerState::Normal;
-                            clock.duration = 200;
-                            if entity == *player_entity {
-                                log.entries
-                                    .insert(0, "You are no longer well fed.".to_string());
-                            }
-                        }
-                        HungerState::Normal => {
-                            clock.state = HungerState::Hungry;
-                            clock.duration = 200;
-                            if entity == *player_entity {
-                                log.entries.insert(0, "You are hungry.".to_string());
-                            }
-                        }
-                        HungerState::Hungry => {
-                            clock.state = HungerState::Starving;
-                            clock.duration = 200;
-                            if entity == *player_entity {
-                                log.entries.insert(0, "You are starving!".to_string());
+                clock.nutrition = i32::max(0, clock.nutrition - 1);
+                let new_state = hunger_state(clock.nutrition);
+                if new_state != clock.state {
+                    clock.state = new_state;
+                    if entity == *player_entity {
+                        match new_state {
+                            HungerState::WellFed => {}
+                            HungerState::Normal => log
+                                .entries
+                                .insert(0, "You are no longer well fed.".to_string()),
+                            HungerState::Hungry => {
+                                log.entries.insert(0, "You are hungry.".to_string())
                             }
-                        }
-                        HungerState::Starving => {
-                            // Inflict damage from hunger
-                            if entity == *player_entity {
-                                log.entries.insert(0, "Your hunger pangs are getting painful! You suffer 1 hp damage.".to_string());
+                            HungerState::Starving => {
+                                log.entries.insert(0, "You are starving!".to_string())
                             }
-                            inflict_damage
-                                .insert(entity, SufferDamage { amount: 1 })
-                                .expect("Unable to do damage");
                         }
                     }
                 }
+
+                if clock.state == HungerState::Starving {
+                    // Inflict damage from hunger
+                    if entity == *player_entity {
+                        log.entries.insert(
+                            0,
+                            "Your hunger pangs are getting painful! You suffer 1 hp damage."
+                                .to_string(),
+                        );
+                    }
+                    inflict_damage
+                        .insert(
+                            entity,
+                            SufferDamage {
+                                amount: 1,
+                                source: DamageSource::Hunger,
+                            },
+                        )
+                        .expect("Unable to do damage");
+                }
+            }
+        }
+    }
+}
+
+/// Corpses carry on rotting after they go bad, until nothing is left.
+const CORPSE_ROTS_AWAY_AT: i32 = -100;
+
+/// Ages perishable food once per player turn, wherever it is.
+pub struct FoodDecaySystem;
+
+impl<'a> System<'a> for FoodDecaySystem {
+    type SystemData = (
+        Entities<'a>,
+        ReadExpect<'a, RunState>,
+        WriteStorage<'a, Perishable>,
+        WriteStorage<'a, Name>,
+        ReadStorage<'a, Corpse>,
+    );
+
+    fn run(&mut self, data: Self::SystemData) {
+        let (entities, runstate, mut perishables, mut names, corpses) = data;
+
+        if *runstate != RunState::PlayerTurn {
+            return;
+        }
+
+        for (entity, food, name) in (&entities, &mut perishables, &mut names).join() {
+            let is_corpse = corpses.get(entity).is_some();
+            if food.is_rotten() && !is_corpse {
+                continue;
+            }
+            food.freshness -= 1;
+            if food.freshness == 0 {
+                name.name = format!("Rotten {}", name.name);
+            }
+            if food.freshness <= CORPSE_ROTS_AWAY_AT {
+                entities.delete(entity).expect("Unable to delete");
             }
         }
     }
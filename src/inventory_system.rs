@@ -1,6 +1,9 @@
 use super::{
-    components::*, game_log::GameLog, map::Map, particle_system::ParticleBuilder, RunState,
+    components::*, encumbrance_system::carried_weight, game_log::GameLog, hunger_system, map::Map,
+    particle_system::ParticleBuilder, spell_system, status_effect_system, teleport_system,
+    RunState,
 };
+use rltk::RandomNumberGenerator;
 use specs::prelude::*;
 
 pub struct ItemCollectionSystem;
@@ -14,14 +17,88 @@ impl<'a> System<'a> for ItemCollectionSystem {
         WriteStorage<'a, Position>,
         ReadStorage<'a, Name>,
         WriteStorage<'a, InBackpack>,
+        ReadStorage<'a, Equipped>,
+        ReadStorage<'a, Weight>,
+        ReadStorage<'a, Encumbrance>,
+        WriteStorage<'a, InContainer>,
+        Entities<'a>,
+        ReadStorage<'a, Gold>,
+        WriteStorage<'a, Purse>,
+        WriteStorage<'a, ForSale>,
+        WriteStorage<'a, Monster>,
     );
 
     fn run(&mut self, data: Self::SystemData) {
-        let (player_entity, mut game_log, mut wants_pickup, mut positions, names, mut backpack) =
-            data;
+        let (
+            player_entity,
+            mut game_log,
+            mut wants_pickup,
+            mut positions,
+            names,
+            mut backpack,
+            equipped,
+            weights,
+            encumbrance,
+            mut in_container,
+            entities,
+            gold_piles,
+            mut purses,
+            mut for_sale,
+            mut monsters,
+        ) = data;
 
+        let mut robbed: Vec<Entity> = Vec::new();
         for pickup in wants_pickup.join() {
+            // Coins go straight into the purse
+            if let Some(gold) = gold_piles.get(pickup.item) {
+                if let Some(purse) = purses.get_mut(pickup.collected_by) {
+                    purse.gold += gold.amount;
+                    entities.delete(pickup.item).expect("Unable to delete");
+                    if pickup.collected_by == *player_entity {
+                        game_log
+                            .entries
+                            .insert(0, format!("You pick up {} gold.", gold.amount));
+                    }
+                }
+                continue;
+            }
+
+            // Refuse anything that would take the collector over their hard limit. Taking
+            // something out of their own bag changes nothing, as it's already carried.
+            let already_carried = in_container
+                .get(pickup.item)
+                .and_then(|inside| backpack.get(inside.container))
+                .map(|pack| pack.owner)
+                == Some(pickup.collected_by);
+            if let Some(load) = encumbrance.get(pickup.collected_by) {
+                let item_weight = if already_carried {
+                    0.0
+                } else {
+                    weights.get(pickup.item).map_or(0.0, |w| w.lbs)
+                };
+                let carried = carried_weight(
+                    pickup.collected_by,
+                    &weights,
+                    &backpack,
+                    &equipped,
+                    &in_container,
+                );
+                if carried + item_weight > load.hard_limit() {
+                    if pickup.collected_by == *player_entity {
+                        game_log.entries.insert(
+                            0,
+                            format!(
+                                "The {} is too heavy to carry with everything else.",
+                                names.get(pickup.item).unwrap().name
+                            ),
+                        );
+                    }
+                    continue;
+                }
+            }
+
             positions.remove(pickup.item);
+            in_container.remove(pickup.item);
             backpack
                 .insert(
                     pickup.item,
@@ -37,6 +114,30 @@ impl<'a> System<'a> for ItemCollectionSystem {
                     format!("You pick up the {}.", names.get(pickup.item).unwrap().name),
                 );
             }
+
+            // Walking off with unpaid stock is theft
+            if let Some(sale) = for_sale.remove(pickup.item) {
+                if entities.is_alive(sale.shopkeeper) {
+                    game_log.entries.insert(
+                        0,
+                        "You haven't paid for that! The shopkeeper is furious!".to_string(),
+                    );
+                    monsters
+                        .insert(sale.shopkeeper, Monster {})
+                        .expect("Unable to insert monster");
+                    robbed.push(sale.shopkeeper);
+                }
+            }
+        }
+
+        // A robbed shopkeeper is done trading
+        let closed: Vec<Entity> = (&entities, &for_sale)
+            .join()
+            .filter(|(_, sale)| robbed.contains(&sale.shopkeeper))
+            .map(|(item, _)| item)
+            .collect();
+        for item in closed {
+            for_sale.remove(item);
         }
 
         wants_pickup.clear();
@@ -70,6 +171,29 @@ impl<'a> System<'a> for ItemUseSystem {
         ReadStorage<'a, ProvidesFood>,
         WriteStorage<'a, HungerClock>,
         ReadStorage<'a, MagicMapper>,
+        WriteStorage<'a, Charges>,
+        ReadStorage<'a, Recharger>,
+        (
+            WriteStorage<'a, Mana>,
+            ReadStorage<'a, Spell>,
+            ReadStorage<'a, KnownSpell>,
+            ReadStorage<'a, TeachesSpell>,
+            Read<'a, LazyUpdate>,
+        ),
+        (
+            ReadStorage<'a, ProvidesStatus>,
+            WriteStorage<'a, StatusEffects>,
+            ReadStorage<'a, Teleports>,
+            WriteStorage<'a, WantsToTeleport>,
+            ReadStorage<'a, TeachesRecipe>,
+            WriteStorage<'a, KnownRecipes>,
+            ReadStorage<'a, Perishable>,
+            WriteExpect<'a, RandomNumberGenerator>,
+            ReadStorage<'a, Charms>,
+            WriteStorage<'a, Monster>,
+            WriteStorage<'a, Ally>,
+            ReadStorage<'a, Undead>,
+        ),
     );
 
     fn run(&mut self, data: Self::SystemData) {
@@ -96,11 +220,63 @@ impl<'a> System<'a> for ItemUseSystem {
             provides_food,
             mut hunger_clocks,
             magic_mapper,
+            mut charges,
+            rechargers,
+            (mut mana, spells, known_spells, teaches_spell, lazy),
+            (
+                provides_status,
+                mut statuses,
+                teleports,
+                mut wants_teleport,
+                teaches_recipe,
+                mut known_recipes,
+                perishables,
+                mut rng,
+                charms,
+                mut monsters,
+                mut allies,
+                undead,
+            ),
         ) = data;
 
         for (entity, use_item) in (&entities, &wants_use).join() {
             let mut used_item = true;
 
+            // Wands and staves do nothing once their charges run out
+            if let Some(item_charges) = charges.get(use_item.item) {
+                if item_charges.current < 1 {
+                    if entity == *player_entity {
+                        game_log.entries.insert(
+                            0,
+                            format!(
+                                "The {} has no charges left.",
+                                names.get(use_item.item).unwrap().name
+                            ),
+                        );
+                    }
+                    continue;
+                }
+            }
+
+            // Spells need enough mana to cast
+            if let Some(spell) = spells.get(use_item.item) {
+                let enough_mana = mana
+                    .get(entity)
+                    .is_some_and(|pool| pool.current >= spell.mana_cost);
+                if !enough_mana {
+                    if entity == *player_entity {
+                        game_log.entries.insert(
+                            0,
+                            format!(
+                                "You don't have enough mana to cast {}.",
+                                names.get(use_item.item).unwrap().name
+                            ),
+                        );
+                    }
+                    continue;
+                }
+            }
+
             // Targeting
             let mut targets: Vec<Entity> = Vec::new();
             match use_item.target {
@@ -196,17 +372,67 @@ impl<'a> System<'a> for ItemUseSystem {
             let item_edible = provides_food.get(use_item.item);
             match item_edible {
                 None => {}
-                Some(_) => {
+                Some(food) => {
                     used_item = true;
                     let target = targets[0];
                     let hc = hunger_clocks.get_mut(target);
                     if let Some(hc) = hc {
-                        hc.state = HungerState::WellFed;
-                        hc.duration = 20;
-                        game_log.entries.insert(
-                            0,
-                            format!("You eat the {}.", names.get(use_item.item).unwrap().name),
-                        );
+                        let item_name = &names.get(use_item.item).unwrap().name;
+                        let rotten = perishables
+                            .get(use_item.item)
+                            .is_some_and(|food| food.is_rotten());
+                        let mut nutrition = food.nutrition;
+                        game_log
+                            .entries
+                            .insert(0, format!("You eat the {}.", item_name));
+
+                        // Rotten food is less filling, and may make you ill
+                        if rotten {
+                            nutrition /= 2;
+                            if rng.roll_dice(1, 2) == 1 {
+                                if statuses.get(target).is_none() {
+                                    statuses
+                                        .insert(target, StatusEffects::default())
+                                        .expect("Unable to insert status effects");
+                                }
+                                if let Some(status) = statuses.get_mut(target) {
+                                    status.add(StatusEffect::Sick, 10);
+                                }
+                                game_log
+                                    .entries
+                                    .insert(0, "Ugh, that was rotten! You feel sick.".to_string());
+                            }
+                        }
+
+                        hc.nutrition += nutrition;
+                        if hc.nutrition > hunger_system::MAX_NUTRITION {
+                            hc.nutrition = hunger_system::MAX_NUTRITION;
+                            if rng.roll_dice(1, 3) == 1 {
+                                let damage = rng.roll_dice(2, 4);
+                                suffer_damage
+                                    .insert(
+                                        target,
+                                        SufferDamage {
+                                            amount: damage,
+                                            source: DamageSource::Choking,
+                                        },
+                                    )
+                                    .expect("Unable to insert");
+                                game_log.entries.insert(
+                                    0,
+                                    format!(
+                                        "You choke on the {}, suffering {} hp damage!",
+                                        item_name, damage
+                                    ),
+                                );
+                            } else {
+                                game_log.entries.insert(
+                                    0,
+                                    "You're having a hard time getting it all down.".to_string(),
+                                );
+                            }
+                        }
+                        hc.state = hunger_system::hunger_state(hc.nutrition);
                     }
                 }
             }
@@ -218,7 +444,7 @@ impl<'a> System<'a> for ItemUseSystem {
                     for target in targets.iter() {
                         let stats = combat_stats.get_mut(*target);
                         if let Some(stats) = stats {
-                            stats.hp = i32::max(stats.max_hp, stats.hp + healer.heal_amount);
+                            stats.hp = i32::min(stats.max_hp, stats.hp + healer.heal_amount);
                             if entity == *player_entity {
                                 game_log.entries.insert(
                                     0,
@@ -246,6 +472,66 @@ impl<'a> System<'a> for ItemUseSystem {
                 }
             }
 
+            // Potions with timed effects
+            if let Some(provides) = provides_status.get(use_item.item) {
+                for target in targets.iter() {
+                    if statuses.get(*target).is_none() {
+                        statuses
+                            .insert(*target, StatusEffects::default())
+                            .expect("Unable to insert status effects");
+                    }
+                    if let Some(status) = statuses.get_mut(*target) {
+                        status.add(provides.effect, provides.turns);
+                    }
+                    if entity == *player_entity {
+                        let message = status_effect_system::drink_message(provides.effect);
+                        if provides_food.get(use_item.item).is_some() {
+                            // Eating already told the player what they ate
+                            game_log.entries.insert(0, message.to_string());
+                        } else {
+                            game_log.entries.insert(
+                                0,
+                                format!(
+                                    "You drink the {}. {}",
+                                    names.get(use_item.item).unwrap().name,
+                                    message
+                                ),
+                            );
+                        }
+                    }
+                }
+            }
+
+            // Teleportation moves the user; blinking needs an open tile as the target
+            if let Some(teleport) = teleports.get(use_item.item) {
+                let destination = match teleport.kind {
+                    TeleportKind::Random => Some(TeleportDestination::Random),
+                    TeleportKind::Level => Some(TeleportDestination::Level),
+                    TeleportKind::Blink => use_item
+                        .target
+                        .filter(|target| teleport_system::is_open_tile(&map, target.x, target.y))
+                        .map(|target| TeleportDestination::Tile {
+                            x: target.x,
+                            y: target.y,
+                        }),
+                };
+                match destination {
+                    Some(destination) => {
+                        wants_teleport
+                            .insert(entity, WantsToTeleport { destination })
+                            .expect("Unable to insert teleport");
+                    }
+                    None => {
+                        used_item = false;
+                        if entity == *player_entity {
+                            game_log
+                                .entries
+                                .insert(0, "You can't blink there.".to_string());
+                        }
+                    }
+                }
+            }
+
             // If it inflicts damage, apply it to the target cell
             let item_damages = does_damage.get(use_item.item);
             match item_damages {
@@ -258,6 +544,7 @@ impl<'a> System<'a> for ItemUseSystem {
                                 *mob,
                                 SufferDamage {
                                     amount: damage.damage,
+                                    source: DamageSource::Attacker(entity),
                                 },
                             )
                             .expect("Unable to insert");
@@ -278,6 +565,31 @@ impl<'a> System<'a> for ItemUseSystem {
                 }
             }
 
+            // Charming turns hostile monsters into allies; the undead can't be swayed
+            if charms.get(use_item.item).is_some() {
+                for mob in targets.iter() {
+                    if monsters.get(*mob).is_none() {
+                        continue;
+                    }
+                    let mob_name = &names.get(*mob).unwrap().name;
+                    if undead.get(*mob).is_some() {
+                        if entity == *player_entity {
+                            game_log
+                                .entries
+                                .insert(0, format!("The {} ignores your charm.", mob_name));
+                        }
+                        continue;
+                    }
+                    monsters.remove(*mob);
+                    allies.insert(*mob, Ally {}).expect("Unable to insert ally");
+                    if entity == *player_entity {
+                        game_log
+                            .entries
+                            .insert(0, format!("The {} is now your friend.", mob_name));
+                    }
+                }
+            }
+
             // Can it pass along confusion? Note the use of scopes to escape from the borrow checker!
             let mut add_confusion = Vec::new();
             {
@@ -335,7 +647,103 @@ impl<'a> System<'a> for ItemUseSystem {
                 }
             }
 
+            // Recharging refills every charged item the user is carrying
+            let is_recharger = rechargers.get(use_item.item);
+            match is_recharger {
+                None => {}
+                Some(_) => {
+                    used_item = true;
+                    for (item_charges, pack) in (&mut charges, &backpack).join() {
+                        if pack.owner == entity {
+                            item_charges.current = item_charges.max;
+                        }
+                    }
+                    if entity == *player_entity {
+                        game_log
+                            .entries
+                            .insert(0, "Your wands and staves hum with power.".to_string());
+                    }
+                }
+            }
+
+            // Spellbooks teach the reader a new spell
+            let teaches = teaches_spell.get(use_item.item);
+            match teaches {
+                None => {}
+                Some(teaches) => {
+                    let already_known = (&known_spells, &spells)
+                        .join()
+                        .any(|(known, spell)| known.owner == entity && spell.kind == teaches.spell);
+                    if already_known {
+                        used_item = false;
+                        if entity == *player_entity {
+                            game_log.entries.insert(
+                                0,
+                                format!(
+                                    "You already know {}.",
+                                    spell_system::spell_name(teaches.spell)
+                                ),
+                            );
+                        }
+                    } else {
+                        used_item = true;
+                        spell_system::build_spell(
+                            lazy.create_entity(&entities),
+                            teaches.spell,
+                            entity,
+                        );
+                        if entity == *player_entity {
+                            game_log.entries.insert(
+                                0,
+                                format!(
+                                    "You learn to cast {}.",
+                                    spell_system::spell_name(teaches.spell)
+                                ),
+                            );
+                        }
+                    }
+                }
+            }
+
+            // Alchemist's notes teach a crafting recipe
+            if let Some(teaches) = teaches_recipe.get(use_item.item) {
+                if known_recipes.get(entity).is_none() {
+                    known_recipes
+                        .insert(entity, KnownRecipes::default())
+                        .expect("Unable to insert known recipes");
+                }
+                if let Some(known) = known_recipes.get_mut(entity) {
+                    if known.recipes.contains(&teaches.recipe) {
+                        used_item = false;
+                        if entity == *player_entity {
+                            game_log.entries.insert(
+                                0,
+                                format!("You already know how to make {}.", teaches.recipe),
+                            );
+                        }
+                    } else {
+                        used_item = true;
+                        known.recipes.push(teaches.recipe.clone());
+                        if entity == *player_entity {
+                            game_log
+                                .entries
+                                .insert(0, format!("You learn how to make {}.", teaches.recipe));
+                        }
+                    }
+                }
+            }
+
             if used_item {
+                if let Some(item_charges) = charges.get_mut(use_item.item) {
+                    item_charges.current -= 1;
+                }
+
+                if let Some(spell) = spells.get(use_item.item) {
+                    if let Some(pool) = mana.get_mut(entity) {
+                        pool.current -= spell.mana_cost;
+                    }
+                }
+
                 let consumable = consumables.get(use_item.item);
                 match consumable {
                     None => {}
@@ -431,3 +839,119 @@ impl<'a> System<'a> for ItemRemoveSystem {
         wants_remove.clear();
     }
 }
+
+pub struct ItemStashSystem;
+
+impl<'a> System<'a> for ItemStashSystem {
+    #[allow(clippy::type_complexity)]
+    type SystemData = (
+        ReadExpect<'a, Entity>,
+        WriteExpect<'a, GameLog>,
+        Entities<'a>,
+        WriteStorage<'a, WantsToStashItem>,
+        ReadStorage<'a, Name>,
+        WriteStorage<'a, InBackpack>,
+        WriteStorage<'a, InContainer>,
+    );
+
+    fn run(&mut self, data: Self::SystemData) {
+        let (
+            player_entity,
+            mut gamelog,
+            entities,
+            mut wants_stash,
+            names,
+            mut backpack,
+            mut in_container,
+        ) = data;
+
+        for (entity, to_stash) in (&entities, &wants_stash).join() {
+            backpack.remove(to_stash.item);
+            in_container
+                .insert(
+                    to_stash.item,
+                    InContainer {
+                        container: to_stash.container,
+                    },
+                )
+                .expect("Unable to insert container entry");
+
+            if entity == *player_entity {
+                gamelog.entries.insert(
+                    0,
+                    format!(
+                        "You put the {} in the {}.",
+                        names.get(to_stash.item).unwrap().name,
+                        names.get(to_stash.container).unwrap().name
+                    ),
+                );
+            }
+        }
+
+        wants_stash.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::component_registry::register_components;
+    use crate::spawner::{self, Spawn};
+    use crate::SerializeMe;
+    use specs::saveload::SimpleMarkerAllocator;
+
+    #[test]
+    fn taking_things_out_of_your_own_bag_at_the_limit() {
+        let mut ecs = World::new();
+        register_components(&mut ecs);
+        ecs.insert(SimpleMarkerAllocator::<SerializeMe>::new());
+        ecs.insert(RandomNumberGenerator::seeded(1));
+        ecs.insert(GameLog {
+            entries: Vec::new(),
+        });
+        let player = spawner::player(&mut ecs, 5, 5);
+        ecs.insert(player);
+
+        let sack = spawner::spawn_item(&mut ecs, Spawn::Sack, 5, 5, 1).unwrap();
+        let dagger = spawner::spawn_item(&mut ecs, Spawn::Dagger, 5, 5, 1).unwrap();
+        ecs.write_storage::<Position>().remove(sack);
+        ecs.write_storage::<Position>().remove(dagger);
+        ecs.write_storage::<InBackpack>()
+            .insert(sack, InBackpack { owner: player })
+            .unwrap();
+        ecs.write_storage::<InContainer>()
+            .insert(dagger, InContainer { container: sack })
+            .unwrap();
+
+        // Load the player right up to their limit
+        let carried = carried_weight(
+            player,
+            &ecs.read_storage::<Weight>(),
+            &ecs.read_storage::<InBackpack>(),
+            &ecs.read_storage::<Equipped>(),
+            &ecs.read_storage::<InContainer>(),
+        );
+        ecs.write_storage::<Encumbrance>()
+            .get_mut(player)
+            .unwrap()
+            .capacity = carried / 2.0;
+
+        ecs.write_storage::<WantsToPickupItem>()
+            .insert(
+                player,
+                WantsToPickupItem {
+                    collected_by: player,
+                    item: dagger,
+                },
+            )
+            .unwrap();
+        ItemCollectionSystem.run_now(&ecs);
+        assert_eq!(
+            ecs.read_storage::<InBackpack>()
+                .get(dagger)
+                .map(|pack| pack.owner),
+            Some(player)
+        );
+        assert!(ecs.read_storage::<InContainer>().get(dagger).is_none());
+    }
+}
@@ -0,0 +1,237 @@
+use super::components::*;
+use rltk::RandomNumberGenerator;
+use specs::prelude::*;
+
+/// Rolls an enchantment level and affixes for a freshly spawned piece of equipment.
+/// The deeper the level, the more likely the item is magical and the stronger the roll.
+/// Mundane items are left untouched.
+pub fn roll_magic_item(ecs: &mut World, item: Entity, map_depth: i32) {
+    let enchantment;
+    let mut prefix = None;
+    let mut suffix = None;
+    let affix_strength = 1 + map_depth / 4;
+    let mut fire_damage = 0;
+    let is_weapon = ecs.read_storage::<MeleePowerBonus>().get(item).is_some();
+
+    // Scope to keep the borrow checker happy
+    {
+        let mut rng = ecs.write_resource::<RandomNumberGenerator>();
+        let magic_chance = i32::min(75, 10 + map_depth * 5);
+        if rng.roll_dice(1, 100) > magic_chance {
+            return;
+        }
+
+        let mut level = rng.roll_dice(1, i32::min(3, 1 + map_depth / 2));
+        if rng.roll_dice(1, 5) == 1 {
+            // Cursed items never carry affixes
+            level = -level;
+        } else {
+            // Prefixes sharpen or set alight a blade, so armour never has one
+            if is_weapon && rng.roll_dice(1, 100) <= magic_chance {
+                prefix = match rng.roll_dice(1, 2) {
+                    1 => {
+                        fire_damage = rng.roll_dice(1, 2 + map_depth / 2);
+                        Some(ItemAffix::Flaming)
+                    }
+                    _ => Some(ItemAffix::Keen),
+                };
+            }
+            if rng.roll_dice(1, 100) <= magic_chance {
+                suffix = match rng.roll_dice(1, 2) {
+                    1 if is_weapon => Some(ItemAffix::Might),
+                    _ => Some(ItemAffix::Warding),
+                };
+            }
+        }
+        enchantment = level;
+    }
+
+    // The enchantment boosts whatever the item is already good at
+    if is_weapon {
+        add_power_bonus(ecs, item, enchantment);
+    } else {
+        add_defense_bonus(ecs, item, enchantment);
+    }
+
+    for affix in prefix.iter().chain(suffix.iter()) {
+        match affix {
+            ItemAffix::Flaming => {
+                ecs.write_storage::<MeleeFireDamage>()
+                    .insert(
+                        item,
+                        MeleeFireDamage {
+                            damage: fire_damage,
+                        },
+                    )
+                    .expect("Unable to insert fire damage");
+            }
+            ItemAffix::Keen => add_power_bonus(ecs, item, 1),
+            ItemAffix::Warding => add_defense_bonus(ecs, item, affix_strength),
+            ItemAffix::Might => add_power_bonus(ecs, item, affix_strength),
+        }
+    }
+
+    {
+        let mut names = ecs.write_storage::<Name>();
+        if let Some(name) = names.get_mut(item) {
+            name.name = magic_item_name(&name.name, enchantment, prefix, suffix);
+        }
+    }
+
+    ecs.write_storage::<MagicItem>()
+        .insert(
+            item,
+            MagicItem {
+                enchantment,
+                prefix,
+                suffix,
+            },
+        )
+        .expect("Unable to insert magic item");
+}
+
+/// Builds a display name such as "+2 Flaming Longsword of Warding".
+pub fn magic_item_name(
+    base_name: &str,
+    enchantment: i32,
+    prefix: Option<ItemAffix>,
+    suffix: Option<ItemAffix>,
+) -> String {
+    let mut name = format!("{:+} ", enchantment);
+    if let Some(prefix) = prefix {
+        name.push_str(affix_name(prefix));
+        name.push(' ');
+    }
+    name.push_str(base_name);
+    if let Some(suffix) = suffix {
+        name.push_str(" of ");
+        name.push_str(affix_name(suffix));
+    }
+    name
+}
+
+fn affix_name(affix: ItemAffix) -> &'static str {
+    match affix {
+        ItemAffix::Flaming => "Flaming",
+        ItemAffix::Keen => "Keen",
+        ItemAffix::Warding => "Warding",
+        ItemAffix::Might => "Might",
+    }
+}
+
+fn add_power_bonus(ecs: &mut World, item: Entity, amount: i32) {
+    let mut bonuses = ecs.write_storage::<MeleePowerBonus>();
+    if let Some(bonus) = bonuses.get_mut(item) {
+        bonus.power += amount;
+    } else {
+        bonuses
+            .insert(item, MeleePowerBonus { power: amount })
+            .expect("Unable to insert power bonus");
+    }
+}
+
+fn add_defense_bonus(ecs: &mut World, item: Entity, amount: i32) {
+    let mut bonuses = ecs.write_storage::<DefenseBonus>();
+    if let Some(bonus) = bonuses.get_mut(item) {
+        bonus.defense += amount;
+    } else {
+        bonuses
+            .insert(item, DefenseBonus { defense: amount })
+            .expect("Unable to insert defense bonus");
+    }
+}
+
+/// How much an enchantment and its affixes add to (or, if cursed, take from) an
+/// item's price.
+pub fn value_bonus(magic: &MagicItem) -> i32 {
+    let affixes = magic.prefix.iter().chain(magic.suffix.iter()).count() as i32;
+    magic.enchantment * 25 + affixes * 30
+}
+
+/// Curses an item, sinking its enchantment below zero and taking its bonuses and
+/// price down with it. Affixes are kept, since they're part of what the item was.
+pub fn curse_item(ecs: &mut World, item: Entity) {
+    let magic = ecs.read_storage::<MagicItem>().get(item).cloned();
+    let (enchantment, prefix, suffix) = magic
+        .as_ref()
+        .map_or((0, None, None), |m| (m.enchantment, m.prefix, m.suffix));
+    let cursed = i32::min(enchantment, 0)
+        - ecs
+            .write_resource::<RandomNumberGenerator>()
+            .roll_dice(1, 3);
+    let change = cursed - enchantment;
+
+    let is_weapon = ecs.read_storage::<MeleePowerBonus>().get(item).is_some();
+    if is_weapon {
+        add_power_bonus(ecs, item, change);
+    } else {
+        add_defense_bonus(ecs, item, change);
+    }
+
+    {
+        let mut names = ecs.write_storage::<Name>();
+        if let Some(name) = names.get_mut(item) {
+            let base_name = match magic {
+                Some(_) => base_item_name(&name.name, prefix, suffix),
+                None => name.name.clone(),
+            };
+            name.name = magic_item_name(&base_name, cursed, prefix, suffix);
+        }
+    }
+    if let Some(value) = ecs.write_storage::<Value>().get_mut(item) {
+        value.gold = i32::max(1, value.gold + change * 25);
+    }
+
+    ecs.write_storage::<MagicItem>()
+        .insert(
+            item,
+            MagicItem {
+                enchantment: cursed,
+                prefix,
+                suffix,
+            },
+        )
+        .expect("Unable to insert magic item");
+}
+
+/// Undoes `magic_item_name`, turning "+2 Flaming Longsword of Warding" back into
+/// "Longsword".
+fn base_item_name(name: &str, prefix: Option<ItemAffix>, suffix: Option<ItemAffix>) -> String {
+    let mut base = name.split_once(' ').map_or(name, |(_, rest)| rest);
+    if let Some(prefix) = prefix {
+        base = base
+            .strip_prefix(affix_name(prefix))
+            .map_or(base, |rest| rest.trim_start());
+    }
+    if let Some(suffix) = suffix {
+        let of_suffix = format!(" of {}", affix_name(suffix));
+        base = base.strip_suffix(&of_suffix[..]).unwrap_or(base);
+    }
+    base.to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::component_registry::register_components;
+    use crate::spawner::{self, Spawn};
+    use specs::saveload::SimpleMarkerAllocator;
+
+    #[test]
+    fn armour_never_takes_weapon_affixes() {
+        let mut ecs = World::new();
+        register_components(&mut ecs);
+        ecs.insert(SimpleMarkerAllocator::<SerializeMe>::new());
+        ecs.insert(RandomNumberGenerator::seeded(7));
+
+        for _ in 0..200 {
+            let shield = spawner::spawn_item(&mut ecs, Spawn::Shield, 1, 1, 12).unwrap();
+            assert!(ecs.read_storage::<MeleePowerBonus>().get(shield).is_none());
+            assert!(ecs.read_storage::<MeleeFireDamage>().get(shield).is_none());
+            if let Some(magic) = ecs.read_storage::<MagicItem>().get(shield) {
+                assert!(magic.prefix.is_none());
+                assert!(magic.suffix != Some(ItemAffix::Might));
+            }
+        }
+    }
+}
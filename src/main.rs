@@ -2,33 +2,54 @@ rltk::add_wasm_support!();
 
 use rltk::{BaseMap, Console, GameState, Point, Rltk};
 use specs::prelude::*;
-use specs::saveload::{SimpleMarker, SimpleMarkerAllocator};
+use specs::saveload::SimpleMarkerAllocator;
 #[macro_use]
 extern crate specs_derive;
 
+mod ally_ai_system;
+mod bones;
+#[macro_use]
+mod component_registry;
 mod components;
+mod config;
+mod crafting;
 mod damage_system;
+mod dialogue;
+mod encumbrance_system;
 mod game_log;
 mod gui;
+mod hall_of_fame;
 mod hunger_system;
 mod inventory_system;
+mod magic_items;
 mod map;
 mod map_indexing_system;
 mod melee_combat_system;
 mod monster_ai_system;
+mod morgue;
 mod particle_system;
 mod player;
 mod random_table;
 mod rect;
 mod rex_assets;
+mod save_format;
+mod save_storage;
 mod saveload_system;
+mod shop;
 mod spawner;
+mod spell_system;
+mod status_effect_system;
+mod teleport_system;
 mod visibility_system;
 
+use ally_ai_system::AllyAISystem;
 use components::*;
 use damage_system::DamageSystem;
-use hunger_system::HungerSystem;
-use inventory_system::{ItemCollectionSystem, ItemDropSystem, ItemRemoveSystem, ItemUseSystem};
+use encumbrance_system::EncumbranceSystem;
+use hunger_system::{FoodDecaySystem, HungerSystem};
+use inventory_system::{
+    ItemCollectionSystem, ItemDropSystem, ItemRemoveSystem, ItemStashSystem, ItemUseSystem,
+};
 use map::*;
 use map_indexing_system::MapIndexingSystem;
 use melee_combat_system::MeleeCombatSystem;
@@ -36,6 +57,9 @@ use monster_ai_system::MonsterAISystem;
 use particle_system::ParticleSpawnSystem;
 use player::*;
 use random_table::RandomTable;
+use spell_system::ManaRegenSystem;
+use status_effect_system::StatusEffectSystem;
+use teleport_system::TeleportSystem;
 use visibility_system::VisibilitySystem;
 
 #[derive(PartialEq, Copy, Clone)]
@@ -45,6 +69,8 @@ pub enum RunState {
     PlayerTurn,
     MonsterTurn,
     ShowInventory,
+    ShowSpells,
+    ShowCrafting,
     ShowDropItem,
     ShowTargeting {
         range: i32,
@@ -53,13 +79,50 @@ pub enum RunState {
     MainMenu {
         menu_selection: gui::MainMenuSelection,
     },
-    SaveGame,
+    SaveSlots {
+        selected: usize,
+    },
+    ConfirmOverwrite {
+        slot: usize,
+    },
+    SaveGame {
+        slot: usize,
+    },
+    LoadSlots {
+        selected: usize,
+    },
+    ConfirmDelete {
+        slot: usize,
+    },
     NextLevel,
     ShowRemoveItem,
+    ShowPickupItem,
+    ShowAutoPickup,
+    ShowContainer {
+        container: Entity,
+    },
+    ShowStashItem {
+        container: Entity,
+    },
+    ConfirmRetire,
     GameOver,
+    HallOfFame,
     MagicMapReveal {
         row: i32,
     },
+    LevelTeleport {
+        depth: i32,
+    },
+    ShowShop {
+        shopkeeper: Entity,
+    },
+    ShowSellItem {
+        shopkeeper: Entity,
+    },
+    Dialogue {
+        npc: Entity,
+        node: usize,
+    },
 }
 
 pub struct State {
@@ -79,6 +142,11 @@ impl State {
         let backpack = self.ecs.read_storage::<InBackpack>();
         let player_entity = self.ecs.fetch::<Entity>();
         let equipped = self.ecs.read_storage::<Equipped>();
+        let in_container = self.ecs.read_storage::<InContainer>();
+        let known_spells = self.ecs.read_storage::<KnownSpell>();
+        let allies = self.ecs.read_storage::<Ally>();
+        let positions = self.ecs.read_storage::<Position>();
+        let player_pos = self.ecs.fetch::<Point>();
 
         let mut to_delete: Vec<Entity> = Vec::new();
         for entity in entities.join() {
@@ -105,6 +173,31 @@ impl State {
                 }
             }
 
+            // Don't forget the player's spells
+            let ks = known_spells.get(entity);
+            if let Some(ks) = ks {
+                if ks.owner == *player_entity {
+                    should_delete = false;
+                }
+            }
+
+            // Allies standing next to the player come along
+            if let (Some(_ally), Some(pos)) = (allies.get(entity), positions.get(entity)) {
+                if i32::abs(pos.x - player_pos.x) <= 1 && i32::abs(pos.y - player_pos.y) <= 1 {
+                    should_delete = false;
+                }
+            }
+
+            // Don't delete anything tucked away in the player's bags
+            let inside = in_container.get(entity);
+            if let Some(inside) = inside {
+                if let Some(bp) = backpack.get(inside.container) {
+                    if bp.owner == *player_entity {
+                        should_delete = false;
+                    }
+                }
+            }
+
             if should_delete {
                 to_delete.push(entity);
             }
@@ -113,7 +206,46 @@ impl State {
         to_delete
     }
 
+    /// Autosaves, telling the player if it didn't work.
+    fn autosave(&mut self) {
+        if let Err(err) = saveload_system::autosave(&mut self.ecs) {
+            self.ecs
+                .fetch_mut::<game_log::GameLog>()
+                .entries
+                .insert(0, format!("Autosave failed: {}", err));
+        }
+    }
+
+    /// Counts the player's turn, autosaving every so many. Hasted turns skip the
+    /// monsters' turn, so this is done as the player's turn starts.
+    fn start_turn(&mut self) {
+        self.ecs.write_resource::<game_log::TurnCounter>().turns += 1;
+        let every_turns = self.ecs.fetch::<config::GameConfig>().autosave.every_turns;
+        let turns = self.ecs.fetch::<game_log::TurnCounter>().turns;
+        if every_turns > 0 && turns % every_turns == 0 {
+            self.autosave();
+        }
+    }
+
     fn goto_next_level(&mut self) {
+        let current_depth = self.ecs.fetch::<Map>().depth;
+        self.goto_level(current_depth + 1);
+
+        // Notify the player and give them some health
+        let player_entity = self.ecs.fetch::<Entity>();
+        let mut gamelog = self.ecs.fetch_mut::<game_log::GameLog>();
+        gamelog.entries.insert(
+            0,
+            "You descend to the next level, and take a moment to heal.".to_string(),
+        );
+        let mut player_health_store = self.ecs.write_storage::<CombatStats>();
+        let player_health = player_health_store.get_mut(*player_entity);
+        if let Some(player_health) = player_health {
+            player_health.hp = i32::max(player_health.hp, player_health.max_hp / 2);
+        }
+    }
+
+    fn goto_level(&mut self, depth: i32) {
         // Delete entities that aren't the player or his/her equipment
         let to_delete = self.entities_to_remove_on_level_change();
         for target in to_delete {
@@ -122,20 +254,26 @@ impl State {
                 .expect("Unable to delete entity");
         }
 
-        // Build a new map and place the player
+        // Build a new map and place the player. Someone may have died here before.
+        let bones = bones::take_bones(&self.ecs, depth);
         let worldmap;
-        let current_depth;
         {
             let mut worldmap_resource = self.ecs.write_resource::<Map>();
-            current_depth = worldmap_resource.depth;
-            let current_depth = worldmap_resource.depth;
-            *worldmap_resource = Map::new_map_rooms_and_corridors(current_depth + 1);
+            *worldmap_resource = match &bones {
+                Some(bones) => bones.map.clone(),
+                None => Map::new_map_rooms_and_corridors(depth),
+            };
             worldmap = worldmap_resource.clone();
         }
+        {
+            let mut run_stats = self.ecs.write_resource::<game_log::RunStats>();
+            run_stats.deepest = i32::max(run_stats.deepest, depth);
+        }
 
         // Spawn bad guys
-        for room in worldmap.rooms.iter().skip(1) {
-            spawner::spawn_room(&mut self.ecs, room, current_depth + 1);
+        spawner::spawn_level(&mut self.ecs, &worldmap, depth);
+        if let Some(bones) = bones {
+            bones::raise_ghost(&mut self.ecs, &bones);
         }
 
         // Place the player and update resources
@@ -157,16 +295,88 @@ impl State {
             vs.dirty = true;
         }
 
-        // Notify the player and give them some health
+        // Any allies that came along arrive next to the player, if there's room
+        let entities = self.ecs.entities();
+        let allies = self.ecs.read_storage::<Ally>();
+        let names = self.ecs.read_storage::<Name>();
         let mut gamelog = self.ecs.fetch_mut::<game_log::GameLog>();
-        gamelog.entries.insert(
-            0,
-            "You descend to the next level, and take a moment to heal.".to_string(),
-        );
-        let mut player_health_store = self.ecs.write_storage::<CombatStats>();
-        let player_health = player_health_store.get_mut(*player_entity);
-        if let Some(player_health) = player_health {
-            player_health.hp = i32::max(player_health.hp, player_health.max_hp / 2);
+        let mut free_tiles = Vec::new();
+        for dy in -1..=1 {
+            for dx in -1..=1 {
+                let idx = worldmap.xy_idx(player_x + dx, player_y + dy);
+                if (dx != 0 || dy != 0) && worldmap.tiles[idx] != TileType::Wall {
+                    free_tiles.push((player_x + dx, player_y + dy));
+                }
+            }
+        }
+        for (ally, _ally) in (&entities, &allies).join() {
+            match free_tiles.pop() {
+                Some((x, y)) => {
+                    if let Some(pos) = position_components.get_mut(ally) {
+                        pos.x = x;
+                        pos.y = y;
+                    }
+                    if let Some(vs) = viewshed_components.get_mut(ally) {
+                        vs.dirty = true;
+                    }
+                }
+                None => {
+                    if let Some(name) = names.get(ally) {
+                        gamelog
+                            .entries
+                            .insert(0, format!("Your {} is left behind.", name.name));
+                    }
+                    entities.delete(ally).expect("Unable to delete ally");
+                }
+            }
+        }
+    }
+
+    /// Sums up the run that just ended, for the game over screen and a morgue file,
+    /// and perhaps leaves bones for a later run. This happens as the player dies, so
+    /// none of it is lost if the game is closed on the game over screen.
+    fn record_death(&mut self) {
+        let mut recap = morgue::DeathRecap::new(&self.ecs);
+        recap.morgue = match morgue::write_morgue(&self.ecs, &recap) {
+            Ok(location) => format!("Morgue file written to {}", location),
+            Err(err) => format!("Unable to write the morgue file: {}", err),
+        };
+        recap.hall_of_fame = self.record_score(&recap.cause);
+        self.ecs.insert(recap);
+
+        // Bones curse the gear, so they're left after the morgue file lists it
+        if let Err(err) = bones::leave_bones(&mut self.ecs) {
+            self.ecs.fetch_mut::<gui::MainMenuMessage>().text =
+                Some(format!("Unable to leave bones: {}", err));
+        }
+    }
+
+    /// Ends the run at the player's request. It's scored like any other, but with no
+    /// morgue file or bones, as the player lived to tell of it.
+    fn retire(&mut self) {
+        let mut recap = morgue::DeathRecap::new(&self.ecs);
+        recap.cause = "Retired".to_string();
+        recap.hall_of_fame = self.record_score(&recap.cause);
+        self.ecs.insert(recap);
+    }
+
+    /// Adds the run to the hall of fame, returning how it went for the recap.
+    fn record_score(&self, cause: &str) -> String {
+        match hall_of_fame::record_run(&self.ecs, cause) {
+            Ok(Some(rank)) => format!("Your run ranks #{} in the Hall of Fame!", rank + 1),
+            Ok(None) => "Your run didn't make the Hall of Fame.".to_string(),
+            Err(err) => format!("Unable to record your score: {}", err),
+        }
+    }
+
+    /// The run is over, so neither its save nor its autosave can be loaded again.
+    fn forget_run(&mut self) {
+        let slot = self.ecs.fetch::<saveload_system::CurrentSave>().slot;
+        let deleted = slot
+            .map_or(Ok(()), |slot| saveload_system::delete_save(&self.ecs, slot))
+            .and(saveload_system::discard_autosave(&mut self.ecs));
+        if let Err(err) = deleted {
+            self.ecs.fetch_mut::<gui::MainMenuMessage>().text = Some(err.to_string());
         }
     }
 
@@ -187,6 +397,12 @@ impl State {
                 entries: vec!["Welcome to my game".to_string()],
             };
         }
+        *self.ecs.write_resource::<game_log::TurnCounter>() = game_log::TurnCounter::default();
+        *self.ecs.write_resource::<game_log::RunStats>() = game_log::RunStats::default();
+        *self.ecs.write_resource::<morgue::DeathRecap>() = morgue::DeathRecap::default();
+        self.ecs
+            .write_resource::<saveload_system::CurrentSave>()
+            .slot = None;
 
         // Build a new map and place the player
         let worldmap;
@@ -197,9 +413,7 @@ impl State {
         }
 
         // Spawn bad guys
-        for room in worldmap.rooms.iter().skip(1) {
-            spawner::spawn_room(&mut self.ecs, room, 1);
-        }
+        spawner::spawn_level(&mut self.ecs, &worldmap, 1);
 
         // Place the player and update resources
         let (player_x, player_y) = worldmap.rooms[0].center();
@@ -236,20 +450,25 @@ impl GameState for State {
         particle_system::cull_dead_particles(&mut self.ecs, ctx);
 
         match new_run_state {
-            RunState::MainMenu { .. } | RunState::GameOver { .. } => {}
+            RunState::MainMenu { .. }
+            | RunState::LoadSlots { .. }
+            | RunState::ConfirmDelete { .. }
+            | RunState::GameOver { .. }
+            | RunState::HallOfFame => {}
             _ => {
                 draw_map(&self.ecs, ctx);
 
                 {
+                    let entities = self.ecs.entities();
                     let positions = self.ecs.read_storage::<Position>();
                     let renderables = self.ecs.read_storage::<Renderable>();
-                    let map = self.ecs.fetch::<Map>();
 
-                    let mut data = (&positions, &renderables).join().collect::<Vec<_>>();
-                    data.sort_by(|&a, &b| b.1.render_order.cmp(&a.1.render_order));
-                    for (pos, render) in data.iter() {
-                        let idx = map.xy_idx(pos.x, pos.y);
-                        if map.visible_tiles[idx] {
+                    let mut data = (&entities, &positions, &renderables)
+                        .join()
+                        .collect::<Vec<_>>();
+                    data.sort_by(|&a, &b| b.2.render_order.cmp(&a.2.render_order));
+                    for (entity, pos, render) in data.iter() {
+                        if gui::player_can_see(&self.ecs, *entity, pos) {
                             ctx.set(pos.x, pos.y, render.fg, render.bg, render.glyph)
                         }
                     }
@@ -261,10 +480,11 @@ impl GameState for State {
 
         match new_run_state {
             RunState::GameOver => {
-                let result = gui::game_over(ctx);
+                let result = gui::game_over(&self.ecs, ctx);
                 match result {
                     gui::GameOverResult::NoSelection => {}
                     gui::GameOverResult::QuitToMenu => {
+                        self.forget_run();
                         self.game_over_cleanup();
                         new_run_state = RunState::MainMenu {
                             menu_selection: gui::MainMenuSelection::NewGame,
@@ -272,12 +492,119 @@ impl GameState for State {
                     }
                 }
             }
-            RunState::SaveGame => {
-                saveload_system::save_game(&mut self.ecs);
-
-                new_run_state = RunState::MainMenu {
-                    menu_selection: gui::MainMenuSelection::LoadGame,
-                };
+            RunState::ConfirmRetire => match gui::confirm(ctx, "Retire from adventuring?") {
+                gui::ItemMenuResult::NoResponse => {}
+                gui::ItemMenuResult::Cancel => new_run_state = RunState::AwaitingInput,
+                gui::ItemMenuResult::Selected => {
+                    self.retire();
+                    new_run_state = RunState::GameOver;
+                }
+            },
+            RunState::HallOfFame => {
+                if gui::hall_of_fame(&self.ecs, ctx) == gui::GameOverResult::QuitToMenu {
+                    new_run_state = RunState::MainMenu {
+                        menu_selection: gui::MainMenuSelection::HallOfFame,
+                    };
+                }
+            }
+            RunState::SaveSlots { selected } => match gui::slot_menu(self, ctx, false, selected) {
+                gui::SlotMenuResult::Cancel => new_run_state = RunState::AwaitingInput,
+                gui::SlotMenuResult::NoResponse { selected } => {
+                    new_run_state = RunState::SaveSlots { selected }
+                }
+                gui::SlotMenuResult::Selected { slot } => {
+                    let occupied = self
+                        .ecs
+                        .fetch::<saveload_system::SlotHeaders>()
+                        .get(slot)
+                        .is_some();
+                    if occupied {
+                        new_run_state = RunState::ConfirmOverwrite { slot };
+                    } else {
+                        new_run_state = RunState::SaveGame { slot };
+                    }
+                }
+                gui::SlotMenuResult::Delete { .. } => {}
+            },
+            RunState::ConfirmOverwrite { slot } => {
+                gui::draw_slots(self, ctx, false, slot);
+                match gui::confirm(ctx, "Overwrite this save?") {
+                    gui::ItemMenuResult::NoResponse => {}
+                    gui::ItemMenuResult::Cancel => {
+                        new_run_state = RunState::SaveSlots { selected: slot }
+                    }
+                    gui::ItemMenuResult::Selected => new_run_state = RunState::SaveGame { slot },
+                }
+            }
+            RunState::SaveGame { slot } => match saveload_system::save_game(&mut self.ecs, slot) {
+                Ok(()) => {
+                    self.ecs.fetch_mut::<saveload_system::CurrentSave>().slot = Some(slot);
+                    if let Err(err) = saveload_system::discard_autosave(&mut self.ecs) {
+                        self.ecs.fetch_mut::<gui::MainMenuMessage>().text = Some(err.to_string());
+                    }
+                    new_run_state = RunState::MainMenu {
+                        menu_selection: gui::MainMenuSelection::LoadGame,
+                    };
+                }
+                Err(err) => {
+                    self.ecs
+                        .fetch_mut::<game_log::GameLog>()
+                        .entries
+                        .insert(0, err.to_string());
+                    new_run_state = RunState::AwaitingInput;
+                }
+            },
+            RunState::LoadSlots { selected } => match gui::slot_menu(self, ctx, true, selected) {
+                gui::SlotMenuResult::Cancel => {
+                    new_run_state = RunState::MainMenu {
+                        menu_selection: gui::MainMenuSelection::LoadGame,
+                    }
+                }
+                gui::SlotMenuResult::NoResponse { selected } => {
+                    new_run_state = RunState::LoadSlots { selected }
+                }
+                gui::SlotMenuResult::Selected { slot } => {
+                    match saveload_system::load_game(&mut self.ecs, slot) {
+                        Ok(()) => {
+                            self.ecs.fetch_mut::<gui::MainMenuMessage>().text = None;
+                            self.ecs.fetch_mut::<saveload_system::CurrentSave>().slot = Some(slot);
+                            new_run_state = RunState::AwaitingInput;
+                        }
+                        Err(err) => {
+                            self.ecs.fetch_mut::<gui::MainMenuMessage>().text =
+                                Some(err.to_string());
+                            new_run_state = RunState::MainMenu {
+                                menu_selection: gui::MainMenuSelection::LoadGame,
+                            };
+                        }
+                    }
+                }
+                gui::SlotMenuResult::Delete { slot } => {
+                    new_run_state = RunState::ConfirmDelete { slot }
+                }
+            },
+            RunState::ConfirmDelete { slot } => {
+                gui::draw_slots(self, ctx, true, slot);
+                match gui::confirm(ctx, "Delete this save?") {
+                    gui::ItemMenuResult::NoResponse => {}
+                    gui::ItemMenuResult::Cancel => {
+                        new_run_state = RunState::LoadSlots { selected: slot }
+                    }
+                    gui::ItemMenuResult::Selected => {
+                        if let Err(err) = saveload_system::delete_save(&self.ecs, slot) {
+                            self.ecs.fetch_mut::<gui::MainMenuMessage>().text =
+                                Some(err.to_string());
+                        }
+                        if saveload_system::does_save_exist(&self.ecs) {
+                            saveload_system::refresh_slot_headers(&self.ecs);
+                            new_run_state = RunState::LoadSlots { selected: slot };
+                        } else {
+                            new_run_state = RunState::MainMenu {
+                                menu_selection: gui::MainMenuSelection::NewGame,
+                            };
+                        }
+                    }
+                }
             }
             RunState::MainMenu { .. } => {
                 let result = gui::main_menu(self, ctx);
@@ -288,14 +615,37 @@ impl GameState for State {
                         }
                     }
                     gui::MainMenuResult::Selected { selected } => match selected {
+                        gui::MainMenuSelection::ResumeAutosave => {
+                            match saveload_system::resume_autosave(&mut self.ecs) {
+                                Ok(()) => {
+                                    self.ecs.fetch_mut::<gui::MainMenuMessage>().text = None;
+                                    new_run_state = RunState::AwaitingInput;
+                                }
+                                Err(err) => {
+                                    self.ecs.fetch_mut::<gui::MainMenuMessage>().text =
+                                        Some(err.to_string());
+                                    new_run_state = RunState::MainMenu {
+                                        menu_selection: gui::MainMenuSelection::NewGame,
+                                    };
+                                }
+                            }
+                        }
                         gui::MainMenuSelection::NewGame => {
+                            self.ecs.fetch_mut::<gui::MainMenuMessage>().text = None;
                             self.game_over_cleanup();
                             new_run_state = RunState::PreRun;
                         }
                         gui::MainMenuSelection::LoadGame => {
-                            saveload_system::load_game(&mut self.ecs);
-                            new_run_state = RunState::AwaitingInput;
-                            saveload_system::delete_save();
+                            saveload_system::refresh_slot_headers(&self.ecs);
+                            let headers = self.ecs.fetch::<saveload_system::SlotHeaders>();
+                            let selected = (0..saveload_system::SAVE_SLOTS)
+                                .find(|slot| headers.get(*slot).is_some())
+                                .unwrap_or(0);
+                            new_run_state = RunState::LoadSlots { selected };
+                        }
+                        gui::MainMenuSelection::HallOfFame => {
+                            hall_of_fame::refresh(&self.ecs);
+                            new_run_state = RunState::HallOfFame;
                         }
                         gui::MainMenuSelection::Quit => {
                             ::std::process::exit(0);
@@ -311,12 +661,22 @@ impl GameState for State {
                 new_run_state = player_input(self, ctx);
             }
             RunState::PlayerTurn => {
+                self.start_turn();
                 self.run_systems();
                 match *self.ecs.fetch::<RunState>() {
                     RunState::MagicMapReveal { .. } => {
                         new_run_state = RunState::MagicMapReveal { row: 0 }
                     }
-                    _ => new_run_state = RunState::MonsterTurn,
+                    RunState::LevelTeleport { depth } => {
+                        new_run_state = RunState::LevelTeleport { depth }
+                    }
+                    _ => {
+                        if status_effect_system::is_hasted_turn(&self.ecs) {
+                            new_run_state = RunState::AwaitingInput;
+                        } else {
+                            new_run_state = RunState::MonsterTurn;
+                        }
+                    }
                 }
             }
             RunState::MagicMapReveal { row } => {
@@ -347,8 +707,13 @@ impl GameState for State {
 
                         let is_ranged = self.ecs.read_storage::<Ranged>();
                         let is_item_ranged = is_ranged.get(item_entity);
+                        let is_container = self.ecs.read_storage::<Container>();
 
-                        if let Some(is_item_ranged) = is_item_ranged {
+                        if is_container.get(item_entity).is_some() {
+                            new_run_state = RunState::ShowContainer {
+                                container: item_entity,
+                            };
+                        } else if let Some(is_item_ranged) = is_item_ranged {
                             new_run_state = RunState::ShowTargeting {
                                 range: is_item_ranged.range,
                                 item: item_entity,
@@ -369,6 +734,53 @@ impl GameState for State {
                     }
                 }
             }
+            RunState::ShowSpells => {
+                let result = gui::show_spells(self, ctx);
+                match result.0 {
+                    gui::ItemMenuResult::Cancel => new_run_state = RunState::AwaitingInput,
+                    gui::ItemMenuResult::NoResponse => {}
+                    gui::ItemMenuResult::Selected => {
+                        let spell_entity = result.1.unwrap();
+
+                        let is_ranged = self.ecs.read_storage::<Ranged>();
+                        let is_spell_ranged = is_ranged.get(spell_entity);
+
+                        if let Some(is_spell_ranged) = is_spell_ranged {
+                            new_run_state = RunState::ShowTargeting {
+                                range: is_spell_ranged.range,
+                                item: spell_entity,
+                            };
+                        } else {
+                            let mut intent = self.ecs.write_storage::<WantsToUseItem>();
+                            intent
+                                .insert(
+                                    *self.ecs.fetch::<Entity>(),
+                                    WantsToUseItem {
+                                        item: spell_entity,
+                                        target: None,
+                                    },
+                                )
+                                .expect("Unable to insert intent");
+                            new_run_state = RunState::PlayerTurn;
+                        }
+                    }
+                }
+            }
+            RunState::ShowCrafting => {
+                let result = gui::crafting_menu(self, ctx);
+                match result.0 {
+                    gui::ItemMenuResult::Cancel => new_run_state = RunState::AwaitingInput,
+                    gui::ItemMenuResult::NoResponse => {}
+                    gui::ItemMenuResult::Selected => {
+                        let recipe = result.1.unwrap();
+                        if crafting::craft(&mut self.ecs, &recipe) {
+                            new_run_state = RunState::PlayerTurn;
+                        } else {
+                            new_run_state = RunState::AwaitingInput;
+                        }
+                    }
+                }
+            }
             RunState::ShowRemoveItem => {
                 let result = gui::remove_item_menu(self, ctx);
                 match result.0 {
@@ -387,6 +799,148 @@ impl GameState for State {
                     }
                 }
             }
+            RunState::ShowPickupItem => {
+                let result = gui::pickup_item_menu(self, ctx);
+                match result.0 {
+                    gui::ItemMenuResult::Cancel => new_run_state = RunState::AwaitingInput,
+                    gui::ItemMenuResult::NoResponse => {}
+                    gui::ItemMenuResult::Selected => {
+                        let item_entity = result.1.unwrap();
+                        let player_entity = *self.ecs.fetch::<Entity>();
+                        let mut intent = self.ecs.write_storage::<WantsToPickupItem>();
+                        intent
+                            .insert(
+                                player_entity,
+                                WantsToPickupItem {
+                                    collected_by: player_entity,
+                                    item: item_entity,
+                                },
+                            )
+                            .expect("Unable to insert intent");
+                        new_run_state = RunState::PlayerTurn;
+                    }
+                }
+            }
+            RunState::ShowAutoPickup => {
+                let result = gui::auto_pickup_menu(self, ctx);
+                match result.0 {
+                    gui::ItemMenuResult::Cancel => {
+                        let storage = self.ecs.fetch::<save_storage::SaveStore>().storage.clone();
+                        let saved = self.ecs.fetch::<config::GameConfig>().save(&*storage);
+                        if let Err(err) = saved {
+                            self.ecs
+                                .fetch_mut::<game_log::GameLog>()
+                                .entries
+                                .insert(0, format!("Unable to save your settings: {}", err));
+                        }
+                        new_run_state = RunState::AwaitingInput;
+                    }
+                    gui::ItemMenuResult::NoResponse => {}
+                    gui::ItemMenuResult::Selected => {
+                        let category = result.1.unwrap();
+                        let mut config = self.ecs.fetch_mut::<config::GameConfig>();
+                        if !config.auto_pickup.remove(&category) {
+                            config.auto_pickup.insert(category);
+                        }
+                    }
+                }
+            }
+            RunState::ShowContainer { container } => {
+                let result = gui::container_menu(self, ctx, container);
+                match result {
+                    gui::ContainerMenuResult::Cancel => new_run_state = RunState::AwaitingInput,
+                    gui::ContainerMenuResult::NoResponse => {}
+                    gui::ContainerMenuResult::Stash => {
+                        new_run_state = RunState::ShowStashItem { container }
+                    }
+                    gui::ContainerMenuResult::Take { item } => {
+                        let player_entity = *self.ecs.fetch::<Entity>();
+                        let mut intent = self.ecs.write_storage::<WantsToPickupItem>();
+                        intent
+                            .insert(
+                                player_entity,
+                                WantsToPickupItem {
+                                    collected_by: player_entity,
+                                    item,
+                                },
+                            )
+                            .expect("Unable to insert intent");
+                        new_run_state = RunState::PlayerTurn;
+                    }
+                }
+            }
+            RunState::ShowStashItem { container } => {
+                let result = gui::stash_item_menu(self, ctx, container);
+                match result.0 {
+                    gui::ItemMenuResult::Cancel => {
+                        new_run_state = RunState::ShowContainer { container }
+                    }
+                    gui::ItemMenuResult::NoResponse => {}
+                    gui::ItemMenuResult::Selected => {
+                        let item_entity = result.1.unwrap();
+                        let mut intent = self.ecs.write_storage::<WantsToStashItem>();
+                        intent
+                            .insert(
+                                *self.ecs.fetch::<Entity>(),
+                                WantsToStashItem {
+                                    item: item_entity,
+                                    container,
+                                },
+                            )
+                            .expect("Unable to insert intent");
+                        new_run_state = RunState::PlayerTurn;
+                    }
+                }
+            }
+            RunState::ShowShop { shopkeeper } => {
+                let result = gui::shop_menu(self, ctx, shopkeeper);
+                match result {
+                    gui::ShopMenuResult::Cancel => new_run_state = RunState::AwaitingInput,
+                    gui::ShopMenuResult::NoResponse => {}
+                    gui::ShopMenuResult::Sell => {
+                        new_run_state = RunState::ShowSellItem { shopkeeper }
+                    }
+                    gui::ShopMenuResult::Buy { item } => {
+                        if shop::buy(&mut self.ecs, shopkeeper, item) {
+                            new_run_state = RunState::PlayerTurn;
+                        }
+                    }
+                }
+            }
+            RunState::ShowSellItem { shopkeeper } => {
+                let result = gui::sell_item_menu(self, ctx, shopkeeper);
+                match result.0 {
+                    gui::ItemMenuResult::Cancel => {
+                        new_run_state = RunState::ShowShop { shopkeeper }
+                    }
+                    gui::ItemMenuResult::NoResponse => {}
+                    gui::ItemMenuResult::Selected => {
+                        let item_entity = result.1.unwrap();
+                        if shop::sell(&mut self.ecs, shopkeeper, item_entity) {
+                            new_run_state = RunState::PlayerTurn;
+                        }
+                    }
+                }
+            }
+            RunState::Dialogue { npc, node } => {
+                let result = gui::dialogue_menu(self, ctx, npc, node);
+                match result.0 {
+                    gui::ItemMenuResult::Cancel => new_run_state = RunState::AwaitingInput,
+                    gui::ItemMenuResult::NoResponse => {}
+                    gui::ItemMenuResult::Selected => {
+                        let choice = result.1.unwrap();
+                        new_run_state = match dialogue::choose(&mut self.ecs, npc, node, choice) {
+                            dialogue::DialogueOutcome::Next(node) => {
+                                RunState::Dialogue { npc, node }
+                            }
+                            dialogue::DialogueOutcome::Leave => RunState::PlayerTurn,
+                            dialogue::DialogueOutcome::RevealMap => {
+                                RunState::MagicMapReveal { row: 0 }
+                            }
+                        };
+                    }
+                }
+            }
             RunState::ShowTargeting { range, item } => {
                 let blast: i32;
                 {
@@ -437,6 +991,30 @@ impl GameState for State {
             }
             RunState::NextLevel => {
                 self.goto_next_level();
+                if self
+                    .ecs
+                    .fetch::<config::GameConfig>()
+                    .autosave
+                    .on_level_change
+                {
+                    self.autosave();
+                }
+                new_run_state = RunState::PreRun;
+            }
+            RunState::LevelTeleport { depth } => {
+                self.goto_level(depth);
+                self.ecs.fetch_mut::<game_log::GameLog>().entries.insert(
+                    0,
+                    format!("You are wrenched through space to depth {}!", depth),
+                );
+                if self
+                    .ecs
+                    .fetch::<config::GameConfig>()
+                    .autosave
+                    .on_level_change
+                {
+                    self.autosave();
+                }
                 new_run_state = RunState::PreRun;
             }
         }
@@ -445,7 +1023,11 @@ impl GameState for State {
             let mut run_writer = self.ecs.write_resource::<RunState>();
             *run_writer = new_run_state;
         }
+        let was_over = new_run_state == RunState::GameOver;
         damage_system::delete_the_dead(&mut self.ecs);
+        if !was_over && *self.ecs.fetch::<RunState>() == RunState::GameOver {
+            self.record_death();
+        }
     }
 }
 
@@ -459,50 +1041,44 @@ macro_rules! register_independent_systems {
     };
 }
 
-macro_rules! register_components {
-    ( $s:expr; $( $x:ty ),* ) => {
-        $(
-            $s.register::<$x>();
-        )*
-    };
-}
-
-fn main() {
-    let mut context = Rltk::init_simple8x8(80, 50, "Hello Rust World", "resources");
-    context.with_post_scanlines(true);
-
-    let systems = register_independent_systems!(
+/// Every system that runs each turn.
+fn build_systems() -> Dispatcher<'static, 'static> {
+    register_independent_systems!(
         VisibilitySystem,
         MapIndexingSystem,
         MeleeCombatSystem,
         DamageSystem,
         MonsterAISystem,
+        AllyAISystem,
         ItemCollectionSystem,
         ItemUseSystem,
+        TeleportSystem,
         ItemDropSystem,
         ItemRemoveSystem,
+        ItemStashSystem,
         ParticleSpawnSystem,
-        HungerSystem
-    );
+        HungerSystem,
+        FoodDecaySystem,
+        EncumbranceSystem,
+        ManaRegenSystem,
+        StatusEffectSystem
+    )
+}
+
+fn main() {
+    let mut context = Rltk::init_simple8x8(80, 50, "Hello Rust World", "resources");
+    context.with_post_scanlines(true);
+
+    let systems = build_systems();
 
     let mut gs = State {
         ecs: World::new(),
         systems,
     };
 
-    // registry components referenced by systems
+    component_registry::register_components(&mut gs.ecs);
     gs.systems.setup(&mut gs.ecs);
-
-    // register any extra components not directly referenced in systems above
-    register_components!(gs.ecs;
-        // serialisation components
-        SimpleMarker<SerializeMe>,
-        SerializationHelper,
-        // used by player get_item
-        Item,
-        // used in main loop
-        Ranged
-    );
+    component_registry::check_components_declared(&gs.ecs);
 
     // resources
     gs.ecs.insert(SimpleMarkerAllocator::<SerializeMe>::new());
@@ -512,19 +1088,43 @@ fn main() {
 
     let player_entity = spawner::player(&mut gs.ecs, player_x, player_y);
 
+    #[cfg(not(target_arch = "wasm32"))]
+    gs.ecs.insert(save_storage::SaveStore::new(
+        save_storage::FileStorage::in_data_dir(),
+    ));
+    #[cfg(target_arch = "wasm32")]
+    gs.ecs.insert(save_storage::SaveStore::new(
+        save_storage::MemoryStorage::default(),
+    ));
+    saveload_system::adopt_old_save(&gs.ecs);
+
     gs.ecs.insert(player_entity);
     gs.ecs.insert(RunState::MainMenu {
-        menu_selection: gui::MainMenuSelection::NewGame,
+        menu_selection: if saveload_system::does_autosave_exist(&gs.ecs) {
+            gui::MainMenuSelection::ResumeAutosave
+        } else {
+            gui::MainMenuSelection::NewGame
+        },
     });
     gs.ecs.insert(Point::new(player_x, player_y));
     gs.ecs.insert(game_log::GameLog {
         entries: vec!["Welcome to my game".to_string()],
     });
+    gs.ecs.insert(game_log::TurnCounter::default());
+    gs.ecs.insert(game_log::RunStats::default());
+    gs.ecs.insert(morgue::DeathRecap::default());
     gs.ecs.insert(rltk::RandomNumberGenerator::new());
+    gs.ecs.insert(gui::MainMenuMessage::default());
+    gs.ecs.insert(saveload_system::CurrentSave::default());
+    gs.ecs.insert(saveload_system::SlotHeaders::default());
+    gs.ecs.insert(hall_of_fame::LoadedHallOfFame::default());
+    gs.ecs.insert(saveload_system::Autosaver::default());
+    let config = config::GameConfig::load(&*gs.ecs.fetch::<save_storage::SaveStore>().storage);
+    gs.ecs.insert(config);
+    gs.ecs.insert(crafting::RecipeBook::load());
+    gs.ecs.insert(dialogue::DialogueBook::load());
 
-    for room in map.rooms.iter().skip(1) {
-        spawner::spawn_room(&mut gs.ecs, room, 1);
-    }
+    spawner::spawn_level(&mut gs.ecs, &map, 1);
 
     gs.ecs.insert(map);
     gs.ecs.insert(particle_system::ParticleBuilder::new());
@@ -532,3 +1132,136 @@ fn main() {
 
     rltk::main_loop(context, gs);
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A player on the first level, autosaving every 5 turns to memory.
+    fn test_state() -> State {
+        let mut gs = State {
+            ecs: World::new(),
+            systems: build_systems(),
+        };
+        component_registry::register_components(&mut gs.ecs);
+        gs.systems.setup(&mut gs.ecs);
+        gs.ecs.insert(SimpleMarkerAllocator::<SerializeMe>::new());
+        gs.ecs.insert(save_storage::SaveStore::new(
+            save_storage::MemoryStorage::default(),
+        ));
+        let mut config = config::GameConfig::default();
+        config.autosave.every_turns = 5;
+        gs.ecs.insert(config);
+        gs.ecs.insert(saveload_system::Autosaver::default());
+        gs.ecs.insert(saveload_system::CurrentSave::default());
+        gs.ecs.insert(gui::MainMenuMessage::default());
+        gs.ecs.insert(rltk::Point::new(0, 0));
+        gs.ecs.insert(game_log::GameLog {
+            entries: Vec::new(),
+        });
+        gs.ecs.insert(game_log::TurnCounter::default());
+        gs.ecs.insert(game_log::RunStats::default());
+        gs.ecs.insert(morgue::DeathRecap::default());
+        gs.ecs.insert(hall_of_fame::LoadedHallOfFame::default());
+        gs.ecs.insert(rltk::RandomNumberGenerator::seeded(1));
+        gs.ecs.insert(crafting::RecipeBook::load());
+        gs.ecs.insert(dialogue::DialogueBook::load());
+        let map = Map::new_map_rooms_and_corridors(1);
+        let (x, y) = map.rooms[0].center();
+        gs.ecs.insert(map);
+        let player = spawner::player(&mut gs.ecs, x, y);
+        gs.ecs.insert(player);
+        gs
+    }
+
+    #[test]
+    fn autosaves_as_the_turn_starts() {
+        let mut gs = test_state();
+        for _ in 0..4 {
+            gs.start_turn();
+        }
+        assert!(!saveload_system::does_autosave_exist(&gs.ecs));
+
+        // Without waiting for the monsters' turn, which a hasted player skips
+        gs.start_turn();
+        saveload_system::finish_autosave(&mut gs.ecs).unwrap();
+        assert!(saveload_system::does_autosave_exist(&gs.ecs));
+    }
+
+    #[test]
+    fn resumed_runs_still_lose_their_save() {
+        let mut gs = test_state();
+        saveload_system::save_game(&mut gs.ecs, 1).unwrap();
+        saveload_system::load_game(&mut gs.ecs, 1).unwrap();
+        gs.ecs.fetch_mut::<saveload_system::CurrentSave>().slot = Some(1);
+        saveload_system::autosave(&mut gs.ecs).unwrap();
+        saveload_system::finish_autosave(&mut gs.ecs).unwrap();
+
+        // The game crashes, and the run is picked up from the autosave
+        gs.ecs.fetch_mut::<saveload_system::CurrentSave>().slot = None;
+        saveload_system::resume_autosave(&mut gs.ecs).unwrap();
+        assert_eq!(gs.ecs.fetch::<saveload_system::CurrentSave>().slot, Some(1));
+
+        gs.forget_run();
+        assert!(!saveload_system::does_save_exist(&gs.ecs));
+        assert!(!saveload_system::does_autosave_exist(&gs.ecs));
+    }
+
+    #[test]
+    fn allies_without_room_are_left_behind() {
+        let mut gs = test_state();
+        let player = *gs.ecs.fetch::<Entity>();
+        let pos = gs
+            .ecs
+            .read_storage::<Position>()
+            .get(player)
+            .cloned()
+            .unwrap();
+        *gs.ecs.fetch_mut::<rltk::Point>() = rltk::Point::new(pos.x, pos.y);
+        // More followers than there are tiles around the player
+        for _ in 0..9 {
+            gs.ecs
+                .create_entity()
+                .with(Position { x: pos.x, y: pos.y })
+                .with(Name {
+                    name: "Orc".to_string(),
+                })
+                .with(Ally {})
+                .build();
+        }
+
+        gs.goto_level(2);
+        gs.ecs.maintain();
+        let names = gs.ecs.read_storage::<Name>();
+        let allies = gs.ecs.read_storage::<Ally>();
+        let orcs = (&allies, &names)
+            .join()
+            .filter(|(_, name)| name.name == "Orc")
+            .count();
+        let left_behind = gs
+            .ecs
+            .fetch::<game_log::GameLog>()
+            .entries
+            .iter()
+            .filter(|entry| *entry == "Your Orc is left behind.")
+            .count();
+        assert!(left_behind > 0);
+        assert_eq!(orcs + left_behind, 9);
+    }
+
+    #[test]
+    fn retiring_makes_the_hall_of_fame() {
+        let mut gs = test_state();
+        gs.retire();
+        assert_eq!(
+            gs.ecs.fetch::<morgue::DeathRecap>().hall_of_fame,
+            "Your run ranks #1 in the Hall of Fame!"
+        );
+
+        hall_of_fame::refresh(&gs.ecs);
+        let loaded = gs.ecs.fetch::<hall_of_fame::LoadedHallOfFame>();
+        let entries = loaded.entries.as_ref().unwrap();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].cause, "Retired");
+    }
+}
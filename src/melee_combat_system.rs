@@ -1,12 +1,14 @@
 use super::{
-    game_log::GameLog, particle_system::ParticleBuilder, CombatStats, DefenseBonus, Equipped,
-    HungerClock, HungerState, MeleePowerBonus, Name, Position, SufferDamage, WantsToMelee,
+    game_log::GameLog, particle_system::ParticleBuilder, status_effect_system::strength_bonus,
+    CombatStats, DamageSource, DefenseBonus, Encumbrance, Equipped, HungerClock, HungerState,
+    MeleeFireDamage, MeleePowerBonus, Name, Position, StatusEffects, SufferDamage, WantsToMelee,
 };
 use specs::prelude::*;
 
 pub struct MeleeCombatSystem;
 
 impl<'a> System<'a> for MeleeCombatSystem {
+    #[allow(clippy::type_complexity)]
     type SystemData = (
         Entities<'a>,
         WriteExpect<'a, GameLog>,
@@ -20,6 +22,9 @@ impl<'a> System<'a> for MeleeCombatSystem {
         WriteExpect<'a, ParticleBuilder>,
         ReadStorage<'a, Position>,
         ReadStorage<'a, HungerClock>,
+        ReadStorage<'a, MeleeFireDamage>,
+        ReadStorage<'a, Encumbrance>,
+        ReadStorage<'a, StatusEffects>,
     );
 
     fn run(&mut self, data: Self::SystemData) {
@@ -36,6 +41,9 @@ impl<'a> System<'a> for MeleeCombatSystem {
             mut particle_builder,
             positions,
             hunger_clock,
+            fire_damages,
+            encumbrance,
+            statuses,
         ) = data;
 
         for (entity, wants_melee, name, stats) in
@@ -58,7 +66,17 @@ impl<'a> System<'a> for MeleeCombatSystem {
                     }
                 }
 
-                let target_stats = combat_stats.get(wants_melee.target).unwrap();
+                if let Some(load) = encumbrance.get(entity) {
+                    offensive_bonus -= load.penalty();
+                }
+
+                offensive_bonus += strength_bonus(statuses.get(entity));
+
+                // The target may already have been killed by someone else
+                let target_stats = match combat_stats.get(wants_melee.target) {
+                    Some(target_stats) => target_stats,
+                    None => continue,
+                };
                 if target_stats.hp > 0 {
                     let target_name = names.get(wants_melee.target).unwrap();
 
@@ -71,6 +89,10 @@ impl<'a> System<'a> for MeleeCombatSystem {
                         }
                     }
 
+                    if let Some(load) = encumbrance.get(wants_melee.target) {
+                        defensive_bonus -= load.penalty();
+                    }
+
                     let pos = positions.get(wants_melee.target);
                     if let Some(pos) = pos {
                         particle_builder.request(
@@ -83,10 +105,20 @@ impl<'a> System<'a> for MeleeCombatSystem {
                         );
                     }
 
-                    let damage = i32::max(
+                    let mut damage = i32::max(
                         0,
                         (stats.power + offensive_bonus) - (target_stats.defense + defensive_bonus),
                     );
+
+                    // Flaming weapons burn straight through armour
+                    let mut fire_damage = 0;
+                    for (_item_entity, fire, equipped_by) in
+                        (&entities, &fire_damages, &equipped).join()
+                    {
+                        if equipped_by.owner == entity {
+                            fire_damage += fire.damage;
+                        }
+                    }
                     if damage == 0 {
                         log.entries.insert(
                             0,
@@ -100,8 +132,27 @@ impl<'a> System<'a> for MeleeCombatSystem {
                                 &name.name, &target_name.name, damage
                             ),
                         );
+                    }
+                    if fire_damage > 0 {
+                        log.entries.insert(
+                            0,
+                            format!(
+                                "{} burns {}, for {} hp.",
+                                &name.name, &target_name.name, fire_damage
+                            ),
+                        );
+                    }
+
+                    damage += fire_damage;
+                    if damage > 0 {
                         inflict_damage
-                            .insert(wants_melee.target, SufferDamage { amount: damage })
+                            .insert(
+                                wants_melee.target,
+                                SufferDamage {
+                                    amount: damage,
+                                    source: DamageSource::Attacker(entity),
+                                },
+                            )
                             .expect("Unable to do damage");
                     }
                 }
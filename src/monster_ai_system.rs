@@ -1,8 +1,9 @@
 use super::{
-    particle_system::ParticleBuilder, Confusion, Map, Monster, Position, RunState, Viewshed,
-    WantsToMelee,
+    game_log::GameLog, particle_system::ParticleBuilder, spawner, teleport_system, Ally,
+    CastsBlink, Confusion, Corpse, Map, Monster, Name, Necromancer, Position, RunState,
+    TeleportDestination, Viewshed, WantsToMelee, WantsToTeleport,
 };
-use rltk::Point;
+use rltk::{Point, RandomNumberGenerator};
 use specs::prelude::*;
 
 pub struct MonsterAISystem;
@@ -21,6 +22,15 @@ impl<'a> System<'a> for MonsterAISystem {
         WriteStorage<'a, WantsToMelee>,
         WriteStorage<'a, Confusion>,
         WriteExpect<'a, ParticleBuilder>,
+        WriteExpect<'a, RandomNumberGenerator>,
+        WriteExpect<'a, GameLog>,
+        ReadStorage<'a, Name>,
+        ReadStorage<'a, CastsBlink>,
+        WriteStorage<'a, WantsToTeleport>,
+        ReadStorage<'a, Necromancer>,
+        ReadStorage<'a, Corpse>,
+        Read<'a, LazyUpdate>,
+        ReadStorage<'a, Ally>,
     );
 
     fn run(&mut self, data: Self::SystemData) {
@@ -36,15 +46,71 @@ impl<'a> System<'a> for MonsterAISystem {
             mut wants_to_melee,
             mut confused,
             mut particle_builder,
+            mut rng,
+            mut log,
+            names,
+            casts_blink,
+            mut wants_teleport,
+            necromancers,
+            corpses,
+            lazy,
+            allies,
         ) = data;
 
         if *run_state != RunState::MonsterTurn {
             return;
         }
 
+        // Necromancers raise a corpse they can see, instead of moving
+        let mut casters: Vec<Entity> = Vec::new();
+        let mut raised: Vec<Entity> = Vec::new();
+        for (entity, _necromancer, viewshed, name) in
+            (&entities, &necromancers, &viewshed, &names).join()
+        {
+            if confused.get(entity).is_some() || rng.roll_dice(1, 3) > 1 {
+                continue;
+            }
+            let target = (&entities, &corpses, &position)
+                .join()
+                .find(|(corpse, _, pos)| {
+                    !raised.contains(corpse)
+                        && viewshed.visible_tiles.contains(&Point::new(pos.x, pos.y))
+                });
+            if let Some((corpse, remains, pos)) = target {
+                let idx = map.xy_idx(pos.x, pos.y);
+                if map.blocked[idx] {
+                    continue;
+                }
+                map.blocked[idx] = true;
+                spawner::zombie(
+                    lazy.create_entity(&entities),
+                    pos.x,
+                    pos.y,
+                    &remains.species,
+                );
+                entities.delete(corpse).expect("Unable to delete corpse");
+                casters.push(entity);
+                raised.push(corpse);
+                if viewshed.visible_tiles.contains(&*player_pos) {
+                    log.entries.insert(
+                        0,
+                        format!("The {} raises the {} corpse!", name.name, remains.species),
+                    );
+                }
+            }
+        }
+
+        let ally_positions: Vec<(Entity, Point)> = (&entities, &allies, &position)
+            .join()
+            .map(|(ally, _ally, pos)| (ally, Point::new(pos.x, pos.y)))
+            .collect();
+
         for (entity, mut viewshed, _monster, mut pos) in
             (&entities, &mut viewshed, &monster, &mut position).join()
         {
+            if casters.contains(&entity) {
+                continue;
+            }
             let mut can_act = true;
 
             let is_confused = confused.get_mut(entity);
@@ -76,6 +142,36 @@ impl<'a> System<'a> for MonsterAISystem {
                             },
                         )
                         .expect("Unable to insert attack");
+                } else if let Some((ally, _)) = ally_positions.iter().find(|(_, ally_pos)| {
+                    rltk::DistanceAlg::Pythagoras.distance2d(Point::new(pos.x, pos.y), *ally_pos)
+                        < 1.5
+                }) {
+                    // Fight back against the player's allies when they get in the way
+                    wants_to_melee
+                        .insert(entity, WantsToMelee { target: *ally })
+                        .expect("Unable to insert attack");
+                } else if viewshed.visible_tiles.contains(&*player_pos)
+                    && casts_blink.get(entity).is_some()
+                    && rng.roll_dice(1, 4) == 1
+                {
+                    // Blink to an open tile next to the player
+                    let dx = rng.range(-1, 2);
+                    let dy = rng.range(-1, 2);
+                    let (x, y) = (player_pos.x + dx, player_pos.y + dy);
+                    if (dx != 0 || dy != 0) && teleport_system::is_open_tile(&map, x, y) {
+                        wants_teleport
+                            .insert(
+                                entity,
+                                WantsToTeleport {
+                                    destination: TeleportDestination::Tile { x, y },
+                                },
+                            )
+                            .expect("Unable to insert teleport");
+                        if let Some(name) = names.get(entity) {
+                            log.entries
+                                .insert(0, format!("The {} blinks towards you!", name.name));
+                        }
+                    }
                 } else if viewshed.visible_tiles.contains(&*player_pos) {
                     // Path to the player
                     let path = rltk::a_star_search(
@@ -0,0 +1,282 @@
+use super::{
+    game_log::{GameLog, RunStats, TurnCounter},
+    gui::{player_can_see, wrap_text},
+    save_storage::{self, SaveStore},
+    Equipped, InBackpack, Map, Name, Position, Renderable, TileType,
+};
+use specs::prelude::*;
+use std::io;
+
+/// How far the map snapshot reaches to either side of, and above and below, the
+/// spot where the player died.
+const SNAPSHOT_WIDTH: i32 = 15;
+const SNAPSHOT_HEIGHT: i32 = 5;
+
+/// How many of the run's last log messages the recap keeps.
+const RECAP_MESSAGES: usize = 20;
+
+/// Lists are wrapped to fit the screen.
+const RECAP_WIDTH: usize = 78;
+
+/// A summary of a run that's over, shown on the game over screen. Runs that ended
+/// in death are written to a morgue file as well.
+#[derive(Default)]
+pub struct DeathRecap {
+    pub cause: String,
+    pub depth: i32,
+    pub turns: i32,
+    /// Kills by species, most killed first.
+    pub kills: Vec<(String, i32)>,
+    pub equipment: Vec<String>,
+    pub inventory: Vec<String>,
+    /// The run's last messages, oldest first.
+    pub messages: Vec<String>,
+    /// The map around where the player died, a row per line.
+    pub map: Vec<String>,
+    /// Where the morgue file was written, or why it couldn't be.
+    pub morgue: String,
+    /// Where the run ranks in the hall of fame.
+    pub hall_of_fame: String,
+}
+
+impl DeathRecap {
+    pub fn new(ecs: &World) -> DeathRecap {
+        let player_entity = *ecs.fetch::<Entity>();
+        let run_stats = ecs.fetch::<RunStats>();
+        let names = ecs.read_storage::<Name>();
+
+        let mut kills: Vec<(String, i32)> = run_stats
+            .kills
+            .iter()
+            .map(|(species, count)| (species.clone(), *count))
+            .collect();
+        kills.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+
+        let equipment = (&ecs.read_storage::<Equipped>(), &names)
+            .join()
+            .filter(|(equipped, _)| equipped.owner == player_entity)
+            .map(|(_, name)| name.name.clone())
+            .collect();
+        let inventory = (&ecs.read_storage::<InBackpack>(), &names)
+            .join()
+            .filter(|(pack, _)| pack.owner == player_entity)
+            .map(|(_, name)| name.name.clone())
+            .collect();
+
+        let mut messages: Vec<String> = ecs
+            .fetch::<GameLog>()
+            .entries
+            .iter()
+            .take(RECAP_MESSAGES)
+            .cloned()
+            .collect();
+        messages.reverse();
+
+        DeathRecap {
+            cause: run_stats
+                .cause_of_death
+                .clone()
+                .unwrap_or_else(|| "Died".to_string()),
+            depth: ecs.fetch::<Map>().depth,
+            turns: ecs.fetch::<TurnCounter>().turns,
+            kills,
+            equipment,
+            inventory,
+            messages,
+            map: map_snapshot(ecs),
+            morgue: String::new(),
+            hall_of_fame: String::new(),
+        }
+    }
+
+    /// The recap as text, a line at a time.
+    pub fn lines(&self) -> Vec<String> {
+        let list = |heading: &str, items: Vec<String>| {
+            let items = if items.is_empty() {
+                "none".to_string()
+            } else {
+                items.join(", ")
+            };
+            wrap_text(&format!("{}: {}", heading, items), RECAP_WIDTH)
+        };
+
+        let mut lines = vec![format!(
+            "{} on level {} after {} turns.",
+            self.cause, self.depth, self.turns
+        )];
+        lines.extend(list(
+            "Kills",
+            self.kills
+                .iter()
+                .map(|(species, count)| format!("{} {}", count, species))
+                .collect(),
+        ));
+        lines.extend(list("Equipment", self.equipment.clone()));
+        lines.extend(list("Inventory", self.inventory.clone()));
+        lines.push(String::new());
+        lines.extend(self.map.iter().cloned());
+        lines.push(String::new());
+        lines.push("Last messages:".to_string());
+        lines.extend(self.messages.iter().cloned());
+        lines
+    }
+}
+
+/// Draws the revealed map around the player as text, with whatever they could see
+/// standing on it.
+fn map_snapshot(ecs: &World) -> Vec<String> {
+    let map = ecs.fetch::<Map>();
+    let player_entity = *ecs.fetch::<Entity>();
+    let centre = ecs
+        .read_storage::<Position>()
+        .get(player_entity)
+        .cloned()
+        .unwrap_or(Position { x: 0, y: 0 });
+    let left = i32::max(0, centre.x - SNAPSHOT_WIDTH);
+    let right = i32::min(map.width - 1, centre.x + SNAPSHOT_WIDTH);
+    let top = i32::max(0, centre.y - SNAPSHOT_HEIGHT);
+    let bottom = i32::min(map.height - 1, centre.y + SNAPSHOT_HEIGHT);
+
+    let mut rows: Vec<Vec<char>> = (top..=bottom)
+        .map(|y| {
+            (left..=right)
+                .map(|x| {
+                    let idx = map.xy_idx(x, y);
+                    if !map.revealed_tiles[idx] {
+                        return ' ';
+                    }
+                    match map.tiles[idx] {
+                        TileType::Floor => '.',
+                        TileType::Wall => '#',
+                        TileType::DownStairs => '>',
+                    }
+                })
+                .collect()
+        })
+        .collect();
+
+    // Lowest render order last, so it ends up on top
+    let positions = ecs.read_storage::<Position>();
+    let renderables = ecs.read_storage::<Renderable>();
+    let mut seen: Vec<(&Position, &Renderable)> = (&ecs.entities(), &positions, &renderables)
+        .join()
+        .filter(|(entity, pos, _)| {
+            pos.x >= left
+                && pos.x <= right
+                && pos.y >= top
+                && pos.y <= bottom
+                && player_can_see(ecs, *entity, pos)
+        })
+        .map(|(_, pos, render)| (pos, render))
+        .collect();
+    seen.sort_by_key(|(_, render)| std::cmp::Reverse(render.render_order));
+    for (pos, render) in seen {
+        rows[(pos.y - top) as usize][(pos.x - left) as usize] = rltk::to_char(render.glyph);
+    }
+
+    rows.into_iter()
+        .map(|row| row.into_iter().collect::<String>().trim_end().to_string())
+        .collect()
+}
+
+/// Writes the recap to a morgue file named for when the player died, and says where
+/// it went.
+pub fn write_morgue(ecs: &World, recap: &DeathRecap) -> io::Result<String> {
+    let storage = ecs.fetch::<SaveStore>().storage.clone();
+    let now = save_storage::now();
+    let stamp =
+        save_storage::format_time(now, "%Y%m%d-%H%M%S").unwrap_or_else(|| "undated".to_string());
+    let name = (1..)
+        .map(|copy| {
+            if copy == 1 {
+                format!("morgue/morgue-{}.txt", stamp)
+            } else {
+                format!("morgue/morgue-{}-{}.txt", stamp, copy)
+            }
+        })
+        .find(|name| !storage.exists(name))
+        .unwrap();
+    let mut text = match save_storage::format_time(now, "%Y-%m-%d %H:%M") {
+        Some(date) => format!("Died {}\n\n", date),
+        None => "Died\n\n".to_string(),
+    };
+    for line in recap.lines() {
+        text.push_str(&line);
+        text.push('\n');
+    }
+
+    storage.write(&name, text.as_bytes())?;
+    Ok(storage.location(&name))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::component_registry::register_components;
+    use crate::save_storage::MemoryStorage;
+    use crate::spawner;
+    use specs::saveload::SimpleMarkerAllocator;
+
+    /// A player who has just died on level 2, with the whole map in view.
+    fn test_world() -> World {
+        let mut ecs = World::new();
+        register_components(&mut ecs);
+        ecs.insert(SimpleMarkerAllocator::<crate::SerializeMe>::new());
+        ecs.insert(SaveStore::new(MemoryStorage::default()));
+        let mut map = Map::new_map_rooms_and_corridors(2);
+        map.revealed_tiles = vec![true; map.tiles.len()];
+        map.visible_tiles = vec![true; map.tiles.len()];
+        let (x, y) = map.rooms[0].center();
+        ecs.insert(map);
+        let player = spawner::player(&mut ecs, x, y);
+        ecs.insert(player);
+
+        ecs.insert(GameLog {
+            entries: (0..25).rev().map(|i| format!("message {}", i)).collect(),
+        });
+        ecs.insert(TurnCounter { turns: 100 });
+        let mut run_stats = RunStats::default();
+        run_stats.kills.insert("Orc".to_string(), 1);
+        run_stats.kills.insert("Goblin".to_string(), 3);
+        run_stats.cause_of_death = Some("Killed by an Orc".to_string());
+        ecs.insert(run_stats);
+        ecs
+    }
+
+    #[test]
+    fn recap_sums_up_the_run() {
+        let recap = DeathRecap::new(&test_world());
+        let lines = recap.lines();
+        assert_eq!(lines[0], "Killed by an Orc on level 2 after 100 turns.");
+        assert_eq!(lines[1], "Kills: 3 Goblin, 1 Orc");
+        assert_eq!(recap.messages.len(), RECAP_MESSAGES);
+        assert_eq!(recap.messages.first().unwrap(), "message 5");
+        assert_eq!(lines.last().unwrap(), "message 24");
+    }
+
+    #[test]
+    fn snapshot_is_centred_on_the_player() {
+        let ecs = test_world();
+        let player = *ecs.fetch::<Entity>();
+        let pos = ecs.read_storage::<Position>().get(player).cloned().unwrap();
+        let recap = DeathRecap::new(&ecs);
+
+        // The snapshot is cut short at the edges of the map
+        let row = &recap.map[i32::min(pos.y, SNAPSHOT_HEIGHT) as usize];
+        let column = i32::min(pos.x, SNAPSHOT_WIDTH) as usize;
+        assert_eq!(row.chars().nth(column), Some('@'));
+    }
+
+    #[test]
+    fn morgue_file_holds_the_recap() {
+        let ecs = test_world();
+        let recap = DeathRecap::new(&ecs);
+        let location = write_morgue(&ecs, &recap).unwrap();
+        assert!(location.starts_with("morgue/morgue-"));
+
+        let text = ecs.fetch::<SaveStore>().storage.read(&location).unwrap();
+        let text = String::from_utf8(text).unwrap();
+        assert!(text.contains("Killed by an Orc on level 2"));
+        assert!(text.contains("message 24"));
+    }
+}
@@ -1,51 +1,103 @@
 use super::{
-    game_log::GameLog, CombatStats, HungerClock, HungerState, Item, Map, Monster, Player, Position,
-    RunState, State, TileType, Viewshed, WantsToMelee, WantsToPickupItem,
+    config::GameConfig, dialogue, game_log::GameLog, saveload_system,
+    status_effect_system::player_has_status, Ally, CombatStats, Container, ForSale, HungerClock,
+    HungerState, Item, ItemClass, Map, Monster, Npc, Player, Position, RunState, Shopkeeper, State,
+    StatusEffect, TileType, Viewshed, WantsToMelee, WantsToPickupItem,
 };
 use rltk::{Point, Rltk, VirtualKeyCode};
 use specs::prelude::*;
 use std::cmp::{max, min};
 
-pub fn try_move_player(delta_x: i32, delta_y: i32, ecs: &mut World) {
-    let mut positions = ecs.write_storage::<Position>();
-    let players = ecs.write_storage::<Player>();
-    let mut viewsheds = ecs.write_storage::<Viewshed>();
-    let entities = ecs.entities();
-    let combat_stats = ecs.read_storage::<CombatStats>();
-    let mut wants_to_melee = ecs.write_storage::<WantsToMelee>();
-    let map = ecs.fetch::<Map>();
-
-    for (entity, _player, pos, viewshed) in
-        (&entities, &players, &mut positions, &mut viewsheds).join()
+/// Moves the player, or acts on whatever is in the way: allies swap places, peaceful
+/// shopkeepers open their shop, peaceful NPCs talk and anything else with combat stats
+/// is attacked.
+pub fn try_move_player(delta_x: i32, delta_y: i32, ecs: &mut World) -> RunState {
+    let mut moved = false;
     {
-        let destination_idx = map.xy_idx(pos.x + delta_x, pos.y + delta_y);
-
-        for potential_target in map.tile_content[destination_idx].iter() {
-            let target = combat_stats.get(*potential_target);
-            if let Some(_target) = target {
-                wants_to_melee
-                    .insert(
-                        entity,
-                        WantsToMelee {
-                            target: *potential_target,
-                        },
-                    )
-                    .expect("Add target failed");
-                return;
+        let mut positions = ecs.write_storage::<Position>();
+        let players = ecs.write_storage::<Player>();
+        let mut viewsheds = ecs.write_storage::<Viewshed>();
+        let entities = ecs.entities();
+        let combat_stats = ecs.read_storage::<CombatStats>();
+        let allies = ecs.read_storage::<Ally>();
+        let shopkeepers = ecs.read_storage::<Shopkeeper>();
+        let npcs = ecs.read_storage::<Npc>();
+        let monsters = ecs.read_storage::<Monster>();
+        let mut wants_to_melee = ecs.write_storage::<WantsToMelee>();
+        let map = ecs.fetch::<Map>();
+        let mut swap_with: Option<(Entity, i32, i32)> = None;
+
+        for (entity, _player, pos, viewshed) in
+            (&entities, &players, &mut positions, &mut viewsheds).join()
+        {
+            let destination_idx = map.xy_idx(pos.x + delta_x, pos.y + delta_y);
+
+            for potential_target in map.tile_content[destination_idx].iter() {
+                // Allies get out of the way by trading places
+                if allies.get(*potential_target).is_some() {
+                    swap_with = Some((*potential_target, pos.x, pos.y));
+                    break;
+                }
+
+                if shopkeepers.get(*potential_target).is_some()
+                    && monsters.get(*potential_target).is_none()
+                {
+                    return RunState::ShowShop {
+                        shopkeeper: *potential_target,
+                    };
+                }
+
+                if npcs.get(*potential_target).is_some()
+                    && monsters.get(*potential_target).is_none()
+                {
+                    return RunState::Dialogue {
+                        npc: *potential_target,
+                        node: dialogue::start_node(ecs, *potential_target),
+                    };
+                }
+
+                let target = combat_stats.get(*potential_target);
+                if let Some(_target) = target {
+                    wants_to_melee
+                        .insert(
+                            entity,
+                            WantsToMelee {
+                                target: *potential_target,
+                            },
+                        )
+                        .expect("Add target failed");
+                    return RunState::PlayerTurn;
+                }
             }
-        }
 
-        if !map.blocked[destination_idx] {
-            pos.x = min(79, max(0, pos.x + delta_x));
-            pos.y = min(49, max(0, pos.y + delta_y));
+            if swap_with.is_some() || !map.blocked[destination_idx] {
+                pos.x = min(79, max(0, pos.x + delta_x));
+                pos.y = min(49, max(0, pos.y + delta_y));
 
-            let mut ppos = ecs.write_resource::<Point>();
-            ppos.x = pos.x;
-            ppos.y = pos.y;
+                let mut ppos = ecs.write_resource::<Point>();
+                ppos.x = pos.x;
+                ppos.y = pos.y;
 
-            viewshed.dirty = true;
+                viewshed.dirty = true;
+                moved = true;
+            }
+        }
+
+        if let Some((ally, x, y)) = swap_with {
+            if let Some(ally_pos) = positions.get_mut(ally) {
+                ally_pos.x = x;
+                ally_pos.y = y;
+            }
+            if let Some(ally_viewshed) = viewsheds.get_mut(ally) {
+                ally_viewshed.dirty = true;
+            }
         }
     }
+
+    if moved {
+        auto_pickup(ecs);
+    }
+    RunState::PlayerTurn
 }
 
 pub fn player_input(gs: &mut State, ctx: &mut Rltk) -> RunState {
@@ -54,34 +106,47 @@ pub fn player_input(gs: &mut State, ctx: &mut Rltk) -> RunState {
         None => return RunState::AwaitingInput, // Nothing happened
         Some(key) => match key {
             VirtualKeyCode::Left | VirtualKeyCode::Numpad4 | VirtualKeyCode::H => {
-                try_move_player(-1, 0, &mut gs.ecs)
+                return try_move_player(-1, 0, &mut gs.ecs)
             }
 
             VirtualKeyCode::Right | VirtualKeyCode::Numpad6 | VirtualKeyCode::L => {
-                try_move_player(1, 0, &mut gs.ecs)
+                return try_move_player(1, 0, &mut gs.ecs)
             }
 
             VirtualKeyCode::Up | VirtualKeyCode::Numpad8 | VirtualKeyCode::K => {
-                try_move_player(0, -1, &mut gs.ecs)
+                return try_move_player(0, -1, &mut gs.ecs)
             }
 
             VirtualKeyCode::Down | VirtualKeyCode::Numpad2 | VirtualKeyCode::J => {
-                try_move_player(0, 1, &mut gs.ecs)
+                return try_move_player(0, 1, &mut gs.ecs)
             }
 
             // Diagonals
-            VirtualKeyCode::Numpad9 | VirtualKeyCode::Y => try_move_player(1, -1, &mut gs.ecs),
+            VirtualKeyCode::Numpad9 | VirtualKeyCode::Y => {
+                return try_move_player(1, -1, &mut gs.ecs)
+            }
 
-            VirtualKeyCode::Numpad7 | VirtualKeyCode::U => try_move_player(-1, -1, &mut gs.ecs),
+            VirtualKeyCode::Numpad7 | VirtualKeyCode::U => {
+                return try_move_player(-1, -1, &mut gs.ecs)
+            }
 
-            VirtualKeyCode::Numpad3 | VirtualKeyCode::N => try_move_player(1, 1, &mut gs.ecs),
+            VirtualKeyCode::Numpad3 | VirtualKeyCode::N => {
+                return try_move_player(1, 1, &mut gs.ecs)
+            }
 
-            VirtualKeyCode::Numpad1 | VirtualKeyCode::B => try_move_player(-1, 1, &mut gs.ecs),
+            VirtualKeyCode::Numpad1 | VirtualKeyCode::B => {
+                return try_move_player(-1, 1, &mut gs.ecs)
+            }
 
-            VirtualKeyCode::G => get_item(&mut gs.ecs),
+            VirtualKeyCode::G => return get_item(&mut gs.ecs),
             VirtualKeyCode::I => return RunState::ShowInventory,
+            VirtualKeyCode::Z => return RunState::ShowSpells,
+            VirtualKeyCode::C => return RunState::ShowCrafting,
             VirtualKeyCode::D => return RunState::ShowDropItem,
-            VirtualKeyCode::Escape => return RunState::SaveGame,
+            VirtualKeyCode::Escape => {
+                saveload_system::refresh_slot_headers(&gs.ecs);
+                return RunState::SaveSlots { selected: 0 };
+            }
             // Level changes
             VirtualKeyCode::Period => {
                 if try_next_level(&mut gs.ecs) {
@@ -92,6 +157,9 @@ pub fn player_input(gs: &mut State, ctx: &mut Rltk) -> RunState {
             VirtualKeyCode::Numpad5 => return skip_turn(&mut gs.ecs),
             VirtualKeyCode::Space => return skip_turn(&mut gs.ecs),
             VirtualKeyCode::R => return RunState::ShowRemoveItem,
+            VirtualKeyCode::A => return RunState::ShowAutoPickup,
+            VirtualKeyCode::O => return open_container(&mut gs.ecs),
+            VirtualKeyCode::Q => return RunState::ConfirmRetire,
 
             _ => return RunState::AwaitingInput,
         },
@@ -145,6 +213,14 @@ pub fn try_next_level(ecs: &mut World) -> bool {
     let map = ecs.fetch::<Map>();
     let player_idx = map.xy_idx(player_pos.x, player_pos.y);
     if map.tiles[player_idx] == TileType::DownStairs {
+        if player_has_status(ecs, StatusEffect::Levitation) {
+            let mut gamelog = ecs.fetch_mut::<GameLog>();
+            gamelog.entries.insert(
+                0,
+                "You float above the stairs, unable to reach them.".to_string(),
+            );
+            return false;
+        }
         true
     } else {
         let mut gamelog = ecs.fetch_mut::<GameLog>();
@@ -155,7 +231,16 @@ pub fn try_next_level(ecs: &mut World) -> bool {
     }
 }
 
-fn get_item(ecs: &mut World) {
+fn get_item(ecs: &mut World) -> RunState {
+    if player_has_status(ecs, StatusEffect::Levitation) {
+        let mut game_log = ecs.fetch_mut::<GameLog>();
+        game_log.entries.insert(
+            0,
+            "You are floating too high to reach the floor.".to_string(),
+        );
+        return RunState::AwaitingInput;
+    }
+
     let player_pos = ecs.fetch::<Point>();
     let player_entity = ecs.fetch::<Entity>();
     let entities = ecs.entities();
@@ -163,28 +248,124 @@ fn get_item(ecs: &mut World) {
     let positions = ecs.read_storage::<Position>();
     let mut game_log = ecs.fetch_mut::<GameLog>();
 
-    let mut target_item: Option<Entity> = None;
+    let mut target_items: Vec<Entity> = Vec::new();
     for (item_entity, _item, position) in (&entities, &items, &positions).join() {
         if position.x == player_pos.x && position.y == player_pos.y {
-            target_item = Some(item_entity);
+            target_items.push(item_entity);
         }
     }
 
-    match target_item {
-        None => game_log
+    match target_items.len() {
+        0 => game_log
             .entries
             .insert(0, "There is nothing here to pick up.".to_string()),
-        Some(item) => {
+        1 => {
             let mut pickup = ecs.write_storage::<WantsToPickupItem>();
             pickup
                 .insert(
                     *player_entity,
                     WantsToPickupItem {
                         collected_by: *player_entity,
-                        item,
+                        item: target_items[0],
                     },
                 )
                 .expect("Unable to insert want to pickup");
         }
+        _ => return RunState::ShowPickupItem,
+    }
+
+    RunState::PlayerTurn
+}
+
+/// Picks up everything on the player's tile that matches the auto-pickup categories.
+/// Shop stock is left for the player to buy, rather than being stolen in passing.
+fn auto_pickup(ecs: &World) {
+    let config = ecs.fetch::<GameConfig>();
+    if config.auto_pickup.is_empty() || player_has_status(ecs, StatusEffect::Levitation) {
+        return;
+    }
+
+    let player_pos = ecs.fetch::<Point>();
+    let player_entity = ecs.fetch::<Entity>();
+    let entities = ecs.entities();
+    let items = ecs.read_storage::<Item>();
+    let classes = ecs.read_storage::<ItemClass>();
+    let positions = ecs.read_storage::<Position>();
+    let for_sale = ecs.read_storage::<ForSale>();
+    let mut pickup = ecs.write_storage::<WantsToPickupItem>();
+
+    for (item_entity, _item, class, position, _) in
+        (&entities, &items, &classes, &positions, !&for_sale).join()
+    {
+        if position.x == player_pos.x
+            && position.y == player_pos.y
+            && config.auto_pickup.contains(&class.category)
+        {
+            // Attached to the item rather than the player, so several can be collected at once
+            pickup
+                .insert(
+                    item_entity,
+                    WantsToPickupItem {
+                        collected_by: *player_entity,
+                        item: item_entity,
+                    },
+                )
+                .expect("Unable to insert want to pickup");
+        }
+    }
+}
+
+/// Opens a container (such as a chest) lying on or next to the player's tile.
+fn open_container(ecs: &mut World) -> RunState {
+    let player_pos = ecs.fetch::<Point>();
+    let entities = ecs.entities();
+    let containers = ecs.read_storage::<Container>();
+    let positions = ecs.read_storage::<Position>();
+
+    for (entity, _container, position) in (&entities, &containers, &positions).join() {
+        if i32::abs(position.x - player_pos.x) <= 1 && i32::abs(position.y - player_pos.y) <= 1 {
+            return RunState::ShowContainer { container: entity };
+        }
+    }
+
+    let mut game_log = ecs.fetch_mut::<GameLog>();
+    game_log
+        .entries
+        .insert(0, "There is nothing here to open.".to_string());
+    RunState::AwaitingInput
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::component_registry::register_components;
+    use crate::spawner::{self, Spawn};
+    use crate::{ItemCategory, SerializeMe};
+    use rltk::RandomNumberGenerator;
+    use specs::saveload::SimpleMarkerAllocator;
+
+    #[test]
+    fn auto_pickup_leaves_shop_stock() {
+        let mut ecs = World::new();
+        register_components(&mut ecs);
+        ecs.insert(SimpleMarkerAllocator::<SerializeMe>::new());
+        ecs.insert(RandomNumberGenerator::seeded(1));
+        let mut config = GameConfig::default();
+        config.auto_pickup.insert(ItemCategory::Potion);
+        ecs.insert(config);
+        ecs.insert(Point::new(5, 5));
+        let player = spawner::player(&mut ecs, 5, 5);
+        ecs.insert(player);
+
+        let free = spawner::spawn_item(&mut ecs, Spawn::HealthPotion, 5, 5, 1).unwrap();
+        let stock = spawner::spawn_item(&mut ecs, Spawn::HealthPotion, 5, 5, 1).unwrap();
+        ecs.write_storage::<ForSale>()
+            .insert(stock, ForSale { shopkeeper: player })
+            .unwrap();
+
+        auto_pickup(&ecs);
+        let pickup = ecs.read_storage::<WantsToPickupItem>();
+        assert!(pickup.get(free).is_some());
+        assert!(pickup.get(stock).is_none());
     }
 }
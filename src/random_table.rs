@@ -1,58 +1,257 @@
 use rltk::RandomNumberGenerator;
 
-pub struct RandomEntry {
-    name: String,
+/// Something a table can produce: either a value, or a nested table to roll on in turn.
+pub enum TableEntry<T> {
+    Value(T),
+    Table(RandomTable<T>),
+}
+
+pub struct RandomEntry<T> {
+    entry: TableEntry<T>,
     weight: i32,
+    min_depth: i32,
+    max_depth: i32,
 }
 
-impl RandomEntry {
-    pub fn new<S: ToString>(name: S, weight: i32) -> RandomEntry {
+impl<T> RandomEntry<T> {
+    pub fn new(entry: TableEntry<T>, weight: i32) -> RandomEntry<T> {
         RandomEntry {
-            name: name.to_string(),
+            entry,
             weight,
+            min_depth: 1,
+            max_depth: i32::MAX,
         }
     }
+
+    /// The entry's weight at `depth`, or 0 if it can't be rolled there. A nested
+    /// table with nothing available at `depth` can't be rolled either.
+    fn weight_at(&self, depth: i32) -> i32 {
+        if self.weight <= 0 || depth < self.min_depth || depth > self.max_depth {
+            return 0;
+        }
+        match &self.entry {
+            TableEntry::Table(table) if table.total_weight(depth) == 0 => 0,
+            _ => self.weight,
+        }
+    }
+}
+
+/// A weighted table of typed entries. Each entry can be limited to a band of dungeon
+/// depths, and can itself be a table (e.g. "any potion"). Values marked as guaranteed
+/// are spawned once per level regardless of what the rolls produce.
+pub struct RandomTable<T> {
+    entries: Vec<RandomEntry<T>>,
+    guaranteed: Vec<(T, i32, i32)>,
 }
 
-#[derive(Default)]
-pub struct RandomTable {
-    entries: Vec<RandomEntry>,
-    total_weight: i32,
+impl<T> Default for RandomTable<T> {
+    fn default() -> Self {
+        RandomTable::new()
+    }
 }
 
-impl RandomTable {
-    pub fn new() -> RandomTable {
+impl<T> RandomTable<T> {
+    pub fn new() -> RandomTable<T> {
         RandomTable {
             entries: Vec::new(),
-            total_weight: 0,
+            guaranteed: Vec::new(),
         }
     }
 
-    pub fn add<S: ToString>(mut self, name: S, weight: i32) -> RandomTable {
-        if weight > 0 {
-            self.total_weight += weight;
-            self.entries
-                .push(RandomEntry::new(name.to_string(), weight));
+    pub fn add(mut self, value: T, weight: i32) -> RandomTable<T> {
+        self.entries
+            .push(RandomEntry::new(TableEntry::Value(value), weight));
+        self
+    }
+
+    /// Adds a nested table, which is rolled on when this entry is picked.
+    pub fn add_table(mut self, table: RandomTable<T>, weight: i32) -> RandomTable<T> {
+        self.entries
+            .push(RandomEntry::new(TableEntry::Table(table), weight));
+        self
+    }
+
+    /// Restricts the most recently added entry to depths `min..=max`.
+    pub fn depths(mut self, min: i32, max: i32) -> RandomTable<T> {
+        if let Some(entry) = self.entries.last_mut() {
+            entry.min_depth = min;
+            entry.max_depth = max;
         }
         self
     }
 
-    pub fn roll(&self, rng: &mut RandomNumberGenerator) -> String {
-        if self.total_weight == 0 {
-            return "None".to_string();
+    /// Restricts the most recently added entry to `min` and deeper.
+    pub fn min_depth(self, min: i32) -> RandomTable<T> {
+        self.depths(min, i32::MAX)
+    }
+
+    /// Always produces `value` once on levels between `min` and `max` inclusive.
+    pub fn guarantee(mut self, value: T, min: i32, max: i32) -> RandomTable<T> {
+        self.guaranteed.push((value, min, max));
+        self
+    }
+
+    fn total_weight(&self, depth: i32) -> i32 {
+        self.entries.iter().map(|e| e.weight_at(depth)).sum()
+    }
+}
+
+impl<T: Clone> RandomTable<T> {
+    /// Picks an entry available at `depth`, or `None` if nothing is.
+    pub fn roll(&self, rng: &mut RandomNumberGenerator, depth: i32) -> Option<T> {
+        let total_weight = self.total_weight(depth);
+        if total_weight == 0 {
+            return None;
         }
-        let mut roll = rng.roll_dice(1, self.total_weight) - 1;
-        let mut index: usize = 0;
 
-        while roll > 0 {
-            if roll < self.entries[index].weight {
-                return self.entries[index].name.clone();
+        let mut roll = rng.roll_dice(1, total_weight) - 1;
+        for entry in self.entries.iter() {
+            let weight = entry.weight_at(depth);
+            if roll < weight {
+                return match &entry.entry {
+                    TableEntry::Value(value) => Some(value.clone()),
+                    TableEntry::Table(table) => table.roll(rng, depth),
+                };
+            }
+            roll -= weight;
+        }
+
+        None
+    }
+
+    /// The values that must appear on a level at `depth`.
+    pub fn guaranteed(&self, depth: i32) -> Vec<T> {
+        self.guaranteed
+            .iter()
+            .filter(|(_, min, max)| depth >= *min && depth <= *max)
+            .map(|(value, _, _)| value.clone())
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ROLLS: usize = 10_000;
+
+    fn count_rolls(
+        table: &RandomTable<&'static str>,
+        depth: i32,
+        seed: u64,
+    ) -> Vec<(&'static str, usize)> {
+        let mut rng = RandomNumberGenerator::seeded(seed);
+        let mut counts: Vec<(&'static str, usize)> = Vec::new();
+        for _ in 0..ROLLS {
+            let name = table.roll(&mut rng, depth).unwrap_or("None");
+            match counts.iter_mut().find(|(n, _)| *n == name) {
+                Some(count) => count.1 += 1,
+                None => counts.push((name, 1)),
             }
+        }
+        counts.sort();
+        counts
+    }
+
+    fn share(counts: &[(&str, usize)], name: &str) -> f32 {
+        let count = counts.iter().find(|(n, _)| *n == name).map_or(0, |c| c.1);
+        count as f32 / ROLLS as f32
+    }
+
+    #[test]
+    fn empty_table_rolls_nothing() {
+        let table: RandomTable<&str> = RandomTable::new();
+        let mut rng = RandomNumberGenerator::seeded(1);
+        assert_eq!(table.roll(&mut rng, 1), None);
+    }
+
+    #[test]
+    fn single_entry_is_always_rolled() {
+        let table = RandomTable::new().add("Goblin", 1);
+        let mut rng = RandomNumberGenerator::seeded(1);
+        for _ in 0..100 {
+            assert_eq!(table.roll(&mut rng, 1), Some("Goblin"));
+        }
+    }
 
-            roll -= self.entries[index].weight;
-            index += 1;
+    #[test]
+    fn distribution_follows_weights() {
+        let table = RandomTable::new()
+            .add("Goblin", 10)
+            .add("Orc", 5)
+            .add("Rations", 5);
+        let counts = count_rolls(&table, 1, 52);
+
+        assert!(counts.iter().all(|(name, _)| *name != "None"));
+        assert!((share(&counts, "Goblin") - 0.5).abs() < 0.02);
+        assert!((share(&counts, "Orc") - 0.25).abs() < 0.02);
+        assert!((share(&counts, "Rations") - 0.25).abs() < 0.02);
+    }
+
+    #[test]
+    fn distribution_matches_seed() {
+        let table = RandomTable::new()
+            .add("Goblin", 10)
+            .add("Orc", 5)
+            .add("Rations", 5);
+        assert_eq!(
+            count_rolls(&table, 1, 52),
+            vec![("Goblin", 5057), ("Orc", 2396), ("Rations", 2547)]
+        );
+    }
+
+    #[test]
+    fn depth_bands_exclude_entries() {
+        let table = RandomTable::new()
+            .add("Goblin", 1)
+            .add("Longsword", 1)
+            .min_depth(3)
+            .add("Dagger", 1)
+            .depths(1, 2);
+
+        let shallow = count_rolls(&table, 1, 7);
+        assert_eq!(share(&shallow, "Longsword"), 0.0);
+        assert!((share(&shallow, "Dagger") - 0.5).abs() < 0.02);
+
+        let deep = count_rolls(&table, 3, 7);
+        assert_eq!(share(&deep, "Dagger"), 0.0);
+        assert!((share(&deep, "Longsword") - 0.5).abs() < 0.02);
+    }
+
+    #[test]
+    fn nested_tables_split_their_weight() {
+        let potions = RandomTable::new()
+            .add("Health Potion", 3)
+            .add("Strength Potion", 1);
+        let table = RandomTable::new().add("Goblin", 1).add_table(potions, 1);
+        let counts = count_rolls(&table, 1, 99);
+
+        assert!((share(&counts, "Goblin") - 0.5).abs() < 0.02);
+        assert!((share(&counts, "Health Potion") - 0.375).abs() < 0.02);
+        assert!((share(&counts, "Strength Potion") - 0.125).abs() < 0.02);
+    }
+
+    #[test]
+    fn nested_table_with_nothing_available_is_skipped() {
+        let deep_only = RandomTable::new().add("Tower Shield", 1).min_depth(5);
+        let table = RandomTable::new().add_table(deep_only, 10).add("Goblin", 1);
+        let mut rng = RandomNumberGenerator::seeded(3);
+        for _ in 0..100 {
+            assert_eq!(table.roll(&mut rng, 1), Some("Goblin"));
         }
 
-        "None".to_string()
+        let deep = count_rolls(&table, 5, 3);
+        assert!(share(&deep, "Tower Shield") > 0.85);
+    }
+
+    #[test]
+    fn guaranteed_spawns_respect_depth() {
+        let table = RandomTable::new()
+            .add("Goblin", 1)
+            .guarantee("Rations", 1, 3)
+            .guarantee("Amulet", 5, 5);
+        assert_eq!(table.guaranteed(2), vec!["Rations"]);
+        assert_eq!(table.guaranteed(5), vec!["Amulet"]);
+        assert!(table.guaranteed(4).is_empty());
     }
 }
@@ -0,0 +1,283 @@
+use super::config::SaveEncoding;
+use super::saveload_system::{ComponentLoader, SaveError, SaveHeader, SavedComponents};
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use serde::de::DeserializeSeed;
+use serde_json::Value as JsonValue;
+use std::convert::TryInto;
+use std::io::{Read, Write};
+
+/// A way of writing the world to disk. Every format can be told apart from the
+/// others by its first few bytes, so saves load whichever one they were written in.
+pub trait SaveFormat: Sync {
+    /// Whether `data` looks like it was written in this format.
+    fn detect(&self, data: &[u8]) -> bool;
+    fn encode(&self, components: &SavedComponents) -> Result<Vec<u8>, SaveError>;
+    /// Reads the components straight into the world.
+    fn decode(&self, data: &[u8], loader: ComponentLoader) -> Result<(), SaveError>;
+    /// Reads the data as plain values, for upgrading saves from older versions.
+    fn decode_value(&self, data: &[u8]) -> Result<JsonValue, SaveError>;
+}
+
+/// Indented JSON, for reading and editing saves by hand.
+pub struct JsonFormat;
+
+impl SaveFormat for JsonFormat {
+    fn detect(&self, data: &[u8]) -> bool {
+        matches!(
+            data.iter().find(|b| !b.is_ascii_whitespace()),
+            Some(b'{') | Some(b'[')
+        )
+    }
+
+    fn encode(&self, components: &SavedComponents) -> Result<Vec<u8>, SaveError> {
+        serde_json::to_vec_pretty(components).map_err(|err| SaveError::Write(err.to_string()))
+    }
+
+    fn decode(&self, data: &[u8], loader: ComponentLoader) -> Result<(), SaveError> {
+        let corrupt = |err: serde_json::Error| SaveError::Corrupt(err.to_string());
+        let mut deserializer = serde_json::Deserializer::from_slice(data);
+        loader.deserialize(&mut deserializer).map_err(corrupt)?;
+        deserializer.end().map_err(corrupt)
+    }
+
+    fn decode_value(&self, data: &[u8]) -> Result<JsonValue, SaveError> {
+        serde_json::from_slice(data).map_err(|_| SaveError::Unrecognised)
+    }
+}
+
+/// CBOR, which is compact and quick to read, optionally gzipped as well.
+pub struct BinaryFormat {
+    compressed: bool,
+}
+
+/// CBOR's self-describing tag, written at the start of every binary save.
+const CBOR_MAGIC: [u8; 3] = [0xd9, 0xd9, 0xf7];
+const GZIP_MAGIC: [u8; 2] = [0x1f, 0x8b];
+
+impl BinaryFormat {
+    fn unpack(&self, data: &[u8]) -> Result<Vec<u8>, SaveError> {
+        let data = if self.compressed {
+            let mut unpacked = Vec::new();
+            GzDecoder::new(data)
+                .read_to_end(&mut unpacked)
+                .map_err(|err| SaveError::Corrupt(err.to_string()))?;
+            unpacked
+        } else {
+            data.to_vec()
+        };
+        if !data.starts_with(&CBOR_MAGIC) {
+            return Err(SaveError::Unrecognised);
+        }
+        Ok(data)
+    }
+}
+
+impl SaveFormat for BinaryFormat {
+    fn detect(&self, data: &[u8]) -> bool {
+        if self.compressed {
+            data.starts_with(&GZIP_MAGIC)
+        } else {
+            data.starts_with(&CBOR_MAGIC)
+        }
+    }
+
+    fn encode(&self, components: &SavedComponents) -> Result<Vec<u8>, SaveError> {
+        let write_error = |err: std::io::Error| SaveError::Write(err.to_string());
+
+        let mut encoded = Vec::new();
+        let mut serializer = serde_cbor::Serializer::new(&mut encoded);
+        serializer
+            .self_describe()
+            .and_then(|_| serde::Serialize::serialize(components, &mut serializer))
+            .map_err(|err| SaveError::Write(err.to_string()))?;
+        if !self.compressed {
+            return Ok(encoded);
+        }
+
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(&encoded).map_err(write_error)?;
+        encoder.finish().map_err(write_error)
+    }
+
+    fn decode(&self, data: &[u8], loader: ComponentLoader) -> Result<(), SaveError> {
+        let corrupt = |err: serde_cbor::Error| SaveError::Corrupt(err.to_string());
+        let data = self.unpack(data)?;
+        let mut deserializer = serde_cbor::Deserializer::from_slice(&data);
+        loader.deserialize(&mut deserializer).map_err(corrupt)?;
+        deserializer.end().map_err(corrupt)
+    }
+
+    fn decode_value(&self, data: &[u8]) -> Result<JsonValue, SaveError> {
+        serde_cbor::from_slice(&self.unpack(data)?)
+            .map_err(|err| SaveError::Corrupt(err.to_string()))
+    }
+}
+
+const FORMATS: [&dyn SaveFormat; 3] = [
+    &BinaryFormat { compressed: true },
+    &BinaryFormat { compressed: false },
+    &JsonFormat,
+];
+
+/// The format to write saves in.
+pub fn format_for(encoding: SaveEncoding) -> &'static dyn SaveFormat {
+    match encoding {
+        SaveEncoding::CompressedBinary => FORMATS[0],
+        SaveEncoding::Binary => FORMATS[1],
+        SaveEncoding::Json => FORMATS[2],
+    }
+}
+
+/// The format `data` was written in, if it's one the game knows.
+pub fn detect_format(data: &[u8]) -> Option<&'static dyn SaveFormat> {
+    FORMATS.iter().copied().find(|format| format.detect(data))
+}
+
+/// Saves start with this, followed by the length of the header and the header itself
+/// as JSON. The header can then be read without decoding the world after it.
+const SAVE_MAGIC: &[u8; 8] = b"ROGUESAV";
+
+/// Lays out a save: the header first, then the components in `format`.
+pub fn encode_save(
+    header: &SaveHeader,
+    components: &SavedComponents,
+    format: &dyn SaveFormat,
+) -> Result<Vec<u8>, SaveError> {
+    let header = serde_json::to_vec(header).map_err(|err| SaveError::Write(err.to_string()))?;
+    let mut data = SAVE_MAGIC.to_vec();
+    data.extend_from_slice(&(header.len() as u32).to_le_bytes());
+    data.extend_from_slice(&header);
+    data.extend(format.encode(components)?);
+    Ok(data)
+}
+
+/// Splits a save into its header and its encoded components, or `None` if it's from
+/// before the header was kept apart.
+pub fn split_save(data: &[u8]) -> Option<Result<(SaveHeader, &[u8]), SaveError>> {
+    let rest = data.strip_prefix(SAVE_MAGIC)?;
+    let cut_short = || SaveError::Corrupt("the header is cut short".to_string());
+    Some((|| {
+        let length = rest.get(..4).ok_or_else(cut_short)?;
+        let length = u32::from_le_bytes(length.try_into().unwrap()) as usize;
+        let header = rest.get(4..4 + length).ok_or_else(cut_short)?;
+        let header = serde_json::from_slice(header)
+            .map_err(|err| SaveError::Corrupt(format!("header: {}", err)))?;
+        Ok((header, &rest[4 + length..]))
+    })())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::component_registry::register_components;
+    use crate::saveload_system::{SaveSummary, SAVE_FORMAT_VERSION};
+    use crate::{Name, Position, SerializeMe};
+    use specs::prelude::*;
+    use specs::saveload::{MarkedBuilder, SimpleMarker, SimpleMarkerAllocator};
+
+    const ENCODINGS: [SaveEncoding; 3] = [
+        SaveEncoding::CompressedBinary,
+        SaveEncoding::Binary,
+        SaveEncoding::Json,
+    ];
+
+    fn empty_world() -> World {
+        let mut ecs = World::new();
+        register_components(&mut ecs);
+        ecs.insert(SimpleMarkerAllocator::<SerializeMe>::new());
+        ecs
+    }
+
+    fn example_world() -> World {
+        let mut ecs = empty_world();
+        ecs.create_entity()
+            .with(Position { x: 3, y: 4 })
+            .with(Name {
+                name: "Goblin".to_string(),
+            })
+            .marked::<SimpleMarker<SerializeMe>>()
+            .build();
+        ecs.create_entity()
+            .with(Position { x: 7, y: 1 })
+            .marked::<SimpleMarker<SerializeMe>>()
+            .build();
+        ecs
+    }
+
+    fn example_header() -> SaveHeader {
+        SaveHeader {
+            format_version: SAVE_FORMAT_VERSION,
+            game_version: "0.1.0".to_string(),
+            saved_at: 1_700_000_000,
+            summary: Some(SaveSummary {
+                depth: 2,
+                turns: 150,
+                hp: 20,
+                max_hp: 30,
+            }),
+            slot: Some(1),
+        }
+    }
+
+    fn positions(ecs: &World) -> Vec<(i32, i32)> {
+        let mut positions: Vec<(i32, i32)> = ecs
+            .read_storage::<Position>()
+            .join()
+            .map(|pos| (pos.x, pos.y))
+            .collect();
+        positions.sort();
+        positions
+    }
+
+    #[test]
+    fn every_format_round_trips() {
+        let saved = example_world();
+        for encoding in ENCODINGS.iter() {
+            let data = encode_save(
+                &example_header(),
+                &SavedComponents(&saved),
+                format_for(*encoding),
+            )
+            .unwrap();
+            let (header, body) = split_save(&data).unwrap().unwrap();
+            assert_eq!(header.saved_at, 1_700_000_000);
+
+            let loaded = empty_world();
+            detect_format(body)
+                .unwrap()
+                .decode(body, ComponentLoader(&loaded))
+                .unwrap();
+            assert_eq!(positions(&loaded), positions(&saved));
+            assert_eq!(loaded.read_storage::<Name>().join().count(), 1);
+        }
+    }
+
+    #[test]
+    fn formats_are_told_apart() {
+        let saved = example_world();
+        for encoding in ENCODINGS.iter() {
+            let data = format_for(*encoding)
+                .encode(&SavedComponents(&saved))
+                .unwrap();
+            let matching = FORMATS.iter().filter(|f| f.detect(&data)).count();
+            assert_eq!(matching, 1);
+        }
+    }
+
+    #[test]
+    fn headers_read_without_the_world() {
+        let mut data = encode_save(
+            &example_header(),
+            &SavedComponents(&example_world()),
+            format_for(SaveEncoding::CompressedBinary),
+        )
+        .unwrap();
+        // Whatever comes after the header is never looked at
+        let length = data.len();
+        data.truncate(length - 10);
+        let (header, _) = split_save(&data).unwrap().unwrap();
+        assert_eq!(header.summary.unwrap().turns, 150);
+    }
+}
@@ -0,0 +1,189 @@
+use std::fs;
+use std::io;
+use std::io::Write;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+/// Somewhere saves can be kept. Saves are named by relative paths such as
+/// `slot1.sav` or `quarantine/slot1-1700000000.sav`.
+pub trait SaveStorage: Send + Sync {
+    fn read(&self, name: &str) -> io::Result<Vec<u8>>;
+    /// Replaces `name` with `data` all at once, so that a crash part way through
+    /// never leaves it half-written.
+    fn write(&self, name: &str, data: &[u8]) -> io::Result<()>;
+    fn exists(&self, name: &str) -> bool;
+    fn rename(&self, from: &str, to: &str) -> io::Result<()>;
+    fn remove(&self, name: &str) -> io::Result<()>;
+    /// Where `name` can be found, for telling the player.
+    fn location(&self, name: &str) -> String;
+
+    fn copy(&self, from: &str, to: &str) -> io::Result<()> {
+        self.write(to, &self.read(from)?)
+    }
+}
+
+/// The storage saves are read from and written to.
+#[derive(Clone)]
+pub struct SaveStore {
+    pub storage: Arc<dyn SaveStorage>,
+}
+
+impl SaveStore {
+    pub fn new<S: SaveStorage + 'static>(storage: S) -> SaveStore {
+        SaveStore {
+            storage: Arc::new(storage),
+        }
+    }
+}
+
+/// Seconds since the Unix epoch, for dating saves, morgue files and the hall of fame.
+/// The web build can't read the system clock, so what it writes is left undated.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn now() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_secs())
+}
+
+#[cfg(target_arch = "wasm32")]
+pub fn now() -> u64 {
+    0
+}
+
+/// Formats a time from `now` as local time, or None if it's undated. The web build
+/// can't look up the local time zone, so it shows UTC instead.
+pub fn format_time(secs: u64, format: &str) -> Option<String> {
+    use chrono::TimeZone;
+    if secs == 0 {
+        return None;
+    }
+    #[cfg(not(target_arch = "wasm32"))]
+    let zone = chrono::Local;
+    #[cfg(target_arch = "wasm32")]
+    let zone = chrono::Utc;
+    zone.timestamp_opt(secs as i64, 0)
+        .single()
+        .map(|time| time.format(format).to_string())
+}
+
+/// Keeps saves as files in a directory.
+pub struct FileStorage {
+    dir: PathBuf,
+}
+
+impl FileStorage {
+    pub fn new(dir: PathBuf) -> FileStorage {
+        FileStorage { dir }
+    }
+
+    /// The platform's per-user data directory, or beside the game if there isn't one.
+    pub fn in_data_dir() -> FileStorage {
+        FileStorage::new(
+            dirs::data_dir()
+                .map(|dir| dir.join("roguelike"))
+                .unwrap_or_else(|| PathBuf::from("."))
+                .join("saves"),
+        )
+    }
+
+    /// The path to `name`, creating the directory it goes in.
+    fn prepare(&self, name: &str) -> io::Result<PathBuf> {
+        let path = self.dir.join(name);
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        Ok(path)
+    }
+}
+
+impl SaveStorage for FileStorage {
+    fn read(&self, name: &str) -> io::Result<Vec<u8>> {
+        fs::read(self.dir.join(name))
+    }
+
+    /// Writes beside the old file and then swaps it in.
+    fn write(&self, name: &str, data: &[u8]) -> io::Result<()> {
+        let path = self.prepare(name)?;
+        let temp_path = path.with_extension("tmp");
+        {
+            let mut file = fs::File::create(&temp_path)?;
+            file.write_all(data)?;
+            file.sync_all()?;
+        }
+        fs::rename(temp_path, path)
+    }
+
+    fn exists(&self, name: &str) -> bool {
+        self.dir.join(name).exists()
+    }
+
+    fn rename(&self, from: &str, to: &str) -> io::Result<()> {
+        let to = self.prepare(to)?;
+        fs::rename(self.dir.join(from), to)
+    }
+
+    fn remove(&self, name: &str) -> io::Result<()> {
+        fs::remove_file(self.dir.join(name))
+    }
+
+    fn location(&self, name: &str) -> String {
+        self.dir.join(name).display().to_string()
+    }
+}
+
+/// Keeps saves in memory, for tests and for builds with nowhere to write files.
+/// They're gone once the game closes.
+#[cfg(any(test, target_arch = "wasm32"))]
+#[derive(Default)]
+pub struct MemoryStorage {
+    files: std::sync::Mutex<std::collections::HashMap<String, Vec<u8>>>,
+}
+
+#[cfg(any(test, target_arch = "wasm32"))]
+impl MemoryStorage {
+    fn files(&self) -> std::sync::MutexGuard<'_, std::collections::HashMap<String, Vec<u8>>> {
+        self.files.lock().expect("Save storage poisoned")
+    }
+}
+
+#[cfg(any(test, target_arch = "wasm32"))]
+fn not_found(name: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::NotFound, format!("{} not found", name))
+}
+
+#[cfg(any(test, target_arch = "wasm32"))]
+impl SaveStorage for MemoryStorage {
+    fn read(&self, name: &str) -> io::Result<Vec<u8>> {
+        self.files()
+            .get(name)
+            .cloned()
+            .ok_or_else(|| not_found(name))
+    }
+
+    fn write(&self, name: &str, data: &[u8]) -> io::Result<()> {
+        self.files().insert(name.to_string(), data.to_vec());
+        Ok(())
+    }
+
+    fn exists(&self, name: &str) -> bool {
+        self.files().contains_key(name)
+    }
+
+    fn rename(&self, from: &str, to: &str) -> io::Result<()> {
+        let mut files = self.files();
+        let data = files.remove(from).ok_or_else(|| not_found(from))?;
+        files.insert(to.to_string(), data);
+        Ok(())
+    }
+
+    fn remove(&self, name: &str) -> io::Result<()> {
+        self.files()
+            .remove(name)
+            .map(|_| ())
+            .ok_or_else(|| not_found(name))
+    }
+
+    fn location(&self, name: &str) -> String {
+        name.to_string()
+    }
+}
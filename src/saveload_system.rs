@@ -1,115 +1,831 @@
+use super::component_registry::register_components;
 use super::components::*;
+use super::config::GameConfig;
+use super::save_format::{detect_format, encode_save, format_for, split_save, SaveFormat};
+use super::save_storage::{self, SaveStorage, SaveStore};
+use serde::de::{DeserializeSeed, Deserializer, Error as _, IgnoredAny, MapAccess, Visitor};
+use serde::ser::{SerializeMap, Serializer};
+use serde::{Deserialize, Serialize};
+use serde_json::Value as JsonValue;
 use specs::error::NoError;
 use specs::prelude::*;
 use specs::saveload::{
-    DeserializeComponents, MarkedBuilder, SerializeComponents, SimpleMarker, SimpleMarkerAllocator,
+    ConvertSaveload, DeserializeComponents, MarkedBuilder, SerializeComponents, SimpleMarker,
+    SimpleMarkerAllocator,
 };
+use std::fmt;
 use std::fs;
-use std::fs::File;
+use std::marker::PhantomData;
 use std::path::Path;
+use std::sync::Arc;
+use std::thread::JoinHandle;
 
-macro_rules! serialize_individually {
-    ($ecs:expr, $ser:expr, $data:expr, $( $type:ty),*) => {
-        $(
+/// Serialized component storages, keyed by component name.
+pub type Components = serde_json::Map<String, JsonValue>;
+
+/// Each migration upgrades a save from the format version matching its index to the
+/// next one. Changing how a saved component looks means adding one here.
+const MIGRATIONS: [fn(&mut Components); 6] = [
+    migrate_legacy_components,
+    add_turn_counter,
+    name_equipped_fields,
+    add_run_stats,
+    add_deepest_level,
+    add_item_kinds,
+];
+
+/// The format this version of the game writes.
+pub const SAVE_FORMAT_VERSION: u32 = MIGRATIONS.len() as u32;
+
+/// How many save slots the player can choose between.
+pub const SAVE_SLOTS: usize = 3;
+
+/// The game saves itself here from time to time, apart from the player's own slots.
+pub const AUTOSAVE_SLOT: usize = SAVE_SLOTS;
+
+/// Written at the top of every save, so a save can be checked before anything in it
+/// is trusted.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct SaveHeader {
+    pub format_version: u32,
+    pub game_version: String,
+    /// Seconds since the Unix epoch.
+    pub saved_at: u64,
+    #[serde(default)]
+    pub summary: Option<SaveSummary>,
+    /// The slot the run belongs to. An autosave records it, so that a resumed run
+    /// still deletes its own save when it ends.
+    #[serde(default)]
+    pub slot: Option<usize>,
+}
+
+/// What the slot picker shows about a save, so it needn't load the whole world.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct SaveSummary {
+    pub depth: i32,
+    pub turns: i32,
+    pub hp: i32,
+    pub max_hp: i32,
+}
+
+/// The slot the game in progress was last saved to or loaded from. Dying deletes
+/// that save, so loading a save doesn't need to.
+#[derive(Default)]
+pub struct CurrentSave {
+    pub slot: Option<usize>,
+}
+
+/// The headers of the save slots, read once when the slot picker opens rather than
+/// on every frame it's drawn.
+#[derive(Default)]
+pub struct SlotHeaders {
+    pub headers: Vec<Option<SaveHeader>>,
+}
+
+impl SlotHeaders {
+    /// The header of the save in `slot`, or None if the slot is empty.
+    pub fn get(&self, slot: usize) -> Option<&SaveHeader> {
+        self.headers.get(slot).and_then(|header| header.as_ref())
+    }
+}
+
+/// How saves were laid out before the header was kept apart from the world: one
+/// document holding both.
+#[derive(Serialize, Deserialize)]
+struct OldSaveFile {
+    header: SaveHeader,
+    components: Components,
+}
+
+/// The components of a save that's been read, still encoded if it's from this
+/// version, or as plain values if it had to be upgraded.
+enum SaveBody<'a> {
+    Encoded(&'static dyn SaveFormat, &'a [u8]),
+    Upgraded(Components),
+}
+
+#[derive(Debug)]
+pub enum SaveError {
+    /// The save couldn't be read from storage.
+    Read(String),
+    /// The save couldn't be written to storage.
+    Write(String),
+    /// Written by a newer version of the game than this one.
+    TooNew { header: SaveHeader },
+    /// Not a save this game knows how to read.
+    Unrecognised,
+    /// A component in the save couldn't be read back.
+    Corrupt(String),
+    /// An unreadable save was moved aside so it can't get in the way again.
+    Quarantined {
+        cause: Box<SaveError>,
+        moved_to: String,
+        backup_restored: bool,
+    },
+}
+
+impl fmt::Display for SaveError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SaveError::Read(err) => write!(f, "Unable to read the save: {}", err),
+            SaveError::Write(err) => write!(f, "Unable to write the save: {}", err),
+            SaveError::TooNew { header } => write!(
+                f,
+                "This save was made by version {} of the game (save format {}), which is newer than this one (save format {}).",
+                header.game_version, header.format_version, SAVE_FORMAT_VERSION
+            ),
+            SaveError::Unrecognised => write!(f, "The save file is not in a format this game recognises."),
+            SaveError::Corrupt(err) => write!(f, "The save file is damaged ({}).", err),
+            SaveError::Quarantined {
+                cause,
+                moved_to,
+                backup_restored,
+            } => {
+                write!(f, "{} It has been moved to {}.", cause, moved_to)?;
+                if *backup_restored {
+                    write!(f, " The previous save in this slot has been restored.")?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+fn storage(ecs: &World) -> Arc<dyn SaveStorage> {
+    ecs.fetch::<SaveStore>().storage.clone()
+}
+
+fn slot_name(slot: usize) -> String {
+    if slot == AUTOSAVE_SLOT {
+        "autosave".to_string()
+    } else {
+        format!("slot{}", slot + 1)
+    }
+}
+
+/// Saves may be binary or JSON depending on `save_encoding`, so the extension
+/// doesn't name either.
+fn slot_file(slot: usize) -> String {
+    format!("{}.sav", slot_name(slot))
+}
+
+/// The previous save in a slot, kept in case the latest one turns out to be damaged.
+fn backup_file(slot: usize) -> String {
+    format!("{}.bak", slot_name(slot))
+}
+
+/// Each component storage is written as the entities that have one, paired with
+/// their markers.
+struct SavedStorage<'a, T>(&'a World, PhantomData<T>);
+
+impl<T> Serialize for SavedStorage<'_, T>
+where
+    T: Component + ConvertSaveload<SimpleMarker<SerializeMe>, Error = NoError>,
+{
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
         SerializeComponents::<NoError, SimpleMarker<SerializeMe>>::serialize(
-            &( $ecs.read_storage::<$type>(), ),
-            &$data.0,
-            &$data.1,
-            &mut $ser,
+            &(self.0.read_storage::<T>(),),
+            &self.0.entities(),
+            &self.0.read_storage::<SimpleMarker<SerializeMe>>(),
+            serializer,
         )
-        .unwrap();
+    }
+}
+
+macro_rules! serialize_individually {
+    ($ecs:expr, $map:expr, $( $type:ty),*) => {
+        $(
+        $map.serialize_entry(stringify!($type), &SavedStorage::<$type>($ecs, PhantomData))?;
         )*
     };
 }
 
-#[cfg(target_arch = "wasm32")]
-pub fn save_game(_ecs: &mut World) {}
+/// Every saved component storage in the world, keyed by component name. Written
+/// straight into whichever format the save is in.
+pub struct SavedComponents<'a>(pub &'a World);
+
+impl Serialize for SavedComponents<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut map = serializer.serialize_map(Some(saved_components!(count_each!())))?;
+        saved_components!(serialize_individually!(self.0, map,));
+        map.end()
+    }
+}
+
+pub fn save_game(ecs: &mut World, slot: usize) -> Result<(), SaveError> {
+    let data = snapshot(ecs, slot)?;
+    write_save(&*storage(ecs), slot, &data)
+}
+
+/// Captures everything that needs saving, laid out ready to be written to `slot`.
+fn snapshot(ecs: &mut World, slot: usize) -> Result<Vec<u8>, SaveError> {
+    let format = format_for(ecs.fetch::<GameConfig>().save_encoding);
 
-#[cfg(not(target_arch = "wasm32"))]
-pub fn save_game(ecs: &mut World) {
     // Create helper
     let map_copy = ecs.get_mut::<super::map::Map>().unwrap().clone();
     let gamelog_copy = ecs.get_mut::<super::game_log::GameLog>().unwrap().clone();
+    let turn_counter = *ecs.fetch::<super::game_log::TurnCounter>();
+    let run_stats = ecs.get_mut::<super::game_log::RunStats>().unwrap().clone();
     let savehelper = ecs
         .create_entity()
         .with(SerializationHelper {
             map: map_copy,
             game_log: gamelog_copy,
+            turn_counter,
+            run_stats,
         })
         .marked::<SimpleMarker<SerializeMe>>()
         .build();
 
-    // Actually serialize
-    {
-        let data = (
-            ecs.entities(),
-            ecs.read_storage::<SimpleMarker<SerializeMe>>(),
-        );
+    let summary = {
+        let player_entity = *ecs.fetch::<Entity>();
+        let stats = ecs.read_storage::<CombatStats>();
+        let stats = stats.get(player_entity);
+        SaveSummary {
+            depth: ecs.fetch::<super::map::Map>().depth,
+            turns: turn_counter.turns,
+            hp: stats.map_or(0, |s| s.hp),
+            max_hp: stats.map_or(0, |s| s.max_hp),
+        }
+    };
+    let header = SaveHeader {
+        format_version: SAVE_FORMAT_VERSION,
+        game_version: env!("CARGO_PKG_VERSION").to_string(),
+        saved_at: save_storage::now(),
+        summary: Some(summary),
+        slot: if slot == AUTOSAVE_SLOT {
+            ecs.fetch::<CurrentSave>().slot
+        } else {
+            Some(slot)
+        },
+    };
+    let data = encode_save(&header, &SavedComponents(ecs), format);
 
-        let writer = File::create("./savegame.json").unwrap();
-        let mut serializer = serde_json::Serializer::new(writer);
-        serialize_individually!(
-            ecs,
-            serializer,
-            data,
-            Position,
-            Renderable,
-            Player,
-            Viewshed,
-            Monster,
-            Name,
-            BlocksTile,
-            CombatStats,
-            SufferDamage,
-            WantsToMelee,
-            Item,
-            Consumable,
-            Ranged,
-            InflictsDamage,
-            AreaOfEffect,
-            Confusion,
-            ProvidesHealing,
-            InBackpack,
-            WantsToPickupItem,
-            WantsToUseItem,
-            WantsToDropItem,
-            SerializationHelper,
-            Equippable,
-            Equipped,
-            MeleePowerBonus,
-            DefenseBonus,
-            WantsToRemoveItem,
-            ParticleLifetime,
-            HungerClock,
-            ProvidesFood,
-            MagicMapper
+    // Clean up
+    ecs.delete_entity(savehelper).expect("Crash on cleanup");
+    data
+}
+
+/// Writes the save to its slot, keeping the old one as a backup.
+fn write_save(storage: &dyn SaveStorage, slot: usize, data: &[u8]) -> Result<(), SaveError> {
+    let write_error = |err: std::io::Error| SaveError::Write(err.to_string());
+
+    if storage.exists(&slot_file(slot)) {
+        storage
+            .copy(&slot_file(slot), &backup_file(slot))
+            .map_err(write_error)?;
+    }
+    storage.write(&slot_file(slot), data).map_err(write_error)
+}
+
+/// Writes autosaves on a background thread, so play doesn't pause while they're
+/// written out.
+#[derive(Default)]
+pub struct Autosaver {
+    writing: Option<JoinHandle<Result<(), SaveError>>>,
+}
+
+/// The web build has no threads to write on, so it autosaves straight away.
+#[cfg(target_arch = "wasm32")]
+pub fn autosave(ecs: &mut World) -> Result<(), SaveError> {
+    let data = snapshot(ecs, AUTOSAVE_SLOT)?;
+    write_save(&*storage(ecs), AUTOSAVE_SLOT, &data)
+}
+
+/// Starts writing an autosave. Errors are from capturing the world or from the
+/// previous autosave, since this one finishes later.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn autosave(ecs: &mut World) -> Result<(), SaveError> {
+    let previous = finish_autosave(ecs);
+    let data = snapshot(ecs, AUTOSAVE_SLOT)?;
+    let storage = storage(ecs);
+    ecs.fetch_mut::<Autosaver>().writing = Some(std::thread::spawn(move || {
+        write_save(&*storage, AUTOSAVE_SLOT, &data)
+    }));
+    previous
+}
+
+/// Waits for any autosave still being written.
+pub fn finish_autosave(ecs: &mut World) -> Result<(), SaveError> {
+    match ecs.fetch_mut::<Autosaver>().writing.take() {
+        Some(writing) => writing
+            .join()
+            .unwrap_or_else(|_| Err(SaveError::Write("the autosave was interrupted".to_string()))),
+        None => Ok(()),
+    }
+}
+
+/// Removes the autosave once the run it belongs to has ended properly. One that's
+/// still around at startup means the game didn't get the chance.
+pub fn discard_autosave(ecs: &mut World) -> Result<(), SaveError> {
+    finish_autosave(ecs).ok();
+    delete_save(ecs, AUTOSAVE_SLOT)
+}
+
+pub fn does_autosave_exist(ecs: &World) -> bool {
+    storage(ecs).exists(&slot_file(AUTOSAVE_SLOT))
+}
+
+pub fn does_save_exist(ecs: &World) -> bool {
+    let storage = storage(ecs);
+    (0..SAVE_SLOTS).any(|slot| storage.exists(&slot_file(slot)))
+}
+
+/// The header of the save in `slot`, or None if the slot is empty.
+pub fn read_header(ecs: &World, slot: usize) -> Option<SaveHeader> {
+    let data = storage(ecs).read(&slot_file(slot)).ok()?;
+    let header = match split_save(&data) {
+        Some(split) => split.ok().map(|(header, _)| header),
+        // Older saves kept the header inside the document, so the whole of it is read
+        None => read_old_save(&data).ok().map(|save| save.header),
+    };
+    Some(header.unwrap_or_else(legacy_header))
+}
+
+/// Rereads the header of every save slot, for the slot picker.
+pub fn refresh_slot_headers(ecs: &World) {
+    let headers = (0..SAVE_SLOTS).map(|slot| read_header(ecs, slot)).collect();
+    ecs.fetch_mut::<SlotHeaders>().headers = headers;
+}
+
+/// Earlier versions kept a single save beside the game. Moves it into the first
+/// free slot so it isn't lost, and renames slots from when every save was JSON.
+pub fn adopt_old_save(ecs: &World) {
+    let storage = storage(ecs);
+
+    // Slots used to be named for the JSON they held
+    for slot in 0..=AUTOSAVE_SLOT {
+        let old_slot = format!("{}.json", slot_name(slot));
+        if storage.exists(&old_slot) && !storage.exists(&slot_file(slot)) {
+            storage.rename(&old_slot, &slot_file(slot)).ok();
+        }
+    }
+
+    let old_save = Path::new("./savegame.json");
+    if !old_save.exists() {
+        return;
+    }
+    if let Some(slot) = (0..SAVE_SLOTS).find(|slot| !storage.exists(&slot_file(*slot))) {
+        let adopted = fs::read(old_save).and_then(|data| storage.write(&slot_file(slot), &data));
+        if adopted.is_ok() {
+            fs::remove_file(old_save).ok();
+        }
+    }
+}
+
+/// Saves from before the header existed were a plain run of the storages of every
+/// component the game had then, in this order. Components were only ever added to
+/// the end, so older saves of that kind hold some prefix of it.
+const LEGACY_COMPONENTS: [&str; 31] = [
+    "Position",
+    "Renderable",
+    "Player",
+    "Viewshed",
+    "Monster",
+    "Name",
+    "BlocksTile",
+    "CombatStats",
+    "SufferDamage",
+    "WantsToMelee",
+    "Item",
+    "Consumable",
+    "Ranged",
+    "InflictsDamage",
+    "AreaOfEffect",
+    "Confusion",
+    "ProvidesHealing",
+    "InBackpack",
+    "WantsToPickupItem",
+    "WantsToUseItem",
+    "WantsToDropItem",
+    "SerializationHelper",
+    "Equippable",
+    "Equipped",
+    "MeleePowerBonus",
+    "DefenseBonus",
+    "WantsToRemoveItem",
+    "ParticleLifetime",
+    "HungerClock",
+    "ProvidesFood",
+    "MagicMapper",
+];
+
+fn legacy_header() -> SaveHeader {
+    SaveHeader {
+        format_version: 0,
+        game_version: "unknown".to_string(),
+        saved_at: 0,
+        summary: None,
+        slot: None,
+    }
+}
+
+/// Reads a headerless save as format version 0.
+fn read_legacy_save(data: &str) -> Result<OldSaveFile, SaveError> {
+    let blocks: Vec<JsonValue> = serde_json::Deserializer::from_str(data)
+        .into_iter::<JsonValue>()
+        .collect::<Result<_, _>>()
+        .map_err(|_| SaveError::Unrecognised)?;
+    if blocks.is_empty() || blocks.len() > LEGACY_COMPONENTS.len() {
+        return Err(SaveError::Unrecognised);
+    }
+
+    let components = LEGACY_COMPONENTS
+        .iter()
+        .map(|name| name.to_string())
+        .zip(blocks)
+        .collect();
+    Ok(OldSaveFile {
+        header: legacy_header(),
+        components,
+    })
+}
+
+/// Reads a save from before the header was kept apart, in any format.
+fn read_old_save(data: &[u8]) -> Result<OldSaveFile, SaveError> {
+    let format = detect_format(data).ok_or(SaveError::Unrecognised)?;
+    let save = format.decode_value(data).and_then(|value| {
+        serde_json::from_value::<OldSaveFile>(value).map_err(|_| SaveError::Unrecognised)
+    });
+    match save {
+        // Saves from before the header existed were JSON, just not in one document
+        Err(SaveError::Unrecognised) => {
+            read_legacy_save(std::str::from_utf8(data).map_err(|_| SaveError::Unrecognised)?)
+        }
+        save => save,
+    }
+}
+
+/// Parses a save of any known format, upgrading it if it's from an older version.
+fn read_save(data: &[u8]) -> Result<(SaveHeader, SaveBody<'_>), SaveError> {
+    let (mut header, mut components) = match split_save(data) {
+        Some(split) => {
+            let (header, body) = split?;
+            let format = detect_format(body).ok_or(SaveError::Unrecognised)?;
+            if header.format_version == SAVE_FORMAT_VERSION {
+                return Ok((header, SaveBody::Encoded(format, body)));
+            }
+            match format.decode_value(body)? {
+                JsonValue::Object(components) => (header, components),
+                _ => return Err(SaveError::Corrupt("the components are missing".to_string())),
+            }
+        }
+        None => {
+            let save = read_old_save(data)?;
+            (save.header, save.components)
+        }
+    };
+
+    if header.format_version > SAVE_FORMAT_VERSION {
+        return Err(SaveError::TooNew { header });
+    }
+    for migrate in MIGRATIONS.iter().skip(header.format_version as usize) {
+        migrate(&mut components);
+    }
+    header.format_version = SAVE_FORMAT_VERSION;
+    Ok((header, SaveBody::Upgraded(components)))
+}
+
+/// Every saved `component` value, paired with the marker of the entity it belongs to.
+fn saved_values<'a>(
+    components: &'a mut Components,
+    component: &str,
+) -> impl Iterator<Item = &'a mut serde_json::Map<String, JsonValue>> {
+    components
+        .get_mut(component)
+        .and_then(|block| block.as_array_mut())
+        .into_iter()
+        .flat_map(|entries| entries.iter_mut())
+        .filter_map(|entry| entry.get_mut("components")?.get_mut(0)?.as_object_mut())
+}
+
+/// Gives the entity with `marker` a `component` if it has none.
+fn add_if_missing(
+    components: &mut Components,
+    component: &str,
+    marker: &JsonValue,
+    value: JsonValue,
+) {
+    let block = components
+        .entry(component.to_string())
+        .or_insert_with(|| JsonValue::Array(Vec::new()));
+    if let Some(entries) = block.as_array_mut() {
+        if !entries
+            .iter()
+            .any(|entry| entry.get("marker") == Some(marker))
+        {
+            entries.push(serde_json::json!({ "marker": marker, "components": [value] }));
+        }
+    }
+}
+
+/// Version 0 to 1: hunger counts nutrition rather than turns, food says how filling it
+/// is, and the player gained components as features were added.
+fn migrate_legacy_components(components: &mut Components) {
+    for clock in saved_values(components, "HungerClock") {
+        if let Some(duration) = clock.remove("duration") {
+            let base = match clock.get("state").and_then(|s| s.as_str()) {
+                Some("WellFed") => 400,
+                Some("Normal") => 200,
+                Some("Hungry") => 0,
+                _ => -1,
+            };
+            let nutrition = i64::max(0, base + duration.as_i64().unwrap_or(0));
+            clock.insert("nutrition".to_string(), JsonValue::from(nutrition));
+        }
+    }
+    for food in saved_values(components, "ProvidesFood") {
+        food.entry("nutrition")
+            .or_insert_with(|| JsonValue::from(400));
+    }
+
+    let players: Vec<JsonValue> = components
+        .get("Player")
+        .and_then(|block| block.as_array())
+        .into_iter()
+        .flatten()
+        .filter_map(|entry| entry.get("marker").cloned())
+        .collect();
+    for marker in players.iter() {
+        add_if_missing(
+            components,
+            "Encumbrance",
+            marker,
+            serde_json::json!({ "carried": 0.0, "capacity": 50.0 }),
+        );
+        add_if_missing(
+            components,
+            "Mana",
+            marker,
+            serde_json::json!({ "current": 10, "max": 10, "regen_timer": 0 }),
+        );
+        add_if_missing(
+            components,
+            "KnownRecipes",
+            marker,
+            serde_json::json!({ "recipes": [] }),
+        );
+        add_if_missing(
+            components,
+            "Purse",
+            marker,
+            serde_json::json!({ "gold": 0 }),
         );
     }
+}
 
-    // Clean up
-    ecs.delete_entity(savehelper).expect("Crash on cleanup");
+/// Version 1 to 2: the turn counter is saved alongside the map and log.
+fn add_turn_counter(components: &mut Components) {
+    for helper in saved_values(components, "SerializationHelper") {
+        helper
+            .entry("turn_counter")
+            .or_insert_with(|| serde_json::json!({ "turns": 0 }));
+    }
 }
 
-pub fn does_save_exist() -> bool {
-    Path::new("./savegame.json").exists()
+/// Version 2 to 3: equipment is saved with named fields, like everything else.
+fn name_equipped_fields(components: &mut Components) {
+    let equipped = components
+        .get_mut("Equipped")
+        .and_then(|block| block.as_array_mut())
+        .into_iter()
+        .flatten()
+        .filter_map(|entry| entry.get_mut("components")?.get_mut(0));
+    for equipped in equipped {
+        if let Some([owner, slot]) = equipped.as_array().map(|fields| fields.as_slice()) {
+            *equipped = serde_json::json!({ "owner": owner, "slot": slot });
+        }
+    }
 }
 
-macro_rules! deserialize_individually {
-    ($ecs:expr, $de:expr, $data:expr, $( $type:ty),*) => {
-        $(
+/// Version 3 to 4: kills are counted for the death recap. Earlier runs start from
+/// none.
+fn add_run_stats(components: &mut Components) {
+    for helper in saved_values(components, "SerializationHelper") {
+        helper
+            .entry("run_stats")
+            .or_insert_with(|| serde_json::json!({ "kills": {}, "cause_of_death": null }));
+    }
+}
+
+/// Version 4 to 5: the deepest level reached is remembered for the hall of fame.
+/// Earlier runs are taken to have got no further than where they were saved.
+fn add_deepest_level(components: &mut Components) {
+    for helper in saved_values(components, "SerializationHelper") {
+        let depth = helper
+            .get("map")
+            .and_then(|map| map.get("depth"))
+            .cloned()
+            .unwrap_or_else(|| serde_json::json!(1));
+        if let Some(run_stats) = helper
+            .get_mut("run_stats")
+            .and_then(|stats| stats.as_object_mut())
+        {
+            run_stats.entry("deepest").or_insert(depth);
+        }
+    }
+}
+
+/// Each saved `component`, keyed by the marker of the entity it belongs to.
+fn values_by_marker(components: &Components, component: &str) -> Vec<(JsonValue, JsonValue)> {
+    components
+        .get(component)
+        .and_then(|block| block.as_array())
+        .into_iter()
+        .flatten()
+        .filter_map(|entry| {
+            let value = entry.get("components")?.get(0)?;
+            Some((entry.get("marker")?.clone(), value.clone()))
+        })
+        .collect()
+}
+
+/// Version 5 to 6: recipes go by what an item is rather than what it's called now.
+/// Earlier items are taken to be what their names say, once rot and enchantment are
+/// set aside.
+fn add_item_kinds(components: &mut Components) {
+    let items: Vec<JsonValue> = values_by_marker(components, "Item")
+        .into_iter()
+        .map(|(marker, _)| marker)
+        .collect();
+    let magic = values_by_marker(components, "MagicItem");
+    for (marker, name) in values_by_marker(components, "Name") {
+        if !items.contains(&marker) {
+            continue;
+        }
+        let name = name.get("name").and_then(|n| n.as_str()).unwrap_or("");
+        let mut kind = name.strip_prefix("Rotten ").unwrap_or(name);
+        // Enchanted names read "+2 Flaming Longsword of Warding"
+        if let Some((_, magic)) = magic.iter().find(|(m, _)| *m == marker) {
+            kind = kind.split_once(' ').map_or(kind, |(_, rest)| rest);
+            if !magic["prefix"].is_null() {
+                kind = kind.split_once(' ').map_or(kind, |(_, rest)| rest);
+            }
+            if !magic["suffix"].is_null() {
+                kind = kind.rsplit_once(" of ").map_or(kind, |(base, _)| base);
+            }
+        }
+        let kind = serde_json::json!({ "name": kind });
+        add_if_missing(components, "ItemKind", &marker, kind);
+    }
+}
+
+/// Reads one component storage into the world.
+struct StorageLoader<'a, T>(&'a World, PhantomData<T>);
+
+impl<'de, T> DeserializeSeed<'de> for StorageLoader<'_, T>
+where
+    T: Component + ConvertSaveload<SimpleMarker<SerializeMe>, Error = NoError>,
+{
+    type Value = ();
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<(), D::Error> {
         DeserializeComponents::<NoError, _>::deserialize(
-            &mut ( &mut $ecs.write_storage::<$type>(), ),
-            &mut $data.0, // entities
-            &mut $data.1, // marker
-            &mut $data.2, // allocater
-            &mut $de,
+            &mut (self.0.write_storage::<T>(),),
+            &self.0.entities(),
+            &mut self.0.write_storage::<SimpleMarker<SerializeMe>>(),
+            &mut self
+                .0
+                .write_resource::<SimpleMarkerAllocator<SerializeMe>>(),
+            deserializer,
         )
-        .unwrap();
-        )*
+    }
+}
+
+macro_rules! deserialize_individually {
+    ($ecs:expr, $name:expr, $map:expr, $( $type:ty),*) => {
+        match $name.as_str() {
+            $(
+            stringify!($type) => $map
+                .next_value_seed(StorageLoader::<$type>($ecs, PhantomData))
+                .map_err(|err| A::Error::custom(format!("{}: {}", stringify!($type), err)))?,
+            )*
+            // Saved by an older version, before the component was dropped
+            _ => {
+                $map.next_value::<IgnoredAny>()?;
+            }
+        }
     };
 }
 
-pub fn load_game(ecs: &mut World) {
+/// Reads saved component storages straight into the world, whichever format they
+/// were written in.
+#[derive(Clone, Copy)]
+pub struct ComponentLoader<'a>(pub &'a World);
+
+impl<'de> DeserializeSeed<'de> for ComponentLoader<'_> {
+    type Value = ();
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<(), D::Error> {
+        deserializer.deserialize_map(self)
+    }
+}
+
+impl<'de> Visitor<'de> for ComponentLoader<'_> {
+    type Value = ();
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "a map of component storages")
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<(), A::Error> {
+        while let Some(name) = map.next_key::<String>()? {
+            saved_components!(deserialize_individually!(self.0, name, map,));
+        }
+        Ok(())
+    }
+}
+
+/// Loads the save, upgrading it first if it's from an older version. The world is
+/// left untouched if the save can't be understood, and a damaged save is moved aside
+/// in favour of its backup.
+pub fn load_game(ecs: &mut World, slot: usize) -> Result<(), SaveError> {
+    load_slot(ecs, slot).map(|_| ())
+}
+
+/// Picks up the run that was autosaved, as the save it came from if it had one.
+pub fn resume_autosave(ecs: &mut World) -> Result<(), SaveError> {
+    let header = load_slot(ecs, AUTOSAVE_SLOT)?;
+    ecs.fetch_mut::<CurrentSave>().slot = header.slot;
+    Ok(())
+}
+
+fn load_slot(ecs: &mut World, slot: usize) -> Result<SaveHeader, SaveError> {
+    let storage = storage(ecs);
+    let data = storage
+        .read(&slot_file(slot))
+        .map_err(|e| SaveError::Read(e.to_string()))?;
+    let loaded =
+        read_save(&data).and_then(|(header, body)| restore_world(ecs, &body).map(|_| header));
+    match loaded {
+        Err(err @ SaveError::Unrecognised) | Err(err @ SaveError::Corrupt(_)) => {
+            Err(quarantine(&*storage, slot, err))
+        }
+        result => result,
+    }
+}
+
+/// Moves a damaged save out of its slot, restoring the backup if there is one.
+fn quarantine(storage: &dyn SaveStorage, slot: usize, cause: SaveError) -> SaveError {
+    let now = save_storage::now();
+    let moved_to = (1..)
+        .map(|copy| {
+            let name = if copy == 1 {
+                format!("{}-{}.sav", slot_name(slot), now)
+            } else {
+                format!("{}-{}-{}.sav", slot_name(slot), now, copy)
+            };
+            format!("quarantine/{}", name)
+        })
+        .find(|name| !storage.exists(name))
+        .unwrap();
+    if storage.rename(&slot_file(slot), &moved_to).is_err() {
+        return cause;
+    }
+
+    let backup_restored = storage.rename(&backup_file(slot), &slot_file(slot)).is_ok();
+    SaveError::Quarantined {
+        cause: Box::new(cause),
+        moved_to: storage.location(&moved_to),
+        backup_restored,
+    }
+}
+
+fn deserialize_components(ecs: &World, body: &SaveBody) -> Result<(), SaveError> {
+    let loader = ComponentLoader(ecs);
+    match body {
+        SaveBody::Encoded(format, data) => format.decode(data, loader),
+        SaveBody::Upgraded(components) => loader
+            .deserialize(JsonValue::Object(components.clone()))
+            .map_err(|err| SaveError::Corrupt(err.to_string())),
+    }
+}
+
+/// Loads the components into a scratch world, so a damaged save is found out before
+/// the real world is touched.
+fn check_components(body: &SaveBody) -> Result<(), SaveError> {
+    let mut scratch = World::new();
+    register_components(&mut scratch);
+    scratch.insert(SimpleMarkerAllocator::<SerializeMe>::new());
+    deserialize_components(&scratch, body)?;
+
+    if scratch
+        .read_storage::<SerializationHelper>()
+        .join()
+        .next()
+        .is_none()
+    {
+        return Err(SaveError::Corrupt("the map is missing".to_string()));
+    }
+    if scratch.read_storage::<Player>().join().next().is_none() {
+        return Err(SaveError::Corrupt("the player is missing".to_string()));
+    }
+    Ok(())
+}
+
+fn restore_world(ecs: &mut World, body: &SaveBody) -> Result<(), SaveError> {
+    check_components(body)?;
+
     {
         // Delete everything
         let mut to_delete = Vec::new();
@@ -121,53 +837,7 @@ pub fn load_game(ecs: &mut World) {
         }
     }
 
-    let data = fs::read_to_string("./savegame.json").unwrap();
-    let mut de = serde_json::Deserializer::from_str(&data);
-
-    {
-        let mut d = (
-            &mut ecs.entities(),
-            &mut ecs.write_storage::<SimpleMarker<SerializeMe>>(),
-            &mut ecs.write_resource::<SimpleMarkerAllocator<SerializeMe>>(),
-        );
-
-        deserialize_individually!(
-            ecs,
-            de,
-            d,
-            Position,
-            Renderable,
-            Player,
-            Viewshed,
-            Monster,
-            Name,
-            BlocksTile,
-            CombatStats,
-            SufferDamage,
-            WantsToMelee,
-            Item,
-            Consumable,
-            Ranged,
-            InflictsDamage,
-            AreaOfEffect,
-            Confusion,
-            ProvidesHealing,
-            InBackpack,
-            WantsToPickupItem,
-            WantsToUseItem,
-            WantsToDropItem,
-            SerializationHelper,
-            Equippable,
-            Equipped,
-            MeleePowerBonus,
-            DefenseBonus,
-            WantsToRemoveItem,
-            ParticleLifetime,
-            HungerClock,
-            ProvidesFood,
-            MagicMapper
-        );
-    }
+    deserialize_components(ecs, body)?;
 
     let mut resources_only: Vec<Entity> = Vec::new();
     {
@@ -186,6 +856,9 @@ pub fn load_game(ecs: &mut World) {
                 .entries
                 .insert(0, "Loaded game from save".to_string());
 
+            *ecs.write_resource::<super::game_log::TurnCounter>() = h.turn_counter;
+            *ecs.write_resource::<super::game_log::RunStats>() = h.run_stats.clone();
+
             resources_only.push(e);
         }
         for (e, _p, pos) in (&entities, &player, &position).join() {
@@ -198,10 +871,284 @@ pub fn load_game(ecs: &mut World) {
     for e in resources_only {
         ecs.delete_entity(e).expect("Unable to delete helper");
     }
+    Ok(())
+}
+
+/// Removes the save in `slot` along with its backup.
+pub fn delete_save(ecs: &World, slot: usize) -> Result<(), SaveError> {
+    let storage = storage(ecs);
+    for name in [slot_file(slot), backup_file(slot)].iter() {
+        if storage.exists(name) {
+            storage
+                .remove(name)
+                .map_err(|err| SaveError::Write(err.to_string()))?;
+        }
+    }
+    Ok(())
 }
 
-pub fn delete_save() {
-    if Path::new("./savegame.json").exists() {
-        std::fs::remove_file("./savegame.json").expect("Unable to delete file");
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::component_registry::register_components;
+    use crate::config::SaveEncoding;
+    use crate::game_log::{GameLog, RunStats, TurnCounter};
+    use crate::save_storage::MemoryStorage;
+
+    /// The components of an old save, once upgraded.
+    fn upgraded(data: &str) -> Components {
+        match read_save(data.as_bytes()).unwrap() {
+            (_, SaveBody::Upgraded(components)) => components,
+            _ => panic!("expected the save to be upgraded"),
+        }
+    }
+
+    fn legacy_save() -> String {
+        let mut blocks = vec!["[]".to_string(); 31];
+        let index = |name| LEGACY_COMPONENTS.iter().position(|c| *c == name).unwrap();
+        blocks[index("Player")] = r#"[{"marker":[1],"components":[{}]}]"#.to_string();
+        blocks[index("HungerClock")] =
+            r#"[{"marker":[1],"components":[{"state":"Normal","duration":50}]}]"#.to_string();
+        blocks[index("ProvidesFood")] = r#"[{"marker":[2],"components":[{}]}]"#.to_string();
+        blocks.join("")
+    }
+
+    #[test]
+    fn legacy_saves_are_migrated() {
+        let (header, _) = read_save(legacy_save().as_bytes()).unwrap();
+        assert_eq!(header.format_version, SAVE_FORMAT_VERSION);
+
+        let mut components = upgraded(&legacy_save());
+        let clock = saved_values(&mut components, "HungerClock").next().unwrap();
+        assert_eq!(clock.get("nutrition"), Some(&JsonValue::from(250)));
+        assert!(clock.get("duration").is_none());
+        let food = saved_values(&mut components, "ProvidesFood")
+            .next()
+            .unwrap();
+        assert!(food.get("nutrition").is_some());
+        assert_eq!(saved_values(&mut components, "Purse").count(), 1);
+    }
+
+    #[test]
+    fn migrating_twice_changes_nothing() {
+        let mut components = upgraded(&legacy_save());
+        let migrated = components.clone();
+        migrate_legacy_components(&mut components);
+        assert_eq!(components, migrated);
+    }
+
+    #[test]
+    fn turn_counter_is_added_to_old_saves() {
+        let data = r#"{"header":{"format_version":1,"game_version":"0.1.0","saved_at":0},"components":{"SerializationHelper":[{"marker":[1],"components":[{}]}]}}"#;
+        let mut components = upgraded(data);
+        let helper = saved_values(&mut components, "SerializationHelper")
+            .next()
+            .unwrap();
+        assert_eq!(
+            helper.get("turn_counter"),
+            Some(&serde_json::json!({ "turns": 0 }))
+        );
+    }
+
+    #[test]
+    fn equipment_fields_are_named() {
+        let data = r#"{"header":{"format_version":2,"game_version":"0.1.0","saved_at":0},"components":{"Equipped":[{"marker":[3],"components":[[[1],"Melee"]]}]}}"#;
+        let mut components = upgraded(data);
+        let equipped = saved_values(&mut components, "Equipped").next().unwrap();
+        assert_eq!(equipped.get("owner"), Some(&serde_json::json!([1])));
+        assert_eq!(equipped.get("slot"), Some(&serde_json::json!("Melee")));
+    }
+
+    #[test]
+    fn kill_counts_are_added_to_old_saves() {
+        let data = r#"{"header":{"format_version":3,"game_version":"0.1.0","saved_at":0},"components":{"SerializationHelper":[{"marker":[1],"components":[{}]}]}}"#;
+        let mut components = upgraded(data);
+        let helper = saved_values(&mut components, "SerializationHelper")
+            .next()
+            .unwrap();
+        assert_eq!(
+            helper.get("run_stats").and_then(|stats| stats.get("kills")),
+            Some(&serde_json::json!({}))
+        );
+    }
+
+    #[test]
+    fn deepest_level_is_added_to_old_saves() {
+        let data = r#"{"header":{"format_version":4,"game_version":"0.1.0","saved_at":0},"components":{"SerializationHelper":[{"marker":[1],"components":[{"map":{"depth":3},"run_stats":{"kills":{},"cause_of_death":null}}]}]}}"#;
+        let mut components = upgraded(data);
+        let helper = saved_values(&mut components, "SerializationHelper")
+            .next()
+            .unwrap();
+        assert_eq!(
+            helper
+                .get("run_stats")
+                .and_then(|stats| stats.get("deepest")),
+            Some(&serde_json::json!(3))
+        );
+    }
+
+    #[test]
+    fn items_are_given_kinds() {
+        let data = r#"{"header":{"format_version":5,"game_version":"0.1.0","saved_at":0},"components":{
+            "Item":[{"marker":[1],"components":[{}]},{"marker":[2],"components":[{}]}],
+            "Name":[{"marker":[1],"components":[{"name":"Rotten Rations"}]},
+                    {"marker":[2],"components":[{"name":"-1 Flaming Longsword of Might"}]},
+                    {"marker":[3],"components":[{"name":"Goblin"}]}],
+            "MagicItem":[{"marker":[2],"components":[{"enchantment":-1,"prefix":"Flaming","suffix":"Might"}]}]}}"#;
+        let mut components = upgraded(data);
+        let kinds: Vec<JsonValue> = saved_values(&mut components, "ItemKind")
+            .map(|kind| kind["name"].clone())
+            .collect();
+        assert_eq!(
+            kinds,
+            vec![serde_json::json!("Rations"), serde_json::json!("Longsword")]
+        );
+    }
+
+    #[test]
+    fn newer_saves_are_rejected() {
+        let data = format!(
+            r#"{{"header":{{"format_version":{},"game_version":"99.0.0","saved_at":0}},"components":{{}}}}"#,
+            SAVE_FORMAT_VERSION + 1
+        );
+        match read_save(data.as_bytes()) {
+            Err(SaveError::TooNew { header }) => assert_eq!(header.game_version, "99.0.0"),
+            _ => panic!("expected a too-new save to be rejected"),
+        }
+    }
+
+    #[test]
+    fn garbage_is_unrecognised() {
+        assert!(matches!(
+            read_save(b"not a save"),
+            Err(SaveError::Unrecognised)
+        ));
+    }
+
+    /// A world holding just a player, saving to memory.
+    fn test_world(encoding: SaveEncoding) -> World {
+        let mut ecs = World::new();
+        register_components(&mut ecs);
+        ecs.insert(SimpleMarkerAllocator::<SerializeMe>::new());
+        ecs.insert(SaveStore::new(MemoryStorage::default()));
+        ecs.insert(GameConfig {
+            save_encoding: encoding,
+            ..GameConfig::default()
+        });
+        ecs.insert(crate::map::Map::default());
+        ecs.insert(GameLog {
+            entries: Vec::new(),
+        });
+        ecs.insert(TurnCounter { turns: 42 });
+        ecs.insert(RunStats::default());
+        ecs.insert(rltk::Point::new(0, 0));
+        ecs.insert(CurrentSave::default());
+        let player = ecs
+            .create_entity()
+            .with(Player {})
+            .with(Position { x: 3, y: 4 })
+            .with(Name {
+                name: "Player".to_string(),
+            })
+            .with(CombatStats {
+                max_hp: 20,
+                hp: 12,
+                defense: 1,
+                power: 5,
+            })
+            .marked::<SimpleMarker<SerializeMe>>()
+            .build();
+        ecs.insert(player);
+        ecs
+    }
+
+    fn player_position(ecs: &World) -> (i32, i32) {
+        let player = *ecs.fetch::<Entity>();
+        let pos = ecs.read_storage::<Position>().get(player).cloned().unwrap();
+        (pos.x, pos.y)
+    }
+
+    fn move_player(ecs: &mut World, x: i32, y: i32) {
+        let player = *ecs.fetch::<Entity>();
+        ecs.write_storage::<Position>()
+            .insert(player, Position { x, y })
+            .unwrap();
+    }
+
+    #[test]
+    fn saves_round_trip_without_disk() {
+        for encoding in [
+            SaveEncoding::CompressedBinary,
+            SaveEncoding::Binary,
+            SaveEncoding::Json,
+        ]
+        .iter()
+        {
+            let mut ecs = test_world(*encoding);
+            ecs.write_resource::<RunStats>()
+                .kills
+                .insert("Orc".to_string(), 2);
+            save_game(&mut ecs, 0).unwrap();
+            move_player(&mut ecs, 10, 10);
+            ecs.write_resource::<TurnCounter>().turns = 0;
+            *ecs.write_resource::<RunStats>() = RunStats::default();
+
+            load_game(&mut ecs, 0).unwrap();
+            assert_eq!(player_position(&ecs), (3, 4));
+            assert_eq!(*ecs.fetch::<rltk::Point>(), rltk::Point::new(3, 4));
+            assert_eq!(ecs.fetch::<TurnCounter>().turns, 42);
+            assert_eq!(ecs.fetch::<RunStats>().kills.get("Orc"), Some(&2));
+            let summary = read_header(&ecs, 0).unwrap().summary.unwrap();
+            assert_eq!((summary.hp, summary.max_hp, summary.turns), (12, 20, 42));
+        }
+    }
+
+    #[test]
+    fn damaged_saves_fall_back_to_the_backup() {
+        let mut ecs = test_world(SaveEncoding::CompressedBinary);
+        save_game(&mut ecs, 1).unwrap();
+        move_player(&mut ecs, 10, 10);
+        save_game(&mut ecs, 1).unwrap();
+        storage(&ecs).write(&slot_file(1), b"garbage").unwrap();
+
+        match load_game(&mut ecs, 1) {
+            Err(SaveError::Quarantined {
+                backup_restored, ..
+            }) => assert!(backup_restored),
+            _ => panic!("expected the damaged save to be quarantined"),
+        }
+        load_game(&mut ecs, 1).unwrap();
+        assert_eq!(player_position(&ecs), (3, 4));
+    }
+
+    #[test]
+    fn damaged_saves_leave_the_world_alone() {
+        let mut ecs = test_world(SaveEncoding::Json);
+        let save = String::from_utf8(snapshot(&mut ecs, 0).unwrap()).unwrap();
+        // Components before this one load fine, so the damage is found part way through
+        let save = save.replace(r#""max_hp": 20"#, r#""max_hp": "twenty""#);
+        write_save(&*storage(&ecs), 0, save.as_bytes()).unwrap();
+        move_player(&mut ecs, 10, 10);
+        let player = *ecs.fetch::<Entity>();
+
+        assert!(matches!(
+            load_game(&mut ecs, 0),
+            Err(SaveError::Quarantined { .. })
+        ));
+        assert!(ecs.is_alive(player));
+        assert_eq!(player_position(&ecs), (10, 10));
+        assert_eq!(ecs.read_storage::<Position>().join().count(), 1);
+    }
+
+    #[test]
+    fn deleting_a_save_removes_its_backup() {
+        let mut ecs = test_world(SaveEncoding::Json);
+        save_game(&mut ecs, 2).unwrap();
+        save_game(&mut ecs, 2).unwrap();
+        assert!(storage(&ecs).exists(&backup_file(2)));
+
+        delete_save(&ecs, 2).unwrap();
+        assert!(!does_save_exist(&ecs));
+        assert!(!storage(&ecs).exists(&backup_file(2)));
     }
 }
@@ -0,0 +1,105 @@
+use super::{
+    game_log::GameLog, ForSale, InBackpack, Name, Position, Purse, Value, WantsToPickupItem,
+};
+use specs::prelude::*;
+
+/// What a shopkeeper pays for an item: half its value, but never nothing.
+pub fn sell_price(value: &Value) -> i32 {
+    i32::max(1, value.gold / 2)
+}
+
+fn item_name(ecs: &World, item: Entity) -> String {
+    ecs.read_storage::<Name>()
+        .get(item)
+        .map_or("item".to_string(), |name| name.name.to_string())
+}
+
+/// Pays the shopkeeper for `item` and picks it up. Returns false (and explains why) if
+/// the player can't afford it.
+pub fn buy(ecs: &mut World, shopkeeper: Entity, item: Entity) -> bool {
+    let player_entity = *ecs.fetch::<Entity>();
+    let name = item_name(ecs, item);
+    let price = ecs.read_storage::<Value>().get(item).map_or(0, |v| v.gold);
+
+    {
+        let mut purses = ecs.write_storage::<Purse>();
+        match purses.get_mut(player_entity) {
+            Some(purse) if purse.gold >= price => purse.gold -= price,
+            _ => {
+                ecs.fetch_mut::<GameLog>()
+                    .entries
+                    .insert(0, format!("You can't afford the {}.", name));
+                return false;
+            }
+        }
+        if let Some(keeper_purse) = purses.get_mut(shopkeeper) {
+            keeper_purse.gold += price;
+        }
+    }
+
+    ecs.write_storage::<ForSale>().remove(item);
+    ecs.write_storage::<WantsToPickupItem>()
+        .insert(
+            player_entity,
+            WantsToPickupItem {
+                collected_by: player_entity,
+                item,
+            },
+        )
+        .expect("Unable to insert want to pickup");
+
+    ecs.fetch_mut::<GameLog>()
+        .entries
+        .insert(0, format!("You buy the {} for {} gold.", name, price));
+    true
+}
+
+/// Hands `item` over to the shopkeeper, who puts it up for sale. Returns false (and
+/// explains why) if they won't or can't buy it.
+pub fn sell(ecs: &mut World, shopkeeper: Entity, item: Entity) -> bool {
+    let player_entity = *ecs.fetch::<Entity>();
+    let name = item_name(ecs, item);
+
+    let price = match ecs.read_storage::<Value>().get(item) {
+        Some(value) => sell_price(value),
+        None => {
+            ecs.fetch_mut::<GameLog>()
+                .entries
+                .insert(0, format!("The shopkeeper has no use for the {}.", name));
+            return false;
+        }
+    };
+
+    {
+        let mut purses = ecs.write_storage::<Purse>();
+        match purses.get_mut(shopkeeper) {
+            Some(keeper_purse) if keeper_purse.gold >= price => keeper_purse.gold -= price,
+            _ => {
+                ecs.fetch_mut::<GameLog>()
+                    .entries
+                    .insert(0, "The shopkeeper can't afford that.".to_string());
+                return false;
+            }
+        }
+        if let Some(purse) = purses.get_mut(player_entity) {
+            purse.gold += price;
+        }
+    }
+
+    // Sold goods are kept behind the counter, under the shopkeeper
+    let keeper_pos = ecs.read_storage::<Position>().get(shopkeeper).cloned();
+    ecs.write_storage::<InBackpack>().remove(item);
+    if let Some(pos) = keeper_pos {
+        ecs.write_storage::<Position>()
+            .insert(item, pos)
+            .expect("Unable to insert position");
+    }
+    ecs.write_storage::<ForSale>()
+        .insert(item, ForSale { shopkeeper })
+        .expect("Unable to insert for sale");
+
+    ecs.fetch_mut::<GameLog>()
+        .entries
+        .insert(0, format!("You sell the {} for {} gold.", name, price));
+    true
+}
@@ -1,12 +1,17 @@
-use super::{components::*, rect::Rect, RandomTable, Viewshed, MAP_WIDTH};
+use super::{
+    components::*, crafting::RecipeBook, magic_items, rect::Rect, spell_system, Map, RandomTable,
+    Viewshed, MAP_WIDTH,
+};
 use rltk::{RandomNumberGenerator, RGB};
+use serde::{Deserialize, Serialize};
 use specs::prelude::*;
 use specs::saveload::{MarkedBuilder, SimpleMarker};
 use std::collections::HashMap;
 
 /// Spawns the player and returns his/her entity object.
 pub fn player(ecs: &mut World, player_x: i32, player_y: i32) -> Entity {
-    ecs.create_entity()
+    let player = ecs
+        .create_entity()
         .with(Position {
             x: player_x,
             y: player_y,
@@ -35,25 +40,233 @@ pub fn player(ecs: &mut World, player_x: i32, player_y: i32) -> Entity {
         .marked::<SimpleMarker<SerializeMe>>()
         .with(HungerClock {
             state: HungerState::WellFed,
-            duration: 20,
+            nutrition: 450,
         })
-        .build()
+        .with(Encumbrance {
+            carried: 0.0,
+            capacity: 50.0,
+        })
+        .with(Mana {
+            current: 10,
+            max: 10,
+            regen_timer: 0,
+        })
+        .with(KnownRecipes::default())
+        .with(Purse { gold: 0 })
+        .build();
+
+    spell_system::build_spell(ecs.create_entity(), SpellKind::MagicMissile, player);
+
+    // Every adventurer sets out with a faithful dog
+    dog(ecs, player_x + 1, player_y);
+
+    player
 }
 
-fn room_table(map_depth: i32) -> RandomTable {
+fn dog(ecs: &mut World, x: i32, y: i32) {
+    ecs.create_entity()
+        .with(Position { x, y })
+        .with(Renderable {
+            glyph: rltk::to_cp437('d'),
+            fg: RGB::named(rltk::BURLYWOOD),
+            bg: RGB::named(rltk::BLACK),
+            render_order: 1,
+        })
+        .with(Viewshed {
+            visible_tiles: Vec::new(),
+            range: 8,
+            dirty: true,
+        })
+        .with(Ally {})
+        .with(Name {
+            name: "Dog".to_string(),
+        })
+        .with(BlocksTile {})
+        .with(CombatStats {
+            max_hp: 14,
+            hp: 14,
+            defense: 1,
+            power: 4,
+        })
+        .marked::<SimpleMarker<SerializeMe>>()
+        .build();
+}
+
+/// Declares `Spawn` along with a list of every variant, so tests that go through
+/// them all can't miss a new one.
+macro_rules! spawns {
+    ($( $variant:ident ),*) => {
+        /// Everything the spawner knows how to place on a level. Recipe files name these
+        /// variants as their results.
+        #[derive(PartialEq, Copy, Clone, Debug, Serialize, Deserialize)]
+        pub enum Spawn {
+            $( $variant ),*
+        }
+
+        #[cfg(test)]
+        impl Spawn {
+            pub const ALL: &'static [Spawn] = &[$( Spawn::$variant ),*];
+        }
+    };
+}
+
+spawns!(
+    Goblin,
+    Orc,
+    Shade,
+    Imp,
+    Necromancer,
+    Hermit,
+    Pilgrim,
+    Brigand,
+    Chest,
+    TeleportTrap,
+    AlchemyStation,
+    HealthPotion,
+    PotionOfStrength,
+    PotionOfTelepathy,
+    PotionOfDetectItems,
+    PotionOfSpeed,
+    PotionOfLevitation,
+    PotionOfSeeInvisible,
+    FireballScroll,
+    ConfusionScroll,
+    MagicMissileScroll,
+    MagicMappingScroll,
+    RechargingScroll,
+    TeleportScroll,
+    BlinkScroll,
+    LevelTeleportScroll,
+    CharmScroll,
+    Dagger,
+    Shield,
+    Longsword,
+    TowerShield,
+    Rations,
+    Bread,
+    HeartyStew,
+    WandOfMagicMissile,
+    StaffOfFireball,
+    StaffOfConfusion,
+    Sack,
+    SpellbookOfFireball,
+    SpellbookOfConfusion,
+    SpellbookOfHealing,
+    AlchemistsNotes,
+    Gold
+);
+
+fn potion_table() -> RandomTable<Spawn> {
+    RandomTable::new()
+        .add(Spawn::HealthPotion, 8)
+        .add(Spawn::PotionOfStrength, 2)
+        .add(Spawn::PotionOfDetectItems, 2)
+        .add(Spawn::PotionOfLevitation, 1)
+        .add(Spawn::PotionOfTelepathy, 2)
+        .min_depth(2)
+        .add(Spawn::PotionOfSpeed, 1)
+        .min_depth(2)
+        .add(Spawn::PotionOfSeeInvisible, 2)
+        .min_depth(3)
+}
+
+fn scroll_table(map_depth: i32) -> RandomTable<Spawn> {
+    RandomTable::new()
+        .add(Spawn::FireballScroll, 2 + map_depth)
+        .add(Spawn::ConfusionScroll, 2 + map_depth)
+        .add(Spawn::MagicMissileScroll, 4)
+        .add(Spawn::MagicMappingScroll, 2)
+        .add(Spawn::RechargingScroll, map_depth)
+        .min_depth(2)
+        .add(Spawn::TeleportScroll, 3)
+        .add(Spawn::BlinkScroll, 2)
+        .min_depth(2)
+        .add(Spawn::LevelTeleportScroll, 1)
+        .min_depth(3)
+        .add(Spawn::CharmScroll, 2)
+}
+
+fn wand_table(map_depth: i32) -> RandomTable<Spawn> {
+    RandomTable::new()
+        .add(Spawn::WandOfMagicMissile, map_depth)
+        .add(Spawn::StaffOfConfusion, map_depth)
+        .min_depth(2)
+        .add(Spawn::StaffOfFireball, map_depth)
+        .min_depth(3)
+}
+
+fn spellbook_table() -> RandomTable<Spawn> {
+    RandomTable::new()
+        .add(Spawn::SpellbookOfHealing, 3)
+        .add(Spawn::SpellbookOfConfusion, 2)
+        .min_depth(2)
+        .add(Spawn::SpellbookOfFireball, 1)
+        .min_depth(4)
+}
+
+fn equipment_table(map_depth: i32) -> RandomTable<Spawn> {
+    RandomTable::new()
+        .add(Spawn::Dagger, 3)
+        .add(Spawn::Shield, 3)
+        .add(Spawn::Longsword, map_depth)
+        .min_depth(2)
+        .add(Spawn::TowerShield, map_depth)
+        .min_depth(2)
+}
+
+fn room_table(map_depth: i32) -> RandomTable<Spawn> {
+    RandomTable::new()
+        .add(Spawn::Goblin, 10)
+        .add(Spawn::Orc, 1 + map_depth)
+        .add(Spawn::Shade, 2)
+        .depths(4, 5)
+        .add(Spawn::Shade, 4)
+        .depths(6, 7)
+        .add(Spawn::Shade, 6)
+        .min_depth(8)
+        .add(Spawn::Imp, map_depth)
+        .min_depth(3)
+        .add(Spawn::Necromancer, 2)
+        .depths(3, 4)
+        .add(Spawn::Necromancer, 4)
+        .depths(5, 6)
+        .add(Spawn::Necromancer, 6)
+        .min_depth(7)
+        .add(Spawn::TeleportTrap, 2)
+        .min_depth(2)
+        .add(Spawn::Pilgrim, 1)
+        .add(Spawn::Hermit, 1)
+        .min_depth(2)
+        .add(Spawn::Brigand, 1)
+        .min_depth(2)
+        .add_table(potion_table(), 7)
+        .add_table(scroll_table(map_depth), 10 + 2 * map_depth)
+        .add_table(wand_table(map_depth), 1)
+        .depths(1, 1)
+        .add_table(wand_table(map_depth), 4)
+        .depths(2, 3)
+        .add_table(wand_table(map_depth), 8)
+        .min_depth(4)
+        .add_table(equipment_table(map_depth), 4 + 2 * map_depth)
+        .add_table(spellbook_table(), 1 + map_depth / 2)
+        .add(Spawn::AlchemistsNotes, 2)
+        .add(Spawn::Rations, 10)
+        .add(Spawn::Bread, 4)
+        .add(Spawn::Chest, 2)
+        .add(Spawn::AlchemyStation, 1)
+        .add(Spawn::Sack, 1)
+        .add(Spawn::Gold, 6)
+        .guarantee(Spawn::Rations, 1, i32::MAX)
+}
+
+fn shop_table(map_depth: i32) -> RandomTable<Spawn> {
     RandomTable::new()
-        .add("Goblin", 10)
-        .add("Orc", 1 + map_depth)
-        .add("Health Potion", 7)
-        .add("Fireball Scroll", 2 + map_depth)
-        .add("Confusion Scroll", 2 + map_depth)
-        .add("Magic Missile Scroll", 4)
-        .add("Dagger", 3)
-        .add("Shield", 3)
-        .add("Longsword", map_depth - 1)
-        .add("Tower Shield", map_depth - 1)
-        .add("Rations", 10)
-        .add("Magic Mapping Scroll", 2)
+        .add_table(potion_table(), 6)
+        .add_table(scroll_table(map_depth), 6)
+        .add_table(wand_table(map_depth), 2 + map_depth)
+        .add_table(equipment_table(map_depth), 6)
+        .add(Spawn::Rations, 3)
+        .add(Spawn::Sack, 1)
 }
 
 fn orc(ecs: &mut World, x: i32, y: i32) {
@@ -64,7 +277,201 @@ fn goblin(ecs: &mut World, x: i32, y: i32) {
     monster(ecs, x, y, rltk::to_cp437('g'), "Goblin");
 }
 
-fn monster<S: ToString>(ecs: &mut World, x: i32, y: i32, glyph: u8, name: S) {
+/// An invisible monster, only seen with a potion of see invisible.
+fn shade(ecs: &mut World, x: i32, y: i32) {
+    let shade = monster(ecs, x, y, rltk::to_cp437('s'), "Shade");
+    ecs.write_storage::<Invisible>()
+        .insert(shade, Invisible {})
+        .expect("Unable to insert invisibility");
+    ecs.write_storage::<Undead>()
+        .insert(shade, Undead {})
+        .expect("Unable to insert undead");
+}
+
+/// Raises nearby corpses to fight for it.
+fn necromancer(ecs: &mut World, x: i32, y: i32) {
+    let necromancer = monster(ecs, x, y, rltk::to_cp437('n'), "Necromancer");
+    ecs.write_storage::<Necromancer>()
+        .insert(necromancer, Necromancer {})
+        .expect("Unable to insert necromancer");
+}
+
+/// The ghost of a character who died on this level in an earlier run. It has come
+/// back for revenge.
+pub fn ghost(ecs: &mut World, x: i32, y: i32) -> Entity {
+    let ghost = monster(ecs, x, y, rltk::to_cp437('G'), "Ghost");
+    ecs.write_storage::<Undead>()
+        .insert(ghost, Undead {})
+        .expect("Unable to insert undead");
+    ghost
+}
+
+/// A corpse risen by a necromancer. Takes a builder so it can be created lazily from
+/// inside a system.
+pub fn zombie<B: Builder + MarkedBuilder>(builder: B, x: i32, y: i32, species: &str) -> Entity {
+    builder
+        .with(Position { x, y })
+        .with(Renderable {
+            glyph: rltk::to_cp437('z'),
+            fg: RGB::named(rltk::OLIVE),
+            bg: RGB::named(rltk::BLACK),
+            render_order: 1,
+        })
+        .with(Viewshed {
+            visible_tiles: Vec::new(),
+            range: 6,
+            dirty: true,
+        })
+        .with(Monster {})
+        .with(Undead {})
+        .with(Name {
+            name: format!("{} Zombie", species),
+        })
+        .with(BlocksTile {})
+        .with(CombatStats {
+            max_hp: 10,
+            hp: 10,
+            defense: 0,
+            power: 3,
+        })
+        .marked::<SimpleMarker<SerializeMe>>()
+        .build()
+}
+
+/// What eating a species' corpse does to you, beyond feeding you.
+fn corpse_effect(species: &str) -> Option<(StatusEffect, i32)> {
+    match species {
+        "Goblin" => Some((StatusEffect::Sick, 8)),
+        "Orc" => Some((StatusEffect::Strength, 20)),
+        "Imp" => Some((StatusEffect::Speed, 10)),
+        _ => None,
+    }
+}
+
+/// Leaves the remains of a dead `species` at the given position.
+pub fn corpse(ecs: &mut World, x: i32, y: i32, species: &str) -> Entity {
+    let mut builder = ecs
+        .create_entity()
+        .with(Position { x, y })
+        .with(Renderable {
+            glyph: rltk::to_cp437('%'),
+            fg: RGB::named(rltk::DARK_RED),
+            bg: RGB::named(rltk::BLACK),
+            render_order: 2,
+        })
+        .with(Name {
+            name: format!("{} Corpse", species),
+        })
+        .with(Item {})
+        .with(ItemClass {
+            category: ItemCategory::Food,
+        })
+        .with(Weight { lbs: 10.0 })
+        .with(ProvidesFood { nutrition: 150 })
+        .with(Perishable { freshness: 60 })
+        .with(Corpse {
+            species: species.to_string(),
+        })
+        .with(ItemKind {
+            name: format!("{} Corpse", species),
+        })
+        .with(Consumable {});
+    if let Some((effect, turns)) = corpse_effect(species) {
+        builder = builder.with(ProvidesStatus { effect, turns });
+    }
+    builder.marked::<SimpleMarker<SerializeMe>>().build()
+}
+
+/// A peaceful character who talks instead of fighting, using the named conversation
+/// from the dialogue file.
+fn npc(ecs: &mut World, x: i32, y: i32, name: &str, conversation: &str, fg: RGB) {
+    ecs.create_entity()
+        .with(Position { x, y })
+        .with(Renderable {
+            glyph: rltk::to_cp437('@'),
+            fg,
+            bg: RGB::named(rltk::BLACK),
+            render_order: 1,
+        })
+        .with(Viewshed {
+            visible_tiles: Vec::new(),
+            range: 8,
+            dirty: true,
+        })
+        .with(Npc {
+            conversation: conversation.to_string(),
+            start: None,
+        })
+        .with(Name {
+            name: name.to_string(),
+        })
+        .with(BlocksTile {})
+        .with(CombatStats {
+            max_hp: 16,
+            hp: 16,
+            defense: 1,
+            power: 4,
+        })
+        .marked::<SimpleMarker<SerializeMe>>()
+        .build();
+}
+
+/// Blinks to the player's side rather than walking.
+fn imp(ecs: &mut World, x: i32, y: i32) {
+    let imp = monster(ecs, x, y, rltk::to_cp437('i'), "Imp");
+    ecs.write_storage::<CastsBlink>()
+        .insert(imp, CastsBlink {})
+        .expect("Unable to insert blink");
+}
+
+fn teleport_trap(ecs: &mut World, x: i32, y: i32) {
+    ecs.create_entity()
+        .with(Position { x, y })
+        .with(Renderable {
+            glyph: rltk::to_cp437('^'),
+            fg: RGB::named(rltk::MAGENTA),
+            bg: RGB::named(rltk::BLACK),
+            render_order: 2,
+        })
+        .with(Name {
+            name: "Teleport Trap".to_string(),
+        })
+        .with(Trap {})
+        .with(Teleports {
+            kind: TeleportKind::Random,
+        })
+        .marked::<SimpleMarker<SerializeMe>>()
+        .build();
+}
+
+fn alchemy_station(ecs: &mut World, x: i32, y: i32) {
+    ecs.create_entity()
+        .with(Position { x, y })
+        .with(Renderable {
+            glyph: rltk::to_cp437('&'),
+            fg: RGB::named(rltk::LIGHT_GREEN),
+            bg: RGB::named(rltk::BLACK),
+            render_order: 2,
+        })
+        .with(Name {
+            name: "Alchemy Station".to_string(),
+        })
+        .with(CraftingStation {})
+        .marked::<SimpleMarker<SerializeMe>>()
+        .build();
+}
+
+fn monster<S: ToString>(ecs: &mut World, x: i32, y: i32, glyph: u8, name: S) -> Entity {
+    // Some monsters carry a few coins, dropped when they die
+    let gold = {
+        let mut rng = ecs.write_resource::<RandomNumberGenerator>();
+        if rng.roll_dice(1, 3) == 1 {
+            rng.roll_dice(1, 10)
+        } else {
+            0
+        }
+    };
+
     ecs.create_entity()
         .with(Position { x, y })
         .with(Renderable {
@@ -89,15 +496,114 @@ fn monster<S: ToString>(ecs: &mut World, x: i32, y: i32, glyph: u8, name: S) {
             defense: 1,
             power: 4,
         })
+        .with(Purse { gold })
         .marked::<SimpleMarker<SerializeMe>>()
-        .build();
+        .build()
+}
+
+/// Stands in the middle of a shop and trades with the player, until robbed.
+fn shopkeeper(ecs: &mut World, x: i32, y: i32, map_depth: i32) -> Entity {
+    ecs.create_entity()
+        .with(Position { x, y })
+        .with(Renderable {
+            glyph: rltk::to_cp437('@'),
+            fg: RGB::named(rltk::GOLD),
+            bg: RGB::named(rltk::BLACK),
+            render_order: 1,
+        })
+        .with(Viewshed {
+            visible_tiles: Vec::new(),
+            range: 8,
+            dirty: true,
+        })
+        .with(Shopkeeper {})
+        .with(Name {
+            name: "Shopkeeper".to_string(),
+        })
+        .with(BlocksTile {})
+        .with(CombatStats {
+            max_hp: 40,
+            hp: 40,
+            defense: 3,
+            power: 8,
+        })
+        .with(Purse {
+            gold: 100 + 50 * map_depth,
+        })
+        .marked::<SimpleMarker<SerializeMe>>()
+        .build()
+}
+
+/// A pile of coins.
+pub fn gold(ecs: &mut World, x: i32, y: i32, amount: i32) -> Entity {
+    ecs.create_entity()
+        .with(Position { x, y })
+        .with(Renderable {
+            glyph: rltk::to_cp437('$'),
+            fg: RGB::named(rltk::GOLD),
+            bg: RGB::named(rltk::BLACK),
+            render_order: 2,
+        })
+        .with(Name {
+            name: format!("{} Gold", amount),
+        })
+        .with(Item {})
+        .with(ItemClass {
+            category: ItemCategory::Gold,
+        })
+        .with(Gold { amount })
+        .marked::<SimpleMarker<SerializeMe>>()
+        .build()
 }
 
 const MAX_SPAWNS: i32 = 3;
 
+/// Chance in 100 that a level has a shop.
+const SHOP_CHANCE: i32 = 40;
+
+/// Populates every room but the first (where the player starts), sometimes turning one
+/// of them into a shop, then places the level's guaranteed spawns in other rooms.
+pub fn spawn_level(ecs: &mut World, map: &Map, map_depth: i32) {
+    let shop_room = {
+        let mut rng = ecs.write_resource::<RandomNumberGenerator>();
+        if map.rooms.len() > 2 && rng.roll_dice(1, 100) <= SHOP_CHANCE {
+            Some(rng.range(1, map.rooms.len() as i32) as usize)
+        } else {
+            None
+        }
+    };
+
+    for (i, room) in map.rooms.iter().enumerate().skip(1) {
+        if shop_room == Some(i) {
+            spawn_shop(ecs, room, map_depth);
+        } else {
+            spawn_room(ecs, room, map_depth);
+        }
+    }
+
+    // A level too small to have anywhere else gets them in the starting room
+    let mut eligible: Vec<usize> = (1..map.rooms.len())
+        .filter(|i| shop_room != Some(*i))
+        .collect();
+    if eligible.is_empty() {
+        eligible.push(0);
+    }
+    let guaranteed = room_table(map_depth).guaranteed(map_depth);
+    for spawn in guaranteed {
+        let (x, y);
+        {
+            let mut rng = ecs.write_resource::<RandomNumberGenerator>();
+            let room = &map.rooms[eligible[rng.range(0, eligible.len() as i32) as usize]];
+            x = room.x1 + rng.roll_dice(1, i32::abs(room.x2 - room.x1));
+            y = room.y1 + rng.roll_dice(1, i32::abs(room.y2 - room.y1));
+        }
+        spawn_entity(ecs, spawn, x, y, map_depth);
+    }
+}
+
 pub fn spawn_room(ecs: &mut World, room: &Rect, map_depth: i32) {
     let spawn_table = room_table(map_depth);
-    let mut spawn_points: HashMap<usize, String> = HashMap::new();
+    let mut spawn_points: HashMap<usize, Spawn> = HashMap::new();
 
     // Scope to keep the borrow checker happy
     {
@@ -112,7 +618,9 @@ pub fn spawn_room(ecs: &mut World, room: &Rect, map_depth: i32) {
                 let y = (room.y1 + rng.roll_dice(1, i32::abs(room.y2 - room.y1))) as usize;
                 let idx = (y * MAP_WIDTH) + x;
                 if !spawn_points.contains_key(&idx) {
-                    spawn_points.insert(idx, spawn_table.roll(&mut rng));
+                    if let Some(spawn) = spawn_table.roll(&mut rng, map_depth) {
+                        spawn_points.insert(idx, spawn);
+                    }
                     added = true;
                 } else {
                     tries += 1;
@@ -122,28 +630,306 @@ pub fn spawn_room(ecs: &mut World, room: &Rect, map_depth: i32) {
     }
 
     // Actually spawn the monsters
-    for spawn in spawn_points.iter() {
-        let x = (*spawn.0 % MAP_WIDTH) as i32;
-        let y = (*spawn.0 / MAP_WIDTH) as i32;
-
-        match spawn.1.as_ref() {
-            "Goblin" => goblin(ecs, x, y),
-            "Orc" => orc(ecs, x, y),
-            "Health Potion" => health_potion(ecs, x, y),
-            "Fireball Scroll" => fireball_scroll(ecs, x, y),
-            "Confusion Scroll" => confusion_scroll(ecs, x, y),
-            "Magic Missile Scroll" => magic_missile_scroll(ecs, x, y),
-            "Dagger" => dagger(ecs, x, y),
-            "Shield" => shield(ecs, x, y),
-            "Longsword" => longsword(ecs, x, y),
-            "Tower Shield" => tower_shield(ecs, x, y),
-            "Rations" => rations(ecs, x, y),
-            "Magic Mapping Scroll" => magic_mapping_scroll(ecs, x, y),
-            _ => {}
+    for (idx, spawn) in spawn_points.iter() {
+        let x = (*idx % MAP_WIDTH) as i32;
+        let y = (*idx / MAP_WIDTH) as i32;
+        spawn_entity(ecs, *spawn, x, y, map_depth);
+    }
+}
+
+/// Fills a room with stock for sale, with the shopkeeper standing in the middle.
+fn spawn_shop(ecs: &mut World, room: &Rect, map_depth: i32) {
+    let (keeper_x, keeper_y) = room.center();
+    let keeper = shopkeeper(ecs, keeper_x, keeper_y, map_depth);
+
+    let table = shop_table(map_depth);
+    let mut stock: HashMap<usize, Spawn> = HashMap::new();
+    {
+        let mut rng = ecs.write_resource::<RandomNumberGenerator>();
+        let num_items = rng.roll_dice(1, 4) + 2;
+        for _i in 0..num_items {
+            for _try in 0..20 {
+                let x = room.x1 + rng.roll_dice(1, i32::abs(room.x2 - room.x1));
+                let y = room.y1 + rng.roll_dice(1, i32::abs(room.y2 - room.y1));
+                let idx = (y as usize * MAP_WIDTH) + x as usize;
+                if (x, y) != (keeper_x, keeper_y) && !stock.contains_key(&idx) {
+                    if let Some(spawn) = table.roll(&mut rng, map_depth) {
+                        stock.insert(idx, spawn);
+                    }
+                    break;
+                }
+            }
         }
     }
+
+    for (idx, spawn) in stock.iter() {
+        let x = (*idx % MAP_WIDTH) as i32;
+        let y = (*idx / MAP_WIDTH) as i32;
+        if let Some(item) = spawn_item(ecs, *spawn, x, y, map_depth) {
+            ecs.write_storage::<ForSale>()
+                .insert(item, ForSale { shopkeeper: keeper })
+                .expect("Unable to insert for sale");
+        }
+    }
+}
+
+pub fn spawn_entity(ecs: &mut World, spawn: Spawn, x: i32, y: i32, map_depth: i32) {
+    match spawn {
+        Spawn::Goblin => goblin(ecs, x, y),
+        Spawn::Orc => orc(ecs, x, y),
+        Spawn::Shade => shade(ecs, x, y),
+        Spawn::Imp => imp(ecs, x, y),
+        Spawn::Necromancer => necromancer(ecs, x, y),
+        Spawn::Hermit => npc(ecs, x, y, "Hermit", "hermit", RGB::named(rltk::LIGHT_GREY)),
+        Spawn::Pilgrim => npc(ecs, x, y, "Pilgrim", "pilgrim", RGB::named(rltk::WHEAT)),
+        Spawn::Brigand => npc(
+            ecs,
+            x,
+            y,
+            "Brigand",
+            "brigand",
+            RGB::named(rltk::ORANGE_RED),
+        ),
+        Spawn::TeleportTrap => teleport_trap(ecs, x, y),
+        Spawn::AlchemyStation => alchemy_station(ecs, x, y),
+        Spawn::Chest => chest(ecs, x, y, map_depth),
+        item => {
+            spawn_item(ecs, item, x, y, map_depth);
+        }
+    }
+}
+
+/// Spawns an item at the given position, rolling magic properties for equipment.
+/// Returns `None` if `spawn` is not an item.
+pub fn spawn_item(ecs: &mut World, spawn: Spawn, x: i32, y: i32, map_depth: i32) -> Option<Entity> {
+    let item = match spawn {
+        Spawn::HealthPotion => health_potion(ecs, x, y),
+        Spawn::PotionOfStrength => status_potion(
+            ecs,
+            x,
+            y,
+            "Potion of Strength",
+            StatusEffect::Strength,
+            30,
+            RGB::named(rltk::RED),
+        ),
+        Spawn::PotionOfTelepathy => status_potion(
+            ecs,
+            x,
+            y,
+            "Potion of Telepathy",
+            StatusEffect::Telepathy,
+            40,
+            RGB::named(rltk::PURPLE),
+        ),
+        Spawn::PotionOfDetectItems => status_potion(
+            ecs,
+            x,
+            y,
+            "Potion of Detect Items",
+            StatusEffect::DetectItems,
+            40,
+            RGB::named(rltk::GOLD),
+        ),
+        Spawn::PotionOfSpeed => status_potion(
+            ecs,
+            x,
+            y,
+            "Potion of Speed",
+            StatusEffect::Speed,
+            20,
+            RGB::named(rltk::YELLOW),
+        ),
+        Spawn::PotionOfLevitation => status_potion(
+            ecs,
+            x,
+            y,
+            "Potion of Levitation",
+            StatusEffect::Levitation,
+            20,
+            RGB::named(rltk::LIGHT_BLUE),
+        ),
+        Spawn::PotionOfSeeInvisible => status_potion(
+            ecs,
+            x,
+            y,
+            "Potion of See Invisible",
+            StatusEffect::SeeInvisible,
+            50,
+            RGB::named(rltk::WHITE),
+        ),
+        Spawn::FireballScroll => fireball_scroll(ecs, x, y),
+        Spawn::ConfusionScroll => confusion_scroll(ecs, x, y),
+        Spawn::MagicMissileScroll => magic_missile_scroll(ecs, x, y),
+        Spawn::Dagger => dagger(ecs, x, y),
+        Spawn::Shield => shield(ecs, x, y),
+        Spawn::Longsword => longsword(ecs, x, y),
+        Spawn::TowerShield => tower_shield(ecs, x, y),
+        Spawn::Rations => rations(ecs, x, y),
+        Spawn::Bread => bread(ecs, x, y),
+        Spawn::HeartyStew => hearty_stew(ecs, x, y),
+        Spawn::MagicMappingScroll => magic_mapping_scroll(ecs, x, y),
+        Spawn::WandOfMagicMissile => wand_of_magic_missile(ecs, x, y),
+        Spawn::StaffOfFireball => staff_of_fireball(ecs, x, y),
+        Spawn::StaffOfConfusion => staff_of_confusion(ecs, x, y),
+        Spawn::RechargingScroll => recharging_scroll(ecs, x, y),
+        Spawn::TeleportScroll => teleport_scroll(ecs, x, y, TeleportKind::Random),
+        Spawn::BlinkScroll => teleport_scroll(ecs, x, y, TeleportKind::Blink),
+        Spawn::LevelTeleportScroll => teleport_scroll(ecs, x, y, TeleportKind::Level),
+        Spawn::CharmScroll => charm_scroll(ecs, x, y),
+        Spawn::Sack => sack(ecs, x, y),
+        Spawn::SpellbookOfFireball => spellbook(ecs, x, y, SpellKind::Fireball),
+        Spawn::SpellbookOfConfusion => spellbook(ecs, x, y, SpellKind::Confusion),
+        Spawn::SpellbookOfHealing => spellbook(ecs, x, y, SpellKind::Healing),
+        Spawn::AlchemistsNotes => alchemists_notes(ecs, x, y),
+        Spawn::Gold => {
+            let amount = ecs
+                .write_resource::<RandomNumberGenerator>()
+                .roll_dice(2, 10)
+                * map_depth;
+            gold(ecs, x, y, amount)
+        }
+        Spawn::Goblin
+        | Spawn::Orc
+        | Spawn::Shade
+        | Spawn::Imp
+        | Spawn::Necromancer
+        | Spawn::Hermit
+        | Spawn::Pilgrim
+        | Spawn::Brigand
+        | Spawn::Chest
+        | Spawn::TeleportTrap
+        | Spawn::AlchemyStation => return None,
+    };
+
+    // Taken before any enchantment changes the name
+    let kind = ecs.read_storage::<Name>().get(item).map(|name| ItemKind {
+        name: name.name.clone(),
+    });
+    if let Some(kind) = kind {
+        ecs.write_storage::<ItemKind>()
+            .insert(item, kind)
+            .expect("Unable to insert item kind");
+    }
+    if matches!(
+        spawn,
+        Spawn::Dagger | Spawn::Shield | Spawn::Longsword | Spawn::TowerShield
+    ) {
+        magic_items::roll_magic_item(ecs, item, map_depth);
+    }
+
+    if let Some(gold) = base_value(spawn) {
+        let bonus = ecs
+            .read_storage::<MagicItem>()
+            .get(item)
+            .map_or(0, magic_items::value_bonus);
+        ecs.write_storage::<Value>()
+            .insert(
+                item,
+                Value {
+                    gold: i32::max(1, gold + bonus),
+                },
+            )
+            .expect("Unable to insert value");
+    }
+    Some(item)
 }
-fn health_potion(ecs: &mut World, x: i32, y: i32) {
+
+/// What a shopkeeper asks for each kind of item, before any enchantment.
+fn base_value(spawn: Spawn) -> Option<i32> {
+    let gold = match spawn {
+        Spawn::HealthPotion => 20,
+        Spawn::PotionOfStrength
+        | Spawn::PotionOfTelepathy
+        | Spawn::PotionOfDetectItems
+        | Spawn::PotionOfLevitation => 30,
+        Spawn::PotionOfSpeed | Spawn::PotionOfSeeInvisible => 40,
+        Spawn::MagicMissileScroll | Spawn::ConfusionScroll | Spawn::BlinkScroll => 25,
+        Spawn::FireballScroll
+        | Spawn::MagicMappingScroll
+        | Spawn::TeleportScroll
+        | Spawn::CharmScroll => 40,
+        Spawn::RechargingScroll | Spawn::LevelTeleportScroll => 60,
+        Spawn::Dagger => 15,
+        Spawn::Shield => 20,
+        Spawn::Longsword => 50,
+        Spawn::TowerShield => 60,
+        Spawn::Rations => 10,
+        Spawn::Bread => 5,
+        Spawn::HeartyStew => 20,
+        Spawn::WandOfMagicMissile => 60,
+        Spawn::StaffOfConfusion => 80,
+        Spawn::StaffOfFireball => 120,
+        Spawn::Sack => 15,
+        Spawn::SpellbookOfHealing => 80,
+        Spawn::SpellbookOfConfusion => 100,
+        Spawn::SpellbookOfFireball => 150,
+        Spawn::AlchemistsNotes => 50,
+        Spawn::Gold
+        | Spawn::Goblin
+        | Spawn::Orc
+        | Spawn::Shade
+        | Spawn::Imp
+        | Spawn::Necromancer
+        | Spawn::Hermit
+        | Spawn::Pilgrim
+        | Spawn::Brigand
+        | Spawn::Chest
+        | Spawn::TeleportTrap
+        | Spawn::AlchemyStation => return None,
+    };
+    Some(gold)
+}
+
+fn chest_table(map_depth: i32) -> RandomTable<Spawn> {
+    RandomTable::new()
+        .add_table(potion_table(), 7)
+        .add_table(scroll_table(map_depth), 10 + 2 * map_depth)
+        .add_table(wand_table(map_depth), 1 + map_depth)
+        .add_table(equipment_table(map_depth), 6 + 2 * map_depth)
+        .add_table(spellbook_table(), 2 + map_depth / 2)
+        .add(Spawn::Rations, 5)
+}
+
+/// Spawns a chest and fills it with a few items from the chest table.
+fn chest(ecs: &mut World, x: i32, y: i32, map_depth: i32) {
+    let chest = ecs
+        .create_entity()
+        .with(Position { x, y })
+        .with(Renderable {
+            glyph: rltk::to_cp437('■'),
+            fg: RGB::named(rltk::BROWN1),
+            bg: RGB::named(rltk::BLACK),
+            render_order: 2,
+        })
+        .with(Name {
+            name: "Chest".to_string(),
+        })
+        .with(Container {})
+        .marked::<SimpleMarker<SerializeMe>>()
+        .build();
+
+    let contents: Vec<Spawn>;
+    {
+        let mut rng = ecs.write_resource::<RandomNumberGenerator>();
+        let table = chest_table(map_depth);
+        let count = rng.roll_dice(1, 3);
+        contents = (0..count)
+            .filter_map(|_| table.roll(&mut rng, map_depth))
+            .collect();
+    }
+
+    for spawn in contents {
+        if let Some(item) = spawn_item(ecs, spawn, x, y, map_depth) {
+            ecs.write_storage::<Position>().remove(item);
+            ecs.write_storage::<InContainer>()
+                .insert(item, InContainer { container: chest })
+                .expect("Unable to insert item into chest");
+        }
+    }
+}
+
+fn health_potion(ecs: &mut World, x: i32, y: i32) -> Entity {
     ecs.create_entity()
         .with(Position { x, y })
         .with(Renderable {
@@ -156,13 +942,48 @@ fn health_potion(ecs: &mut World, x: i32, y: i32) {
             name: "Health Potion".to_string(),
         })
         .with(Item {})
+        .with(ItemClass {
+            category: ItemCategory::Potion,
+        })
+        .with(Weight { lbs: 1.0 })
         .with(Consumable {})
         .with(ProvidesHealing { heal_amount: 8 })
         .marked::<SimpleMarker<SerializeMe>>()
-        .build();
+        .build()
 }
 
-fn fireball_scroll(ecs: &mut World, x: i32, y: i32) {
+fn status_potion(
+    ecs: &mut World,
+    x: i32,
+    y: i32,
+    name: &str,
+    effect: StatusEffect,
+    turns: i32,
+    colour: RGB,
+) -> Entity {
+    ecs.create_entity()
+        .with(Position { x, y })
+        .with(Renderable {
+            glyph: rltk::to_cp437('¡'),
+            fg: colour,
+            bg: RGB::named(rltk::BLACK),
+            render_order: 2,
+        })
+        .with(Name {
+            name: name.to_string(),
+        })
+        .with(Item {})
+        .with(ItemClass {
+            category: ItemCategory::Potion,
+        })
+        .with(Weight { lbs: 1.0 })
+        .with(Consumable {})
+        .with(ProvidesStatus { effect, turns })
+        .marked::<SimpleMarker<SerializeMe>>()
+        .build()
+}
+
+fn fireball_scroll(ecs: &mut World, x: i32, y: i32) -> Entity {
     ecs.create_entity()
         .with(Position { x, y })
         .with(Renderable {
@@ -175,15 +996,19 @@ fn fireball_scroll(ecs: &mut World, x: i32, y: i32) {
             name: "Fireball Scroll".to_string(),
         })
         .with(Item {})
+        .with(ItemClass {
+            category: ItemCategory::Scroll,
+        })
+        .with(Weight { lbs: 0.5 })
         .with(Consumable {})
         .with(Ranged { range: 6 })
         .with(InflictsDamage { damage: 20 })
         .with(AreaOfEffect { radius: 3 })
         .marked::<SimpleMarker<SerializeMe>>()
-        .build();
+        .build()
 }
 
-fn magic_missile_scroll(ecs: &mut World, x: i32, y: i32) {
+fn magic_missile_scroll(ecs: &mut World, x: i32, y: i32) -> Entity {
     ecs.create_entity()
         .with(Position { x, y })
         .with(Renderable {
@@ -196,14 +1021,18 @@ fn magic_missile_scroll(ecs: &mut World, x: i32, y: i32) {
             name: "Magic Missile Scroll".to_string(),
         })
         .with(Item {})
+        .with(ItemClass {
+            category: ItemCategory::Scroll,
+        })
+        .with(Weight { lbs: 0.5 })
         .with(Consumable {})
         .with(Ranged { range: 6 })
         .with(InflictsDamage { damage: 8 })
         .marked::<SimpleMarker<SerializeMe>>()
-        .build();
+        .build()
 }
 
-fn confusion_scroll(ecs: &mut World, x: i32, y: i32) {
+fn confusion_scroll(ecs: &mut World, x: i32, y: i32) -> Entity {
     ecs.create_entity()
         .with(Position { x, y })
         .with(Renderable {
@@ -216,14 +1045,42 @@ fn confusion_scroll(ecs: &mut World, x: i32, y: i32) {
             name: "Confusion Scroll".to_string(),
         })
         .with(Item {})
+        .with(ItemClass {
+            category: ItemCategory::Scroll,
+        })
+        .with(Weight { lbs: 0.5 })
         .with(Consumable {})
         .with(Ranged { range: 6 })
         .with(Confusion { turns: 4 })
         .marked::<SimpleMarker<SerializeMe>>()
-        .build();
+        .build()
+}
+
+fn charm_scroll(ecs: &mut World, x: i32, y: i32) -> Entity {
+    ecs.create_entity()
+        .with(Position { x, y })
+        .with(Renderable {
+            glyph: rltk::to_cp437(')'),
+            fg: RGB::named(rltk::LIGHT_PINK),
+            bg: RGB::named(rltk::BLACK),
+            render_order: 2,
+        })
+        .with(Name {
+            name: "Scroll of Charm Monster".to_string(),
+        })
+        .with(Item {})
+        .with(ItemClass {
+            category: ItemCategory::Scroll,
+        })
+        .with(Weight { lbs: 0.5 })
+        .with(Consumable {})
+        .with(Ranged { range: 6 })
+        .with(Charms {})
+        .marked::<SimpleMarker<SerializeMe>>()
+        .build()
 }
 
-fn dagger(ecs: &mut World, x: i32, y: i32) {
+fn dagger(ecs: &mut World, x: i32, y: i32) -> Entity {
     ecs.create_entity()
         .with(Position { x, y })
         .with(Renderable {
@@ -236,15 +1093,19 @@ fn dagger(ecs: &mut World, x: i32, y: i32) {
             name: "Dagger".to_string(),
         })
         .with(Item {})
+        .with(ItemClass {
+            category: ItemCategory::Weapon,
+        })
+        .with(Weight { lbs: 1.0 })
         .marked::<SimpleMarker<SerializeMe>>()
         .with(Equippable {
             slot: EquipmentSlot::Melee,
         })
         .with(MeleePowerBonus { power: 2 })
-        .build();
+        .build()
 }
 
-fn shield(ecs: &mut World, x: i32, y: i32) {
+fn shield(ecs: &mut World, x: i32, y: i32) -> Entity {
     ecs.create_entity()
         .with(Position { x, y })
         .with(Renderable {
@@ -257,15 +1118,19 @@ fn shield(ecs: &mut World, x: i32, y: i32) {
             name: "Shield".to_string(),
         })
         .with(Item {})
+        .with(ItemClass {
+            category: ItemCategory::Armour,
+        })
+        .with(Weight { lbs: 5.0 })
         .with(Equippable {
             slot: EquipmentSlot::Shield,
         })
         .with(DefenseBonus { defense: 1 })
         .marked::<SimpleMarker<SerializeMe>>()
-        .build();
+        .build()
 }
 
-fn longsword(ecs: &mut World, x: i32, y: i32) {
+fn longsword(ecs: &mut World, x: i32, y: i32) -> Entity {
     ecs.create_entity()
         .with(Position { x, y })
         .with(Renderable {
@@ -278,15 +1143,19 @@ fn longsword(ecs: &mut World, x: i32, y: i32) {
             name: "Longsword".to_string(),
         })
         .with(Item {})
+        .with(ItemClass {
+            category: ItemCategory::Weapon,
+        })
+        .with(Weight { lbs: 3.0 })
         .with(Equippable {
             slot: EquipmentSlot::Melee,
         })
         .with(MeleePowerBonus { power: 4 })
         .marked::<SimpleMarker<SerializeMe>>()
-        .build();
+        .build()
 }
 
-fn tower_shield(ecs: &mut World, x: i32, y: i32) {
+fn tower_shield(ecs: &mut World, x: i32, y: i32) -> Entity {
     ecs.create_entity()
         .with(Position { x, y })
         .with(Renderable {
@@ -299,15 +1168,19 @@ fn tower_shield(ecs: &mut World, x: i32, y: i32) {
             name: "Tower Shield".to_string(),
         })
         .with(Item {})
+        .with(ItemClass {
+            category: ItemCategory::Armour,
+        })
+        .with(Weight { lbs: 15.0 })
         .with(Equippable {
             slot: EquipmentSlot::Shield,
         })
         .with(DefenseBonus { defense: 3 })
         .marked::<SimpleMarker<SerializeMe>>()
-        .build();
+        .build()
 }
 
-fn rations(ecs: &mut World, x: i32, y: i32) {
+fn rations(ecs: &mut World, x: i32, y: i32) -> Entity {
     ecs.create_entity()
         .with(Position { x, y })
         .with(Renderable {
@@ -320,13 +1193,67 @@ fn rations(ecs: &mut World, x: i32, y: i32) {
             name: "Rations".to_string(),
         })
         .with(Item {})
-        .with(ProvidesFood {})
+        .with(ItemClass {
+            category: ItemCategory::Food,
+        })
+        .with(Weight { lbs: 2.0 })
+        .with(ProvidesFood { nutrition: 400 })
         .with(Consumable {})
         .marked::<SimpleMarker<SerializeMe>>()
-        .build();
+        .build()
+}
+
+/// Tastier than rations, but goes stale and then rotten.
+fn bread(ecs: &mut World, x: i32, y: i32) -> Entity {
+    ecs.create_entity()
+        .with(Position { x, y })
+        .with(Renderable {
+            glyph: rltk::to_cp437('%'),
+            fg: RGB::named(rltk::WHEAT),
+            bg: RGB::named(rltk::BLACK),
+            render_order: 2,
+        })
+        .with(Name {
+            name: "Loaf of Bread".to_string(),
+        })
+        .with(Item {})
+        .with(ItemClass {
+            category: ItemCategory::Food,
+        })
+        .with(Weight { lbs: 1.0 })
+        .with(ProvidesFood { nutrition: 250 })
+        .with(Perishable { freshness: 400 })
+        .with(Consumable {})
+        .marked::<SimpleMarker<SerializeMe>>()
+        .build()
+}
+
+/// Only made by cooking, not found lying around.
+fn hearty_stew(ecs: &mut World, x: i32, y: i32) -> Entity {
+    ecs.create_entity()
+        .with(Position { x, y })
+        .with(Renderable {
+            glyph: rltk::to_cp437('%'),
+            fg: RGB::named(rltk::ORANGE),
+            bg: RGB::named(rltk::BLACK),
+            render_order: 2,
+        })
+        .with(Name {
+            name: "Hearty Stew".to_string(),
+        })
+        .with(Item {})
+        .with(ItemClass {
+            category: ItemCategory::Food,
+        })
+        .with(Weight { lbs: 2.0 })
+        .with(ProvidesFood { nutrition: 600 })
+        .with(Perishable { freshness: 300 })
+        .with(Consumable {})
+        .marked::<SimpleMarker<SerializeMe>>()
+        .build()
 }
 
-fn magic_mapping_scroll(ecs: &mut World, x: i32, y: i32) {
+fn magic_mapping_scroll(ecs: &mut World, x: i32, y: i32) -> Entity {
     ecs.create_entity()
         .with(Position { x, y })
         .with(Renderable {
@@ -339,8 +1266,255 @@ fn magic_mapping_scroll(ecs: &mut World, x: i32, y: i32) {
             name: "Scroll of Magic Mapping".to_string(),
         })
         .with(Item {})
+        .with(ItemClass {
+            category: ItemCategory::Scroll,
+        })
+        .with(Weight { lbs: 0.5 })
         .with(MagicMapper {})
         .with(Consumable {})
         .marked::<SimpleMarker<SerializeMe>>()
-        .build();
+        .build()
+}
+
+fn teleport_scroll(ecs: &mut World, x: i32, y: i32, kind: TeleportKind) -> Entity {
+    let (name, colour) = match kind {
+        TeleportKind::Random => ("Scroll of Teleportation", RGB::named(rltk::PURPLE)),
+        TeleportKind::Blink => ("Scroll of Blinking", RGB::named(rltk::VIOLET)),
+        TeleportKind::Level => ("Scroll of Level Teleport", RGB::named(rltk::DARK_VIOLET)),
+    };
+    let mut builder = ecs
+        .create_entity()
+        .with(Position { x, y })
+        .with(Renderable {
+            glyph: rltk::to_cp437(')'),
+            fg: colour,
+            bg: RGB::named(rltk::BLACK),
+            render_order: 2,
+        })
+        .with(Name {
+            name: name.to_string(),
+        })
+        .with(Item {})
+        .with(ItemClass {
+            category: ItemCategory::Scroll,
+        })
+        .with(Weight { lbs: 0.5 })
+        .with(Consumable {})
+        .with(Teleports { kind });
+    if kind == TeleportKind::Blink {
+        builder = builder.with(Ranged { range: 8 });
+    }
+    builder.marked::<SimpleMarker<SerializeMe>>().build()
+}
+
+fn wand_of_magic_missile(ecs: &mut World, x: i32, y: i32) -> Entity {
+    ecs.create_entity()
+        .with(Position { x, y })
+        .with(Renderable {
+            glyph: rltk::to_cp437('-'),
+            fg: RGB::named(rltk::CYAN),
+            bg: RGB::named(rltk::BLACK),
+            render_order: 2,
+        })
+        .with(Name {
+            name: "Wand of Magic Missile".to_string(),
+        })
+        .with(Item {})
+        .with(ItemClass {
+            category: ItemCategory::Wand,
+        })
+        .with(Weight { lbs: 1.0 })
+        .with(Charges { current: 5, max: 5 })
+        .with(Ranged { range: 6 })
+        .with(InflictsDamage { damage: 8 })
+        .marked::<SimpleMarker<SerializeMe>>()
+        .build()
+}
+
+fn staff_of_fireball(ecs: &mut World, x: i32, y: i32) -> Entity {
+    ecs.create_entity()
+        .with(Position { x, y })
+        .with(Renderable {
+            glyph: rltk::to_cp437('|'),
+            fg: RGB::named(rltk::ORANGE),
+            bg: RGB::named(rltk::BLACK),
+            render_order: 2,
+        })
+        .with(Name {
+            name: "Staff of Fireball".to_string(),
+        })
+        .with(Item {})
+        .with(ItemClass {
+            category: ItemCategory::Wand,
+        })
+        .with(Weight { lbs: 4.0 })
+        .with(Charges { current: 3, max: 3 })
+        .with(Ranged { range: 6 })
+        .with(InflictsDamage { damage: 20 })
+        .with(AreaOfEffect { radius: 3 })
+        .marked::<SimpleMarker<SerializeMe>>()
+        .build()
+}
+
+fn staff_of_confusion(ecs: &mut World, x: i32, y: i32) -> Entity {
+    ecs.create_entity()
+        .with(Position { x, y })
+        .with(Renderable {
+            glyph: rltk::to_cp437('|'),
+            fg: RGB::named(rltk::PINK),
+            bg: RGB::named(rltk::BLACK),
+            render_order: 2,
+        })
+        .with(Name {
+            name: "Staff of Confusion".to_string(),
+        })
+        .with(Item {})
+        .with(ItemClass {
+            category: ItemCategory::Wand,
+        })
+        .with(Weight { lbs: 4.0 })
+        .with(Charges { current: 4, max: 4 })
+        .with(Ranged { range: 6 })
+        .with(Confusion { turns: 4 })
+        .marked::<SimpleMarker<SerializeMe>>()
+        .build()
+}
+
+fn recharging_scroll(ecs: &mut World, x: i32, y: i32) -> Entity {
+    ecs.create_entity()
+        .with(Position { x, y })
+        .with(Renderable {
+            glyph: rltk::to_cp437(')'),
+            fg: RGB::named(rltk::GOLD),
+            bg: RGB::named(rltk::BLACK),
+            render_order: 2,
+        })
+        .with(Name {
+            name: "Scroll of Recharging".to_string(),
+        })
+        .with(Item {})
+        .with(ItemClass {
+            category: ItemCategory::Scroll,
+        })
+        .with(Weight { lbs: 0.5 })
+        .with(Recharger {})
+        .with(Consumable {})
+        .marked::<SimpleMarker<SerializeMe>>()
+        .build()
+}
+
+fn sack(ecs: &mut World, x: i32, y: i32) -> Entity {
+    ecs.create_entity()
+        .with(Position { x, y })
+        .with(Renderable {
+            glyph: rltk::to_cp437('&'),
+            fg: RGB::named(rltk::BROWN1),
+            bg: RGB::named(rltk::BLACK),
+            render_order: 2,
+        })
+        .with(Name {
+            name: "Sack".to_string(),
+        })
+        .with(Item {})
+        .with(ItemClass {
+            category: ItemCategory::Container,
+        })
+        .with(Weight { lbs: 1.0 })
+        .with(Container {})
+        .marked::<SimpleMarker<SerializeMe>>()
+        .build()
+}
+
+fn spellbook(ecs: &mut World, x: i32, y: i32, spell: SpellKind) -> Entity {
+    ecs.create_entity()
+        .with(Position { x, y })
+        .with(Renderable {
+            glyph: rltk::to_cp437('+'),
+            fg: RGB::named(rltk::LIGHT_BLUE),
+            bg: RGB::named(rltk::BLACK),
+            render_order: 2,
+        })
+        .with(Name {
+            name: format!("Spellbook of {}", spell_system::spell_name(spell)),
+        })
+        .with(Item {})
+        .with(ItemClass {
+            category: ItemCategory::Book,
+        })
+        .with(Weight { lbs: 2.0 })
+        .with(TeachesSpell { spell })
+        .with(Consumable {})
+        .marked::<SimpleMarker<SerializeMe>>()
+        .build()
+}
+
+/// Notes teaching one of the recipes that isn't known from the start.
+fn alchemists_notes(ecs: &mut World, x: i32, y: i32) -> Entity {
+    let recipe;
+    {
+        let book = ecs.fetch::<RecipeBook>();
+        let mut rng = ecs.write_resource::<RandomNumberGenerator>();
+        let unknown: Vec<&String> = book
+            .recipes
+            .iter()
+            .filter(|recipe| !recipe.known)
+            .map(|recipe| &recipe.name)
+            .collect();
+        recipe = if unknown.is_empty() {
+            book.recipes[0].name.clone()
+        } else {
+            unknown[rng.roll_dice(1, unknown.len() as i32) as usize - 1].clone()
+        };
+    }
+
+    ecs.create_entity()
+        .with(Position { x, y })
+        .with(Renderable {
+            glyph: rltk::to_cp437('?'),
+            fg: RGB::named(rltk::LIGHT_GREEN),
+            bg: RGB::named(rltk::BLACK),
+            render_order: 2,
+        })
+        .with(Name {
+            name: "Alchemist's Notes".to_string(),
+        })
+        .with(Item {})
+        .with(ItemClass {
+            category: ItemCategory::Book,
+        })
+        .with(Weight { lbs: 0.5 })
+        .with(TeachesRecipe { recipe })
+        .with(Consumable {})
+        .marked::<SimpleMarker<SerializeMe>>()
+        .build()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::component_registry::register_components;
+    use specs::saveload::SimpleMarkerAllocator;
+
+    #[test]
+    fn guaranteed_spawns_fit_on_a_one_room_level() {
+        let mut ecs = World::new();
+        register_components(&mut ecs);
+        ecs.insert(SimpleMarkerAllocator::<SerializeMe>::new());
+        ecs.insert(RandomNumberGenerator::seeded(1));
+        let mut map = Map::default();
+        let room = Rect::new(10, 10, 6, 4);
+        map.rooms.push(room);
+
+        spawn_level(&mut ecs, &map, 1);
+        let positions = ecs.read_storage::<Position>();
+        let names = ecs.read_storage::<Name>();
+        let rations: Vec<&Position> = (&positions, &names)
+            .join()
+            .filter(|(_, name)| name.name == "Rations")
+            .map(|(pos, _)| pos)
+            .collect();
+        assert_eq!(rations.len(), 1);
+        assert!(rations[0].x > room.x1 && rations[0].x <= room.x2);
+        assert!(rations[0].y > room.y1 && rations[0].y <= room.y2);
+    }
 }
@@ -0,0 +1,81 @@
+use super::{components::*, RunState};
+use specs::prelude::*;
+use specs::saveload::{MarkedBuilder, SimpleMarker};
+
+/// Player turns it takes to regain one point of mana.
+const MANA_REGEN_TURNS: i32 = 4;
+
+pub fn spell_name(kind: SpellKind) -> &'static str {
+    match kind {
+        SpellKind::MagicMissile => "Magic Missile",
+        SpellKind::Fireball => "Fireball",
+        SpellKind::Confusion => "Confusion",
+        SpellKind::Healing => "Healing",
+    }
+}
+
+/// Builds a spell known by `owner`. Spells carry the same effect components as scrolls,
+/// so casting one is just using it through `ItemUseSystem`.
+pub fn build_spell<B: Builder + MarkedBuilder>(
+    builder: B,
+    kind: SpellKind,
+    owner: Entity,
+) -> Entity {
+    let builder = builder
+        .with(Name {
+            name: spell_name(kind).to_string(),
+        })
+        .with(KnownSpell { owner });
+
+    let builder = match kind {
+        SpellKind::MagicMissile => builder
+            .with(Spell { kind, mana_cost: 2 })
+            .with(Ranged { range: 6 })
+            .with(InflictsDamage { damage: 6 }),
+        SpellKind::Fireball => builder
+            .with(Spell { kind, mana_cost: 6 })
+            .with(Ranged { range: 6 })
+            .with(InflictsDamage { damage: 15 })
+            .with(AreaOfEffect { radius: 2 }),
+        SpellKind::Confusion => builder
+            .with(Spell { kind, mana_cost: 3 })
+            .with(Ranged { range: 6 })
+            .with(Confusion { turns: 4 }),
+        SpellKind::Healing => builder
+            .with(Spell { kind, mana_cost: 4 })
+            .with(ProvidesHealing { heal_amount: 6 }),
+    };
+
+    builder.marked::<SimpleMarker<SerializeMe>>().build()
+}
+
+pub struct ManaRegenSystem;
+
+impl<'a> System<'a> for ManaRegenSystem {
+    type SystemData = (
+        Entities<'a>,
+        WriteStorage<'a, Mana>,
+        ReadExpect<'a, Entity>,
+        ReadExpect<'a, RunState>,
+    );
+
+    fn run(&mut self, data: Self::SystemData) {
+        let (entities, mut mana, player_entity, runstate) = data;
+
+        if *runstate != RunState::PlayerTurn {
+            return;
+        }
+
+        for (entity, pool) in (&entities, &mut mana).join() {
+            if entity != *player_entity || pool.current >= pool.max {
+                continue;
+            }
+
+            pool.regen_timer += 1;
+            if pool.regen_timer >= MANA_REGEN_TURNS {
+                pool.regen_timer = 0;
+                pool.current += 1;
+            }
+        }
+    }
+}
@@ -0,0 +1,124 @@
+use super::{game_log::GameLog, DamageSource, RunState, StatusEffect, StatusEffects, SufferDamage};
+use specs::prelude::*;
+
+/// Extra melee power (and carrying strength) granted by a potion of strength.
+pub const STRENGTH_BONUS: i32 = 3;
+
+pub fn status_name(effect: StatusEffect) -> &'static str {
+    match effect {
+        StatusEffect::Strength => "Strong",
+        StatusEffect::Telepathy => "Telepathic",
+        StatusEffect::DetectItems => "Item Sense",
+        StatusEffect::Speed => "Fast",
+        StatusEffect::Levitation => "Levitating",
+        StatusEffect::SeeInvisible => "See Invisible",
+        StatusEffect::Sick => "Sick",
+    }
+}
+
+pub fn drink_message(effect: StatusEffect) -> &'static str {
+    match effect {
+        StatusEffect::Strength => "You feel mighty!",
+        StatusEffect::Telepathy => "You sense the minds around you.",
+        StatusEffect::DetectItems => "You sense treasure nearby.",
+        StatusEffect::Speed => "You feel quick!",
+        StatusEffect::Levitation => "You float into the air.",
+        StatusEffect::SeeInvisible => "Your eyes tingle.",
+        StatusEffect::Sick => "You feel queasy.",
+    }
+}
+
+fn expiry_message(effect: StatusEffect) -> &'static str {
+    match effect {
+        StatusEffect::Strength => "You feel weaker.",
+        StatusEffect::Telepathy => "Your mind goes quiet.",
+        StatusEffect::DetectItems => "You lose your sense for treasure.",
+        StatusEffect::Speed => "You slow down.",
+        StatusEffect::Levitation => "You float gently to the floor.",
+        StatusEffect::SeeInvisible => "Your vision returns to normal.",
+        StatusEffect::Sick => "You feel better.",
+    }
+}
+
+pub fn strength_bonus(status: Option<&StatusEffects>) -> i32 {
+    match status {
+        Some(status) if status.has(StatusEffect::Strength) => STRENGTH_BONUS,
+        _ => 0,
+    }
+}
+
+pub fn player_has_status(ecs: &World, effect: StatusEffect) -> bool {
+    let player_entity = ecs.fetch::<Entity>();
+    ecs.read_storage::<StatusEffects>()
+        .get(*player_entity)
+        .is_some_and(|status| status.has(effect))
+}
+
+/// A hasted player acts twice for every monster turn: on every other turn of the
+/// effect the monsters are skipped.
+pub fn is_hasted_turn(ecs: &World) -> bool {
+    let player_entity = ecs.fetch::<Entity>();
+    ecs.read_storage::<StatusEffects>()
+        .get(*player_entity)
+        .is_some_and(|status| {
+            status.has(StatusEffect::Speed) && status.turns_left(StatusEffect::Speed) % 2 == 0
+        })
+}
+
+pub struct StatusEffectSystem;
+
+impl<'a> System<'a> for StatusEffectSystem {
+    type SystemData = (
+        Entities<'a>,
+        WriteStorage<'a, StatusEffects>,
+        ReadExpect<'a, Entity>,
+        ReadExpect<'a, RunState>,
+        WriteExpect<'a, GameLog>,
+        WriteStorage<'a, SufferDamage>,
+    );
+
+    fn run(&mut self, data: Self::SystemData) {
+        let (entities, mut statuses, player_entity, runstate, mut log, mut inflict_damage) = data;
+
+        let mut finished = Vec::new();
+        for (entity, status) in (&entities, &mut statuses).join() {
+            let proceed = match *runstate {
+                RunState::PlayerTurn => entity == *player_entity,
+                RunState::MonsterTurn => entity != *player_entity,
+                _ => false,
+            };
+            if !proceed {
+                continue;
+            }
+
+            // Sickness wears you down every other turn
+            if status.has(StatusEffect::Sick) && status.turns_left(StatusEffect::Sick) % 2 == 0 {
+                inflict_damage
+                    .insert(
+                        entity,
+                        SufferDamage {
+                            amount: 1,
+                            source: DamageSource::Sickness,
+                        },
+                    )
+                    .expect("Unable to do damage");
+            }
+
+            for effect in status.active.iter_mut() {
+                effect.turns -= 1;
+                if effect.turns < 1 && entity == *player_entity {
+                    log.entries
+                        .insert(0, expiry_message(effect.effect).to_string());
+                }
+            }
+            status.active.retain(|effect| effect.turns > 0);
+            if status.active.is_empty() {
+                finished.push(entity);
+            }
+        }
+
+        for entity in finished {
+            statuses.remove(entity);
+        }
+    }
+}
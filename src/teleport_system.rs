@@ -0,0 +1,197 @@
+use super::{
+    game_log::GameLog, BlocksTile, CombatStats, Map, Position, RunState, StatusEffect,
+    StatusEffects, TeleportDestination, TeleportKind, Teleports, TileType, Trap, Viewshed,
+    WantsToTeleport,
+};
+use rltk::{Point, RandomNumberGenerator};
+use specs::prelude::*;
+
+/// Whether something could be teleported onto this tile.
+pub fn is_open_tile(map: &Map, x: i32, y: i32) -> bool {
+    if x < 1 || x > map.width - 2 || y < 1 || y > map.height - 2 {
+        return false;
+    }
+    let idx = map.xy_idx(x, y);
+    map.tiles[idx] != TileType::Wall && !map.blocked[idx]
+}
+
+fn random_open_tile(
+    map: &Map,
+    rng: &mut RandomNumberGenerator,
+    player_pos: Point,
+) -> Option<(i32, i32)> {
+    for _try in 0..1000 {
+        let x = rng.range(1, map.width - 1);
+        let y = rng.range(1, map.height - 1);
+        if is_open_tile(map, x, y) && (x != player_pos.x || y != player_pos.y) {
+            return Some((x, y));
+        }
+    }
+    None
+}
+
+pub struct TeleportSystem;
+
+impl<'a> System<'a> for TeleportSystem {
+    #[allow(clippy::type_complexity)]
+    type SystemData = (
+        WriteExpect<'a, Map>,
+        WriteExpect<'a, Point>,
+        ReadExpect<'a, Entity>,
+        WriteExpect<'a, RunState>,
+        WriteExpect<'a, RandomNumberGenerator>,
+        WriteExpect<'a, GameLog>,
+        Entities<'a>,
+        WriteStorage<'a, WantsToTeleport>,
+        WriteStorage<'a, Position>,
+        WriteStorage<'a, Viewshed>,
+        ReadStorage<'a, BlocksTile>,
+        ReadStorage<'a, Teleports>,
+        ReadStorage<'a, Trap>,
+        ReadStorage<'a, CombatStats>,
+        ReadStorage<'a, StatusEffects>,
+    );
+
+    fn run(&mut self, data: Self::SystemData) {
+        let (
+            mut map,
+            mut player_pos,
+            player_entity,
+            mut runstate,
+            mut rng,
+            mut log,
+            entities,
+            mut wants_teleport,
+            mut positions,
+            mut viewsheds,
+            blockers,
+            teleports,
+            traps,
+            combat_stats,
+            statuses,
+        ) = data;
+
+        // Anything standing on a teleport trap is sent away, unless it floats above it
+        if *runstate == RunState::PlayerTurn || *runstate == RunState::MonsterTurn {
+            let mut triggered = Vec::new();
+            for (_trap, trap_pos, teleport) in (&traps, &positions, &teleports).join() {
+                for (entity, pos, _stats) in (&entities, &positions, &combat_stats).join() {
+                    let levitating = matches!(
+                        statuses.get(entity),
+                        Some(status) if status.has(StatusEffect::Levitation)
+                    );
+                    if pos.x == trap_pos.x && pos.y == trap_pos.y && !levitating {
+                        triggered.push((entity, teleport.kind));
+                    }
+                }
+            }
+            for (entity, kind) in triggered {
+                let destination = match kind {
+                    TeleportKind::Level => TeleportDestination::Level,
+                    _ => TeleportDestination::Random,
+                };
+                if entity == *player_entity {
+                    log.entries
+                        .insert(0, "You step onto a teleport trap!".to_string());
+                }
+                wants_teleport
+                    .insert(entity, WantsToTeleport { destination })
+                    .expect("Unable to insert teleport");
+            }
+        }
+
+        for (entity, wants) in (&entities, &wants_teleport).join() {
+            let is_player = entity == *player_entity;
+            let (x, y) = match wants.destination {
+                TeleportDestination::Tile { x, y } => {
+                    if !is_open_tile(&map, x, y) {
+                        continue;
+                    }
+                    (x, y)
+                }
+                TeleportDestination::Random => {
+                    match random_open_tile(&map, &mut rng, *player_pos) {
+                        Some(tile) => tile,
+                        None => continue,
+                    }
+                }
+                TeleportDestination::Level => {
+                    // Only the player can leave the level; the main loop builds the new one
+                    if is_player {
+                        let mut depth = map.depth;
+                        while depth == map.depth {
+                            depth = rng.range(1, map.depth + 4);
+                        }
+                        *runstate = RunState::LevelTeleport { depth };
+                    }
+                    continue;
+                }
+            };
+
+            if let Some(pos) = positions.get_mut(entity) {
+                if blockers.get(entity).is_some() {
+                    let old_idx = map.xy_idx(pos.x, pos.y);
+                    map.blocked[old_idx] = false;
+                    let new_idx = map.xy_idx(x, y);
+                    map.blocked[new_idx] = true;
+                }
+                pos.x = x;
+                pos.y = y;
+            }
+            if let Some(viewshed) = viewsheds.get_mut(entity) {
+                viewshed.dirty = true;
+            }
+            if is_player {
+                *player_pos = Point::new(x, y);
+                log.entries.insert(0, "Space folds around you.".to_string());
+            }
+        }
+
+        wants_teleport.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::component_registry::register_components;
+    use crate::spawner::{self, Spawn};
+    use crate::SerializeMe;
+    use specs::saveload::SimpleMarkerAllocator;
+
+    #[test]
+    fn levitating_over_a_teleport_trap() {
+        let mut ecs = World::new();
+        register_components(&mut ecs);
+        ecs.insert(SimpleMarkerAllocator::<SerializeMe>::new());
+        ecs.insert(RandomNumberGenerator::seeded(1));
+        ecs.insert(GameLog {
+            entries: Vec::new(),
+        });
+        ecs.insert(RunState::PlayerTurn);
+        let map = Map::new_map_rooms_and_corridors(1);
+        let (x, y) = map.rooms[0].center();
+        ecs.insert(map);
+        ecs.insert(Point::new(x, y));
+        let player = spawner::player(&mut ecs, x, y);
+        ecs.insert(player);
+        spawner::spawn_entity(&mut ecs, Spawn::TeleportTrap, x, y, 1);
+
+        let mut status = StatusEffects::default();
+        status.add(StatusEffect::Levitation, 10);
+        ecs.write_storage::<StatusEffects>()
+            .insert(player, status)
+            .unwrap();
+        TeleportSystem.run_now(&ecs);
+        assert!(ecs.fetch::<GameLog>().entries.is_empty());
+        assert_eq!(*ecs.fetch::<Point>(), Point::new(x, y));
+
+        // Once back on the floor, the trap goes off
+        ecs.write_storage::<StatusEffects>().remove(player);
+        TeleportSystem.run_now(&ecs);
+        assert!(ecs
+            .fetch::<GameLog>()
+            .entries
+            .contains(&"You step onto a teleport trap!".to_string()));
+    }
+}